@@ -0,0 +1,83 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{Data, DeriveInput, Fields, LitStr, parse_macro_input};
+
+/// Derives `betrayer::TraySignal` for an enum with only unit variants.
+///
+/// Every variant becomes a button in the generated menu. The following attributes can be used to customize the items:
+/// * `#[tray(label = "...")]` sets the label of the button (defaults to the name of the variant)
+/// * `#[tray(separator_before)]` / `#[tray(separator_after)]` insert a separator before/after the button
+/// * `#[tray(skip)]` excludes the variant from the menu
+#[proc_macro_derive(TraySignal, attributes(tray))]
+pub fn derive_tray_signal(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[derive(Default)]
+struct ItemOptions {
+    label: Option<String>,
+    separator_before: bool,
+    separator_after: bool,
+    skip: bool
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Enum(data) = &input.data else {
+        return Err(syn::Error::new_spanned(&input.ident, "TraySignal can only be derived for enums"));
+    };
+
+    let mut items = Vec::new();
+    for variant in &data.variants {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(syn::Error::new_spanned(variant, "TraySignal only supports unit variants"));
+        }
+        let options = parse_options(&variant.attrs)?;
+        if options.skip {
+            continue;
+        }
+        let ident = &variant.ident;
+        let label = options.label.unwrap_or_else(|| ident.to_string());
+        if options.separator_before {
+            items.push(quote!(::betrayer::MenuItem::separator()));
+        }
+        items.push(quote!(::betrayer::MenuItem::button(#label, Self::#ident)));
+        if options.separator_after {
+            items.push(quote!(::betrayer::MenuItem::separator()));
+        }
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::betrayer::TraySignal for #name #ty_generics #where_clause {
+            fn menu() -> ::betrayer::Menu<Self> {
+                ::betrayer::Menu::new([#(#items),*])
+            }
+        }
+    })
+}
+
+fn parse_options(attrs: &[syn::Attribute]) -> syn::Result<ItemOptions> {
+    let mut options = ItemOptions::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("tray")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("label") {
+                options.label = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("separator_before") {
+                options.separator_before = true;
+            } else if meta.path.is_ident("separator_after") {
+                options.separator_after = true;
+            } else if meta.path.is_ident("skip") {
+                options.skip = true;
+            } else {
+                return Err(meta.error("unknown tray attribute"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(options)
+}
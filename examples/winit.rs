@@ -30,7 +30,7 @@ fn main() -> Result<()> {
         .with_tooltip("Demo System Tray")
         .with_menu(build_menu(selected))
         // with `winit` feature:
-        .build_event_loop(&event_loop, |e| Some(e))?;
+        .build_event_loop(&event_loop, Some)?;
         // without:
         //.build({
         //    let proxy = event_loop.create_proxy();
@@ -39,24 +39,21 @@ fn main() -> Result<()> {
 
     event_loop.set_control_flow(ControlFlow::Wait);
     event_loop.run(|event, evtl| {
-        match event {
-            Event::UserEvent(event) => {
-                log::info!("tray event: {:?}", event);
-                if let TrayEvent::Menu(signal) = event {
-                    match signal {
-                        Signal::Profile(i) => {
-                            if selected != i {
-                                selected = i;
-                                tray.set_tooltip(format!("Active Profile: {selected}"));
-                                tray.set_menu(build_menu(selected));
-                            }
-                        },
-                        Signal::Open => {}
-                        Signal::Quit => evtl.exit()
-                    }
+        if let Event::UserEvent(event) = event {
+            log::info!("tray event: {:?}", event);
+            if let TrayEvent::Menu { signal, .. } = event {
+                match signal {
+                    Signal::Profile(i) => {
+                        if selected != i {
+                            selected = i;
+                            tray.set_tooltip(format!("Active Profile: {selected}"));
+                            tray.set_menu(build_menu(selected));
+                        }
+                    },
+                    Signal::Open => {}
+                    Signal::Quit => evtl.exit()
                 }
             }
-            _ => {}
         }
     })?;
     Ok(())
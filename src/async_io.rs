@@ -0,0 +1,37 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use crate::{ControlFlow, TrayEvent, TrayIcon, TrayIconBuilder, TrayIconParts, TrayResult};
+use crate::platform::NativeTrayIcon;
+
+impl<T: Clone + Send + 'static> TrayIconBuilder<T> {
+
+    /// Creates the tray icon on an existing `async-io` based runtime like `smol` or `async-std`
+    /// instead of spawning a dedicated thread for the DBus connection.
+    ///
+    /// The returned [TrayDriver] has to be polled (e.g. by spawning it) for as long as the tray icon is in use.
+    /// It completes once the tray icon is dropped.
+    pub async fn build_async_io<F, R>(mut self, callback: F) -> TrayResult<(TrayIcon<T>, TrayDriver)>
+        where F: FnMut(TrayEvent<T>) -> R + Send + 'static, R: Into<ControlFlow>
+    {
+        self.check_labels()?;
+        let (parts, callback) = TrayIconParts::new(&self, callback);
+        let builder = parts.with_placeholder(self);
+        let native = NativeTrayIcon::new_async(builder, callback, false).await?;
+        let driver = TrayDriver(Box::pin(native.drive()));
+        Ok((parts.finish(native), driver))
+    }
+
+}
+
+/// A future that drives the DBus connection of a tray icon created with [TrayIconBuilder::build_async_io]
+#[must_use = "the tray icon does not work unless the driver is polled"]
+pub struct TrayDriver(Pin<Box<dyn Future<Output=()> + Send>>);
+
+impl Future for TrayDriver {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.0.as_mut().poll(cx)
+    }
+}
@@ -0,0 +1,60 @@
+use crate::ensure;
+use crate::error::{TrayError, TrayResult};
+use crate::platform::autostart;
+
+/// Starts the application when the user logs in
+///
+/// The entry is stored per user: as a value of the `Run` key in the registry on *Windows*, as a `LaunchAgent` on *Mac*
+/// and as an XDG autostart `.desktop` file on *Linux*. Enabling an entry again replaces the stored command, which
+/// keeps it up to date when the executable moved.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Autostart {
+    name: String,
+    command: Vec<String>
+}
+
+impl Autostart {
+
+    /// Creates an entry that starts the current executable
+    ///
+    /// `name` identifies the entry and should be unique, e.g. the app id. It's also used as file name, so it must not
+    /// contain path separators or control characters.
+    pub fn new<S: ToString>(name: S) -> TrayResult<Self> {
+        let name = name.to_string();
+        let valid = !name.is_empty() && !name.contains(['/', '\\']) && !name.contains(char::is_control);
+        ensure!(valid, TrayError::custom(format!("Invalid autostart name: {name:?}")));
+        let exe = std::env::current_exe()
+            .map_err(|err| TrayError::custom(format!("Failed to get the path of the executable: {err}")))?
+            .into_os_string()
+            .into_string()
+            .map_err(|path| TrayError::custom(format!("The path of the executable is not valid unicode: {path:?}")))?;
+        Ok(Self {
+            name,
+            command: vec![exe],
+        })
+    }
+
+    /// Adds command line arguments, e.g. to start minimized to the tray
+    pub fn with_args<I, S>(mut self, args: I) -> Self
+        where I: IntoIterator<Item=S>, S: ToString
+    {
+        self.command.extend(args.into_iter().map(|arg| arg.to_string()));
+        self
+    }
+
+    /// Starts the application at the next login
+    pub fn enable(&self) -> TrayResult<()> {
+        autostart::enable(&self.name, &self.command)
+    }
+
+    /// Removes the entry again. Does nothing if it doesn't exist.
+    pub fn disable(&self) -> TrayResult<()> {
+        autostart::disable(&self.name)
+    }
+
+    /// Whether an entry with this name exists, regardless of the command it starts
+    pub fn is_enabled(&self) -> TrayResult<bool> {
+        autostart::is_enabled(&self.name)
+    }
+
+}
@@ -0,0 +1,52 @@
+use std::sync::{Condvar, Mutex, PoisonError};
+use crate::platform;
+
+/// Tells the built-in event loop of [TrayIcon::run](crate::TrayIcon::run) whether it should keep running
+///
+/// Tray event callbacks can either return nothing, which is treated as [ControlFlow::Continue], or a [ControlFlow].
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum ControlFlow {
+    #[default]
+    Continue,
+    Exit
+}
+
+impl From<()> for ControlFlow {
+    fn from(_: ()) -> Self {
+        ControlFlow::Continue
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct ExitSignal {
+    requested: Mutex<bool>,
+    condvar: Condvar
+}
+
+impl ExitSignal {
+
+    pub fn request(&self) {
+        *self.requested
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = true;
+        self.condvar.notify_all();
+        platform::wake_event_loop();
+    }
+
+    pub fn is_requested(&self) -> bool {
+        *self.requested
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    pub fn wait(&self) {
+        let requested = self.requested
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let _unused = self.condvar
+            .wait_while(requested, |requested| !*requested)
+            .unwrap_or_else(PoisonError::into_inner);
+    }
+
+}
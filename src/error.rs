@@ -10,9 +10,86 @@ pub type TrayResult<T> = Result<T, TrayError>;
 #[derive(Debug)]
 pub enum ErrorSource {
     Os(PlatformError),
+    Icon(IconError),
+    InvalidLabel(LabelError),
+    /// Tray icons are not supported on the current platform
+    NotSupported,
+    /// The process runs without an interactive desktop (e.g. as a Windows service in session 0), so a tray icon would never be visible
+    NoInteractiveSession,
+    /// The menu was replaced with a native menu, which can not be modified through a [MenuHandle](crate::MenuHandle)
+    NativeMenu,
     Custom(Cow<'static, str>)
 }
 
+/// Describes why the data passed to an [Icon](crate::Icon) constructor was rejected
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum IconError {
+    /// The width or height is zero or doesn't match the length of the buffer
+    BadDimensions {
+        width: u32,
+        height: u32,
+        len: usize
+    },
+    /// The width or height exceeds [Icon::MAX_SIZE](crate::Icon::MAX_SIZE)
+    TooLarge {
+        width: u32,
+        height: u32
+    },
+    /// The buffer contains no data
+    EmptyBuffer
+}
+
+impl Display for IconError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IconError::BadDimensions { width, height, len } =>
+                write!(f, "A {width}x{height} icon does not match a buffer of {len} bytes"),
+            IconError::TooLarge { width, height } =>
+                write!(f, "A {width}x{height} icon is too large"),
+            IconError::EmptyBuffer => write!(f, "The icon data is empty")
+        }
+    }
+}
+
+impl Error for IconError {}
+
+impl From<IconError> for ErrorSource {
+    fn from(value: IconError) -> Self {
+        ErrorSource::Icon(value)
+    }
+}
+
+/// Describes why the text of a menu label, title or tooltip was rejected
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum LabelError {
+    /// The text contains a NUL character, which would cut it off on Windows
+    Nul,
+    /// The text contains a control character other than a line break or tab
+    ControlCharacter(char),
+    /// The text has more than [MAX_LABEL_LENGTH](crate::MAX_LABEL_LENGTH) characters
+    TooLong {
+        len: usize
+    }
+}
+
+impl Display for LabelError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LabelError::Nul => write!(f, "The text contains a NUL character"),
+            LabelError::ControlCharacter(c) => write!(f, "The text contains the control character {c:?}"),
+            LabelError::TooLong { len } => write!(f, "The text is {len} characters long")
+        }
+    }
+}
+
+impl Error for LabelError {}
+
+impl From<LabelError> for ErrorSource {
+    fn from(value: LabelError) -> Self {
+        ErrorSource::InvalidLabel(value)
+    }
+}
+
 pub struct TrayError {
     location: &'static Location<'static>,
     source: ErrorSource
@@ -32,7 +109,7 @@ impl TrayError {
     }
 
     pub fn location(&self) -> &'static Location<'static> {
-        &self.location
+        self.location
     }
 
 }
@@ -0,0 +1,222 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, TryLockError};
+use std::task::{Context, Poll, Waker};
+use crate::TrayEvent;
+use crate::log;
+use crate::metrics::{self, Counter};
+
+/// Decides what happens to events that arrive while event delivery is paused with
+/// [TrayIcon::set_events_paused](crate::TrayIcon::set_events_paused)
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum PauseBehavior {
+    /// Events are stored and delivered in order once event delivery is resumed
+    #[default]
+    Queue,
+    /// Events are discarded
+    Drop
+}
+
+type Callback<T> = Box<dyn FnMut(TrayEvent<T>) + Send>;
+
+struct QueueState<T> {
+    paused: bool,
+    events: VecDeque<TrayEvent<T>>,
+    // A callback that was set while the current one was running
+    replacement: Option<Callback<T>>
+}
+
+pub(crate) struct EventQueue<T> {
+    behavior: PauseBehavior,
+    state: Mutex<QueueState<T>>,
+    callback: Mutex<Callback<T>>
+}
+
+impl<T> EventQueue<T> {
+
+    pub fn new<F>(behavior: PauseBehavior, callback: F) -> Self
+        where F: FnMut(TrayEvent<T>) + Send + 'static
+    {
+        Self {
+            behavior,
+            state: Mutex::new(QueueState {
+                paused: false,
+                events: VecDeque::new(),
+                replacement: None,
+            }),
+            callback: Mutex::new(Box::new(callback)),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, QueueState<T>> {
+        self.state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn dispatch(&self, event: TrayEvent<T>) {
+        {
+            let mut state = self.state();
+            if state.paused && self.behavior == PauseBehavior::Drop {
+                log::trace!("Dropping event while paused");
+                metrics::count(Counter::EventsDropped);
+                return;
+            }
+            state.events.push_back(event);
+        }
+        self.flush();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state().paused
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.state().paused = paused;
+        if !paused {
+            self.flush();
+        }
+    }
+
+    /// Replaces the callback. If the current callback is running, the new one takes over once it returns.
+    pub fn set_callback<F>(&self, callback: F)
+        where F: FnMut(TrayEvent<T>) + Send + 'static
+    {
+        let callback: Callback<T> = Box::new(callback);
+        match self.callback.try_lock() {
+            Ok(mut current) => *current = callback,
+            Err(TryLockError::Poisoned(err)) => *err.into_inner() = callback,
+            Err(TryLockError::WouldBlock) => self.state().replacement = Some(callback)
+        }
+    }
+
+    fn has_pending(&self) -> bool {
+        let state = self.state();
+        !state.paused && !state.events.is_empty()
+    }
+
+    fn flush(&self) {
+        loop {
+            // If the callback is currently running (either on another thread or because it resumed the events itself)
+            // its holder will pick up the pending events once it is done
+            let mut callback = match self.callback.try_lock() {
+                Ok(callback) => callback,
+                Err(TryLockError::Poisoned(err)) => err.into_inner(),
+                Err(TryLockError::WouldBlock) => return
+            };
+            loop {
+                let replacement = self.state().replacement.take();
+                if let Some(replacement) = replacement {
+                    *callback = replacement;
+                }
+                let event = {
+                    let mut state = self.state();
+                    if state.paused {
+                        break;
+                    }
+                    match state.events.pop_front() {
+                        Some(event) => event,
+                        None => break
+                    }
+                };
+                metrics::count(Counter::EventsDispatched);
+                callback(event);
+            }
+            drop(callback);
+            if !self.has_pending() {
+                return;
+            }
+        }
+    }
+
+}
+
+/// An asynchronous stream of tray events created by [TrayIconBuilder::build_async](crate::TrayIconBuilder::build_async)
+///
+/// The stream ends once the [TrayIcon](crate::TrayIcon) is dropped.
+/// With the `stream` feature enabled this type also implements [futures_core::Stream].
+pub struct AsyncTrayEvents<T> {
+    channel: Arc<Mutex<Channel<T>>>
+}
+
+struct Channel<T> {
+    events: VecDeque<TrayEvent<T>>,
+    waker: Option<Waker>,
+    closed: bool
+}
+
+impl<T> AsyncTrayEvents<T> {
+
+    pub(crate) fn new() -> (Self, EventSender<T>) {
+        let channel = Arc::new(Mutex::new(Channel {
+            events: VecDeque::new(),
+            waker: None,
+            closed: false,
+        }));
+        (Self { channel: channel.clone() }, EventSender { channel })
+    }
+
+    /// Attempts to receive the next event, registering the current task for wakeup if none is available.
+    ///
+    /// Returns `Poll::Ready(None)` once the tray icon has been dropped and all remaining events have been received.
+    pub fn poll_next_event(&mut self, cx: &mut Context<'_>) -> Poll<Option<TrayEvent<T>>> {
+        let mut channel = self.channel
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        match channel.events.pop_front() {
+            Some(event) => Poll::Ready(Some(event)),
+            None if channel.closed => Poll::Ready(None),
+            None => {
+                if !channel.waker.as_ref().is_some_and(|waker| waker.will_wake(cx.waker())) {
+                    channel.waker = Some(cx.waker().clone());
+                }
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Waits for the next event. Returns `None` once the tray icon has been dropped.
+    pub async fn next_event(&mut self) -> Option<TrayEvent<T>> {
+        std::future::poll_fn(|cx| self.poll_next_event(cx)).await
+    }
+
+}
+
+#[cfg(feature = "stream")]
+impl<T> futures_core::Stream for AsyncTrayEvents<T> {
+    type Item = TrayEvent<T>;
+
+    fn poll_next(self: std::pin::Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_next_event(cx)
+    }
+}
+
+pub(crate) struct EventSender<T> {
+    channel: Arc<Mutex<Channel<T>>>
+}
+
+impl<T> EventSender<T> {
+
+    fn update(&self, func: impl FnOnce(&mut Channel<T>)) {
+        let waker = {
+            let mut channel = self.channel
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            func(&mut channel);
+            channel.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    pub fn send(&self, event: TrayEvent<T>) {
+        self.update(|channel| channel.events.push_back(event));
+    }
+
+}
+
+impl<T> Drop for EventSender<T> {
+    fn drop(&mut self) {
+        self.update(|channel| channel.closed = true);
+    }
+}
@@ -0,0 +1,47 @@
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+use crate::{TrayEvent, TrayIcon};
+use crate::error::TrayResult;
+use crate::platform::instance::{self, InstanceLock};
+
+/// How often activations by other instances are checked
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Makes sure that only one instance of the application runs per user session
+///
+/// Starting the application again activates the running instance, which receives a [TrayEvent::Activate] and can e.g.
+/// open its main window. The running instance is found with a named mutex and a named pipe on *Windows* and a unix
+/// socket in `$XDG_RUNTIME_DIR` on *Linux* and *Mac*. Without a runtime directory a private `betrayer-<uid>` directory
+/// is created in the temporary directory.
+pub struct SingleInstance {
+    lock: InstanceLock,
+    activations: Receiver<()>
+}
+
+impl SingleInstance {
+
+    /// Becomes the primary instance under the name `name`, which should be unique, e.g. the app id
+    ///
+    /// The name is used as file name on *Linux* and *Mac*, so it must not contain `/` or control characters.
+    ///
+    /// Returns `None` if another instance is already running. That instance has been activated at this point and the
+    /// caller should exit.
+    pub fn acquire(name: &str) -> TrayResult<Option<Self>> {
+        let (sender, activations) = mpsc::channel();
+        Ok(instance::acquire(name, sender)?
+            .map(|lock| Self { lock, activations }))
+    }
+
+    /// Delivers the activations by other instances to the callback of `tray` until it's dropped
+    pub fn attach<T: Send + 'static>(self, tray: &TrayIcon<T>) {
+        let Self { lock, activations } = self;
+        tray.set_interval(POLL_INTERVAL, move |tray| {
+            // The instance stays the primary one for as long as the tray icon exists
+            let _lock = &lock;
+            for () in activations.try_iter() {
+                tray.0.events.dispatch(TrayEvent::Activate);
+            }
+        });
+    }
+
+}
@@ -0,0 +1,137 @@
+use std::borrow::Cow;
+use std::fmt::{self, Display, Write};
+use unicode_segmentation::UnicodeSegmentation;
+use crate::error::LabelError;
+use crate::log;
+
+/// The maximum number of characters in a menu label, title or tooltip
+///
+/// Windows truncates tooltips to 127 characters on its own.
+pub const MAX_LABEL_LENGTH: usize = 1024;
+
+/// Line breaks and tabs are the only control characters that the platforms render sensibly
+fn is_forbidden(c: char) -> bool {
+    c.is_control() && c != '\n' && c != '\t'
+}
+
+pub fn check_label(text: &str) -> Result<(), LabelError> {
+    if let Some(c) = text.chars().find(|c| is_forbidden(*c)) {
+        return Err(match c {
+            '\0' => LabelError::Nul,
+            c => LabelError::ControlCharacter(c)
+        });
+    }
+    let len = text.chars().count();
+    if len > MAX_LABEL_LENGTH {
+        return Err(LabelError::TooLong { len });
+    }
+    Ok(())
+}
+
+/// Removes the forbidden characters and truncates the text to [MAX_LABEL_LENGTH]
+pub fn sanitize_label(text: Cow<'_, str>) -> Cow<'_, str> {
+    match check_label(&text) {
+        Ok(()) => text,
+        Err(err) => {
+            log::warn!("Sanitizing invalid label: {err}");
+            let text: String = text
+                .chars()
+                .filter(|c| !is_forbidden(*c))
+                .collect();
+            Cow::Owned(truncate_graphemes(&text, MAX_LABEL_LENGTH, |g| g.chars().count()).to_string())
+        }
+    }
+}
+
+/// Whether `value` is displayed as `text`, without allocating a string for it
+pub fn display_eq(text: &str, value: &impl Display) -> bool {
+    // Consumes the matching prefix of the text and fails as soon as the output differs
+    struct Matcher<'a>(&'a str);
+
+    impl Write for Matcher<'_> {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.0 = self.0.strip_prefix(s).ok_or(fmt::Error)?;
+            Ok(())
+        }
+    }
+
+    let mut matcher = Matcher(text);
+    write!(matcher, "{value}").is_ok() && matcher.0.is_empty()
+}
+
+/// The longest prefix of `text` that is at most `max` units long (as measured by `len`) and doesn't split a grapheme
+///
+/// Cutting in the middle of a grapheme would break up emoji sequences or, when counting UTF-16 units, leave a lone surrogate.
+pub fn truncate_graphemes(text: &str, max: usize, len: impl Fn(&str) -> usize) -> &str {
+    let mut used = 0;
+    let end = text
+        .grapheme_indices(true)
+        .take_while(|(_, grapheme)| {
+            used += len(grapheme);
+            used <= max
+        })
+        .last()
+        .map_or(0, |(start, grapheme)| start + grapheme.len());
+    &text[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_labels_are_borrowed() {
+        let label = sanitize_label(Cow::Borrowed("Line\nwith\ttab"));
+        assert!(matches!(label, Cow::Borrowed("Line\nwith\ttab")));
+    }
+
+    #[test]
+    fn control_characters_are_removed() {
+        assert_eq!(sanitize_label(Cow::Borrowed("a\0b\x07c\r")), "abc");
+        assert_eq!(check_label("a\0b"), Err(LabelError::Nul));
+        assert_eq!(check_label("a\x1bb"), Err(LabelError::ControlCharacter('\x1b')));
+    }
+
+    #[test]
+    fn long_labels_are_truncated() {
+        let label = "x".repeat(MAX_LABEL_LENGTH + 10);
+        assert_eq!(check_label(&label), Err(LabelError::TooLong { len: MAX_LABEL_LENGTH + 10 }));
+        assert_eq!(sanitize_label(Cow::Borrowed(&label)).chars().count(), MAX_LABEL_LENGTH);
+    }
+
+    #[test]
+    fn truncation_keeps_graphemes_intact() {
+        let chars = |g: &str| g.chars().count();
+        assert_eq!(truncate_graphemes("abcdef", 3, chars), "abc");
+        assert_eq!(truncate_graphemes("abc", 10, chars), "abc");
+        assert_eq!(truncate_graphemes("abc", 0, chars), "");
+        // The family emoji consists of five chars and would be split after the first one
+        assert_eq!(truncate_graphemes("a👨‍👩‍👧b", 3, chars), "a");
+        assert_eq!(truncate_graphemes("a👨‍👩‍👧b", 6, chars), "a👨‍👩‍👧");
+        // Emoji outside the basic plane take two UTF-16 units
+        let utf16 = |g: &str| g.encode_utf16().count();
+        assert_eq!(truncate_graphemes("a😀", 2, utf16), "a");
+        assert_eq!(truncate_graphemes("a😀", 3, utf16), "a😀");
+    }
+
+    #[test]
+    fn emoji_labels_are_kept_unchanged() {
+        // Sequences joined with ZWJ, flags and skin tone modifiers are passed to the platforms as is
+        for label in ["👨‍👩‍👧 Family", "🏳️‍🌈", "🇩🇪 Deutsch", "👍🏽", "e\u{301}"] {
+            assert!(matches!(sanitize_label(Cow::Borrowed(label)), Cow::Borrowed(l) if l == label));
+            assert!(display_eq(label, &label));
+            assert!(display_eq(label, &String::from(label)));
+        }
+    }
+
+    #[test]
+    fn display_eq_compares_the_formatted_value() {
+        assert!(display_eq("42", &42));
+        assert!(display_eq("", &""));
+        assert!(display_eq("1.5 GB", &format_args!("{} GB", 1.5)));
+        assert!(!display_eq("42", &421));
+        assert!(!display_eq("421", &42));
+        assert!(!display_eq("", &0));
+    }
+
+}
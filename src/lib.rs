@@ -1,22 +1,92 @@
 #![doc = include_str!("../Readme.md")]
 
+mod log;
 mod platform;
 mod error;
+mod control_flow;
+mod events;
+mod notification;
+mod shortcut;
+mod label;
+mod metrics;
+mod model;
+#[cfg(any(target_os = "windows", target_os = "macos"))]
 mod utils;
 
 #[cfg(feature = "winit")]
 pub mod winit;
+#[cfg(all(feature = "async-io", target_os = "linux"))]
+mod async_io;
+#[cfg(feature = "watch")]
+mod watch;
+#[cfg(feature = "record")]
+mod record;
+#[cfg(all(feature = "remote", any(target_os = "windows", target_os = "linux")))]
+pub mod remote;
+#[cfg(feature = "autostart")]
+mod autostart;
+#[cfg(feature = "single-instance")]
+mod instance;
 
-use platform::{NativeIcon, NativeTrayIcon};
+use std::borrow::Cow;
+use std::fmt::{Debug, Display, Formatter};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use platform::{Acknowledgement, NativeIcon, NativeTrayIcon};
+use control_flow::ExitSignal;
+use events::EventQueue;
 
-pub use error::{TrayResult, TrayError, ErrorSource};
+pub use error::{TrayResult, TrayError, ErrorSource, IconError, LabelError};
+pub use control_flow::ControlFlow;
+pub use events::{AsyncTrayEvents, PauseBehavior};
+pub use notification::{Notification, NotificationEvent};
+pub use shortcut::{Modifiers, Shortcut};
+pub use label::MAX_LABEL_LENGTH;
+pub use metrics::{metrics, TrayMetrics};
+pub use model::TrayModel;
+#[cfg(feature = "autostart")]
+pub use autostart::Autostart;
+#[cfg(feature = "single-instance")]
+pub use instance::SingleInstance;
+#[cfg(feature = "record")]
+pub use record::{read_events, record_events, replay_events};
+#[cfg(all(feature = "async-io", target_os = "linux"))]
+pub use async_io::TrayDriver;
+#[cfg(target_os = "linux")]
+pub use platform::DBusMenuInterface;
+
+#[cfg(feature = "derive")]
+pub use betrayer_derive::TraySignal;
 
 /// Builder struct for a tray icon
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct TrayIconBuilder<T = ()> {
     menu: Option<Menu<T>>,
     tooltip: Option<String>,
-    icon: Option<Icon>
+    tooltip_provider: Option<TooltipProvider>,
+    title: Option<String>,
+    #[cfg_attr(not(any(target_os = "windows", target_os = "linux", target_os = "macos")), allow(dead_code))]
+    app_id: Option<String>,
+    icon: Option<Icon>,
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    icon_name: Option<String>,
+    placeholder_icon: PlaceholderIcon,
+    pause_behavior: PauseBehavior,
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    max_scroll_rate: Option<u32>,
+    #[cfg_attr(not(any(target_os = "windows", target_os = "linux")), allow(dead_code))]
+    click_policy: ClickPolicy,
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+    menu_trigger: MenuTrigger,
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+    menu_placement: MenuPlacement,
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+    hover_flyout: bool,
+    #[cfg(target_os = "windows")]
+    wndproc_hook: Option<WndProcHook>
 }
 
 impl<T> TrayIconBuilder<T> {
@@ -25,7 +95,20 @@ impl<T> TrayIconBuilder<T> {
         Self {
             menu: None,
             tooltip: None,
+            tooltip_provider: None,
+            title: None,
+            app_id: None,
             icon: None,
+            icon_name: None,
+            placeholder_icon: PlaceholderIcon::default(),
+            pause_behavior: PauseBehavior::default(),
+            max_scroll_rate: Some(30),
+            click_policy: ClickPolicy::default(),
+            menu_trigger: MenuTrigger::default(),
+            menu_placement: MenuPlacement::default(),
+            hover_flyout: false,
+            #[cfg(target_os = "windows")]
+            wndproc_hook: None,
         }
     }
 
@@ -43,72 +126,1097 @@ impl<T> TrayIconBuilder<T> {
         self
     }
 
+    /// Sets a function that gets evaluated every time the tooltip is about to be shown.
+    ///
+    /// This allows the tooltip to display frequently changing information without constantly calling [TrayIcon::set_tooltip].
+    /// On Windows the function is called when the tooltip pops up, on Linux when the host queries the tooltip.
+    /// Does nothing on MacOS.
+    pub fn with_tooltip_provider<F>(mut self, provider: F) -> Self
+        where F: FnMut() -> String + Send + 'static
+    {
+        self.tooltip_provider = Some(TooltipProvider(Arc::new(Mutex::new(provider))));
+        self
+    }
+
+    /// Replaces the standard tooltip with a flyout drawn by the application
+    ///
+    /// Hovering over the icon then emits [TrayEvent::ShowFlyout] with the position of the icon instead of showing the tooltip,
+    /// and [TrayEvent::HideFlyout] once the pointer leaves it again. [TrayIcon::show_flyout] and [TrayIcon::hide_flyout]
+    /// move a window of the application next to the icon and hide it again. The tooltip is still exposed to screen readers.
+    ///
+    /// Only supported on *Windows*. The other platforms keep showing the tooltip and never emit the flyout events.
+    pub fn with_hover_flyout(mut self) -> Self {
+        self.hover_flyout = true;
+        self
+    }
+
+    /// Sets the title of the tray icon.
+    ///
+    /// On MacOS the title is displayed next to the icon in the menu bar, on Linux it is exposed to the host
+    /// which may use it for accessibility. Does nothing on Windows.
+    pub fn with_title<S: ToString>(mut self, title: S) -> Self {
+        self.title = Some(title.to_string());
+        self
+    }
+
+    /// Sets the id of the application, usually its reverse domain name like `dev.example.App`
+    ///
+    /// Without an id everything is named after this library, which makes multiple applications indistinguishable to the host.
+    ///
+    /// On *Linux* the id is used for the `Id` of the StatusNotifierItem, as suffix of its D-Bus name, as prefix of the
+    /// object path of the menu and as the application name and `desktop-entry` hint of notifications, so it should match the name of the `.desktop` file.
+    /// On *Windows* it's used as the AppUserModelID of the process and of toast notifications. The id gets registered for the current user
+    /// with the title (or the id itself) as display name, so toasts show up without a start menu shortcut.
+    /// On *Mac* it's used for the autosave name of the status item, which lets the position of the icon persist.
+    /// Without an id, *Mac* uses the bundle identifier or the process name instead.
+    pub fn with_app_id<S: ToString>(mut self, app_id: S) -> Self {
+        self.app_id = Some(app_id.to_string());
+        self
+    }
+
     /// Sets the icon of the tray icon
     pub fn with_icon(mut self, icon: Icon) -> Self {
         self.icon = Some(icon);
+        self.icon_name = None;
+        self
+    }
+
+    /// Sets the icon from an ordered list of sources, using the first one the platform supports
+    ///
+    /// Theme names are only supported on *Linux*. There the first theme name is used together with the first image
+    /// that follows it, which hosts without access to the icon theme (e.g. GNOME extensions) show instead.
+    /// An image that is listed before any theme name takes precedence.
+    /// *Windows* and *Mac* skip theme names and use the first image.
+    ///
+    /// ```no_run
+    /// # use betrayer::{Icon, IconSource, TrayIconBuilder};
+    /// # let icon: Icon = unimplemented!();
+    /// let builder = TrayIconBuilder::<()>::new()
+    ///     .with_icon_sources([IconSource::theme("network-wired"), IconSource::Image(icon)]);
+    /// ```
+    pub fn with_icon_sources<I: IntoIterator<Item=IconSource>>(mut self, sources: I) -> Self {
+        (self.icon_name, self.icon) = IconSource::resolve(sources);
+        self
+    }
+
+    /// Sets the icon that is shown while no icon is set, i.e. when the builder has no icon or after `TrayIcon::set_icon(None)`.
+    ///
+    /// Defaults to a fully transparent icon on *Windows* and *Linux*, which would otherwise show an empty space or a "missing icon" symbol.
+    /// `None` leaves the choice to the platform. On *Mac* there is no placeholder by default, as the title is shown instead.
+    pub fn with_placeholder_icon(mut self, icon: impl Into<Option<Icon>>) -> Self {
+        self.placeholder_icon = match icon.into() {
+            Some(icon) => PlaceholderIcon::Custom(icon),
+            None => PlaceholderIcon::None
+        };
+        self
+    }
+
+    /// Limits how many [TrayEvent::Scroll] events are delivered per second. Defaults to 30.
+    ///
+    /// Scroll deltas that arrive in between are summed up and delivered as a single event.
+    /// `None` disables the coalescing and delivers every scroll event as is.
+    /// Scroll events are currently only emitted on *Linux*.
+    pub fn with_max_scroll_rate(mut self, max_rate: impl Into<Option<u32>>) -> Self {
+        self.max_scroll_rate = max_rate.into();
+        self
+    }
+
+    /// Sets how clicks on the icon are reported. Defaults to [ClickPolicy::DetectDoubleClicks].
+    pub fn with_click_policy(mut self, policy: ClickPolicy) -> Self {
+        self.click_policy = policy;
+        self
+    }
+
+    /// Sets which clicks open the menu. Defaults to [MenuTrigger::RightClick].
+    ///
+    /// Only supported on *Windows*. On *Mac* the menu always opens on any click and on *Linux* the host decides.
+    pub fn with_menu_trigger(mut self, trigger: MenuTrigger) -> Self {
+        self.menu_trigger = trigger;
         self
     }
 
+    /// Sets the direction in which the menu grows from the point it opens at. Defaults to opening away from the taskbar.
+    ///
+    /// Only supported on *Windows*. On *Mac* the menu always drops down from the menu bar and on *Linux* the host decides.
+    pub fn with_menu_placement(mut self, placement: MenuPlacement) -> Self {
+        self.menu_placement = placement;
+        self
+    }
+
+    /// Sets a function that sees every message of the hidden window that receives the messages of the tray icon
+    ///
+    /// This allows receiving broadcasts like `WM_DEVICECHANGE`, `WM_POWERBROADCAST` or custom registered messages without
+    /// creating another window. The function receives the message, `WPARAM` and `LPARAM` and can return `Some(LRESULT)`
+    /// to override the value that is returned to the sender. The messages are still handled by the tray icon as well.
+    /// Note that the window is shared by all tray icons of the same thread.
+    ///
+    /// Only available on *Windows*.
+    #[cfg(target_os = "windows")]
+    pub fn with_wndproc_hook<F>(mut self, hook: F) -> Self
+        where F: FnMut(u32, usize, isize) -> Option<isize> + Send + 'static
+    {
+        self.wndproc_hook = Some(WndProcHook(Arc::new(Mutex::new(hook))));
+        self
+    }
+
+    /// Sets what happens to events that arrive while event delivery is paused. Defaults to [PauseBehavior::Queue].
+    pub fn with_pause_behavior(mut self, behavior: PauseBehavior) -> Self {
+        self.pause_behavior = behavior;
+        self
+    }
+
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum PlaceholderIcon {
+    Transparent,
+    Custom(Icon),
+    None
+}
+
+impl Default for PlaceholderIcon {
+    fn default() -> Self {
+        match cfg!(target_os = "macos") {
+            true => PlaceholderIcon::None,
+            false => PlaceholderIcon::Transparent
+        }
+    }
+}
+
+impl PlaceholderIcon {
+    fn create(&self) -> Option<Icon> {
+        match self {
+            PlaceholderIcon::Transparent => Icon::from_rgba(vec![0; 16 * 16 * 4], 16, 16)
+                .map_err(|err| log::warn!("Failed to create placeholder icon: {err}"))
+                .ok(),
+            PlaceholderIcon::Custom(icon) => Some(icon.clone()),
+            PlaceholderIcon::None => None
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct TooltipProvider(Arc<Mutex<dyn FnMut() -> String + Send>>);
+
+impl TooltipProvider {
+    #[cfg_attr(not(any(target_os = "windows", target_os = "linux")), allow(dead_code))]
+    pub(crate) fn get(&self) -> String {
+        let mut provider = self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        label::sanitize_label(provider().into()).into_owned()
+    }
+}
+
+impl Debug for TooltipProvider {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TooltipProvider")
+    }
+}
+
+impl PartialEq for TooltipProvider {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for TooltipProvider {}
+
+#[cfg(target_os = "windows")]
+type WndProcFn = dyn FnMut(u32, usize, isize) -> Option<isize> + Send;
+
+#[cfg(target_os = "windows")]
+#[derive(Clone)]
+pub(crate) struct WndProcHook(Arc<Mutex<WndProcFn>>);
+
+#[cfg(target_os = "windows")]
+impl WndProcHook {
+    pub(crate) fn call(&self, msg: u32, wparam: usize, lparam: isize) -> Option<isize> {
+        // The hook can cause new messages to be sent to the window, e.g. by showing a dialog
+        let mut hook = match self.0.try_lock() {
+            Ok(hook) => hook,
+            Err(std::sync::TryLockError::Poisoned(err)) => err.into_inner(),
+            Err(std::sync::TryLockError::WouldBlock) => {
+                log::debug!("Skipping re-entrant window procedure hook");
+                return None;
+            }
+        };
+        hook(msg, wparam, lparam)
+    }
+}
+
+#[cfg(target_os = "windows")]
+impl Debug for WndProcHook {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("WndProcHook")
+    }
+}
+
+#[cfg(target_os = "windows")]
+impl PartialEq for WndProcHook {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+#[cfg(target_os = "windows")]
+impl Eq for WndProcHook {}
+
+impl<T> TrayIconBuilder<T> {
+
+    fn check_labels(&mut self) -> TrayResult<()> {
+        for text in [&self.tooltip, &self.title].into_iter().flatten() {
+            label::check_label(text)?;
+        }
+        if let Some(menu) = &mut self.menu {
+            menu.visit_labels(&mut |label| label::check_label(label))?;
+        }
+        Ok(())
+    }
+
+}
+
+impl<T> Default for TrayIconBuilder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<T: Clone + Send + 'static> TrayIconBuilder<T> {
 
     /// Attempts to create the tray icon. See the the *Platform notes* section of the Readme for more information.
-    pub fn build<F>(self, callback: F) -> TrayResult<TrayIcon<T>>
-        where F: FnMut(TrayEvent<T>) + Send + 'static
+    ///
+    /// The callback can optionally return a [ControlFlow] to stop the event loop started by [TrayIcon::run].
+    /// Fails with a [LabelError] if the tooltip, title or a menu label contains control characters or is longer than [MAX_LABEL_LENGTH].
+    pub fn build<F, R>(mut self, callback: F) -> TrayResult<TrayIcon<T>>
+        where F: FnMut(TrayEvent<T>) -> R + Send + 'static, R: Into<ControlFlow>
+    {
+        self.check_labels()?;
+        let (parts, callback) = TrayIconParts::new(&self, callback);
+        let builder = parts.with_placeholder(self);
+        Ok(parts.finish(NativeTrayIcon::new(builder, callback)?))
+    }
+
+    /// Creates the tray icon and returns its events as an [AsyncTrayEvents] instead of delivering them to a callback.
+    ///
+    /// This allows integrating the tray icon with any executor or poll-based framework without a dedicated thread.
+    /// On Windows and Mac the events are still produced by the platform event loop, which has to keep running.
+    pub fn build_async(self) -> TrayResult<(TrayIcon<T>, AsyncTrayEvents<T>)> {
+        let (events, sender) = AsyncTrayEvents::new();
+        let tray = self.build(move |event| sender.send(event))?;
+        Ok((tray, events))
+    }
+
+}
+
+impl TrayIconBuilder<usize> {
+
+    /// Like [TrayIconBuilder::build], but first replaces the signals of the menu with sequential indices
+    ///
+    /// This avoids declaring a signal enum for quick tools or menus that are generated from runtime lists.
+    /// The signals passed to [MenuItem::button] and co. are ignored, so any placeholder works.
+    /// Use [Menu::enumerated] for menus that are set later.
+    ///
+    /// ```no_run
+    /// # use betrayer::{Menu, MenuItem, TrayEvent, TrayIconBuilder};
+    /// let files = ["a.txt", "b.txt"];
+    /// let tray = TrayIconBuilder::new()
+    ///     .with_menu(Menu::new(files.iter().map(|file| MenuItem::button(file, 0))))
+    ///     .build_enumerated(move |event| {
+    ///         if let TrayEvent::Menu { signal: index, .. } = event {
+    ///             println!("Opening {}", files[index]);
+    ///         }
+    ///     });
+    /// ```
+    pub fn build_enumerated<F, R>(mut self, callback: F) -> TrayResult<TrayIcon<usize>>
+        where F: FnMut(TrayEvent<usize>) -> R + Send + 'static, R: Into<ControlFlow>
+    {
+        self.menu = self.menu.map(Menu::enumerated);
+        self.build(callback)
+    }
+
+}
+
+/// Wraps an event callback so that returning [ControlFlow::Exit] stops [TrayIcon::run]
+fn exit_on_request<T, F, R>(exit: &Arc<ExitSignal>, mut callback: F) -> impl FnMut(TrayEvent<T>) + Send + 'static
+    where F: FnMut(TrayEvent<T>) -> R + Send + 'static, R: Into<ControlFlow>
+{
+    let exit = exit.clone();
+    move |event| if callback(event).into() == ControlFlow::Exit {
+        exit.request();
+    }
+}
+
+/// Everything besides the native tray icon that makes up a [TrayIcon]
+struct TrayIconParts<T> {
+    state: CurrentState<T>,
+    placeholder: Option<Icon>,
+    exit: Arc<ExitSignal>,
+    events: Arc<EventQueue<T>>
+}
+
+impl<T: Clone + Send + 'static> TrayIconParts<T> {
+
+    /// Also returns the callback that has to be passed to the native tray icon
+    fn new<F, R>(builder: &TrayIconBuilder<T>, callback: F) -> (Self, impl FnMut(TrayEvent<T>) + Send + 'static)
+        where F: FnMut(TrayEvent<T>) -> R + Send + 'static, R: Into<ControlFlow>
+    {
+        let state = CurrentState {
+            icon: builder.icon.clone(),
+            tooltip: builder.tooltip.clone(),
+            title: builder.title.clone(),
+            menu: None,
+            native_menu: false,
+        };
+        let exit = Arc::new(ExitSignal::default());
+        let events = Arc::new(EventQueue::new(builder.pause_behavior, exit_on_request(&exit, callback)));
+        let callback = {
+            let events = events.clone();
+            move |event| events.dispatch(event)
+        };
+        let placeholder = builder.placeholder_icon.create();
+        (Self { state, placeholder, exit, events }, callback)
+    }
+
+    /// Shows the placeholder if the builder has no icon
+    fn with_placeholder(&self, mut builder: TrayIconBuilder<T>) -> TrayIconBuilder<T> {
+        if builder.icon.is_none() {
+            builder.icon = self.placeholder.clone();
+        }
+        builder
+    }
+
+    fn finish(self, native: NativeTrayIcon<T>) -> TrayIcon<T> {
+        let tray = TrayIcon(Arc::new(TrayIconInner {
+            native,
+            state: Mutex::new(self.state),
+            placeholder: self.placeholder,
+            exit: self.exit,
+            events: self.events,
+        }));
+        #[cfg(feature = "watch")]
+        tray.set_interval(watch::POLL_INTERVAL, TrayIcon::reload_watched_icon);
+        tray
+    }
+
+}
+
+/// The complete visible state of a tray icon
+///
+/// Used with [TrayIcon::set_state] to describe what the tray icon should look like
+/// instead of issuing the individual `set_*` calls. [TrayModel] derives it from the state of the application.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TrayState<T> {
+    pub icon: Option<Icon>,
+    pub tooltip: Option<String>,
+    pub title: Option<String>,
+    pub menu: Option<Menu<T>>
+}
+
+impl<T> Default for TrayState<T> {
+    fn default() -> Self {
+        Self {
+            icon: None,
+            tooltip: None,
+            title: None,
+            menu: None,
+        }
+    }
+}
+
+/// What the tray icon currently shows, used by [TrayIcon::set_state] to find the parts that changed
+struct CurrentState<T> {
+    icon: Option<Icon>,
+    tooltip: Option<String>,
+    title: Option<String>,
+    /// A copy of the menu is only kept when it was set with [TrayIcon::set_state], which can clone it anyway.
+    /// `None` means that the current menu is unknown and the next [TrayIcon::set_state] has to replace it.
+    menu: Option<Option<Menu<T>>>,
+    /// Whether the menu was replaced with a native menu
+    native_menu: bool
+}
+
+/// The parts of a [TrayState] that differ from the [CurrentState]
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+struct StateChanges {
+    icon: bool,
+    tooltip: bool,
+    title: bool,
+    menu: bool
+}
+
+impl<T: PartialEq> CurrentState<T> {
+    fn changes(&self, state: &TrayState<T>) -> StateChanges {
+        StateChanges {
+            icon: self.icon != state.icon,
+            tooltip: self.tooltip != state.tooltip,
+            title: self.title != state.title,
+            menu: self.menu.as_ref() != Some(&state.menu)
+        }
+    }
+}
+
+impl<T> CurrentState<T> {
+    /// The native menu is unknown, so the next [TrayIcon::set_state] replaces it
+    #[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
+    fn set_native_menu(&mut self) {
+        self.menu = None;
+        self.native_menu = true;
+    }
+}
+
+pub struct TrayIcon<T>(Arc<TrayIconInner<T>>);
+
+struct TrayIconInner<T> {
+    native: NativeTrayIcon<T>,
+    state: Mutex<CurrentState<T>>,
+    placeholder: Option<Icon>,
+    exit: Arc<ExitSignal>,
+    events: Arc<EventQueue<T>>
+}
+
+impl<T> TrayIcon<T> {
+
+    fn native(&self) -> &NativeTrayIcon<T> {
+        &self.0.native
+    }
+
+    /// Like [TrayIcon::native], but counts the call as an update in the [TrayMetrics]
+    fn update(&self) -> &NativeTrayIcon<T> {
+        metrics::count(metrics::Counter::Updates);
+        self.native()
+    }
+
+    fn state(&self) -> MutexGuard<'_, CurrentState<T>> {
+        self.0.state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The backend that implements this tray icon. See [Backend::current].
+    pub fn backend(&self) -> Backend {
+        Backend::current()
+    }
+
+    /// Returns the identity of the tray icon at the OS level, e.g. for correlating log lines or multiple icons
+    ///
+    /// On *Windows* this is the window handle and notify icon id (`<hwnd>:<id>`), on *Linux* the DBus name and object path
+    /// of the StatusNotifierItem and on *Mac* the autosave name of the status item.
+    pub fn id(&self) -> String {
+        self.native().id()
+    }
+
+    /// Whether the icon is hidden in the overflow flyout instead of being shown directly on the taskbar
+    ///
+    /// Useful for showing a one-time hint that the icon can be dragged out of the overflow area.
+    ///
+    /// Only available on *Windows*.
+    #[cfg(target_os = "windows")]
+    pub fn is_in_overflow(&self) -> TrayResult<bool> {
+        self.native().is_in_overflow()
+    }
+
+    /// The position and size of the icon on the screen in physical pixels
+    ///
+    /// Only available on *Windows*.
+    #[cfg(target_os = "windows")]
+    pub fn icon_rect(&self) -> TrayResult<IconRect> {
+        self.native().icon_rect()
+    }
+
+    /// Moves the window with the handle `hwnd` next to the icon without covering it and shows it without activating it
+    ///
+    /// Meant to be called in response to [TrayEvent::ShowFlyout]. The window keeps its size and opens in the direction
+    /// set by [TrayIconBuilder::with_menu_placement]. It's also made topmost, so it isn't hidden behind the taskbar.
+    ///
+    /// Only available on *Windows*.
+    #[cfg(target_os = "windows")]
+    pub fn show_flyout(&self, hwnd: isize) -> TrayResult<()> {
+        self.native().show_flyout(hwnd)
+    }
+
+    /// Hides the window with the handle `hwnd` again, usually in response to [TrayEvent::HideFlyout]
+    ///
+    /// Only available on *Windows*.
+    #[cfg(target_os = "windows")]
+    pub fn hide_flyout(&self, hwnd: isize) -> TrayResult<()> {
+        self.native().hide_flyout(hwnd)
+    }
+
+    /// Asks the shell to always show the icon directly in the taskbar instead of the overflow area
+    ///
+    /// This relies on the undocumented `ITrayNotify` interface. Recent versions of *Windows* may ignore the request
+    /// without reporting an error, so use [TrayIcon::is_in_overflow] to check whether it had an effect.
+    /// Where the interface is missing, the promotion setting of *Windows 11* is changed instead.
+    ///
+    /// Only available on *Windows* with the `tray-notify` feature.
+    #[cfg(all(target_os = "windows", feature = "tray-notify"))]
+    pub fn request_promotion(&self) -> TrayResult<()> {
+        self.native().request_promotion()
+    }
+
+    /// Shows the progress of a long running operation on the taskbar button of the window with the handle `hwnd`
+    ///
+    /// Pass [TaskbarProgress::None] to remove the progress again. The taskbar only accepts progress once it has sent the
+    /// registered `TaskbarButtonCreated` message to the window, so this fails for windows that were just shown.
+    ///
+    /// Only available on *Windows* with the `taskbar-progress` feature.
+    #[cfg(all(target_os = "windows", feature = "taskbar-progress"))]
+    pub fn set_taskbar_progress(&self, hwnd: isize, progress: TaskbarProgress) -> TrayResult<()> {
+        self.native().set_taskbar_progress(hwnd, progress)
+    }
+
+    /// Returns a future that resolves once all updates made so far have been applied by the platform
+    ///
+    /// This allows sequencing updates deterministically, e.g. in tests. Awaiting it is optional.
+    pub fn acknowledge(&self) -> Applied {
+        Applied(self.native().acknowledge())
+    }
+
+    /// Updates or removes the tooltip
+    ///
+    /// Accepts anything that can be displayed, like strings or numbers. Setting the tooltip that is already shown
+    /// does nothing and doesn't allocate, so this is cheap to call periodically.
+    /// Control characters are removed and tooltips longer than [MAX_LABEL_LENGTH] are truncated.
+    /// Use [TrayIcon::acknowledge] to wait until the platform shows the new tooltip.
+    pub fn set_tooltip<S: Display>(&self, tooltip: impl Into<Option<S>>) {
+        let tooltip = tooltip.into();
+        let tooltip = {
+            let mut state = self.state();
+            let unchanged = match (&state.tooltip, &tooltip) {
+                (Some(current), Some(tooltip)) => label::display_eq(current, tooltip),
+                (None, None) => true,
+                _ => false
+            };
+            // The same tooltip might still be on its way to the platform
+            if unchanged {
+                return;
+            }
+            let tooltip = tooltip.map(|s| label::sanitize_label(s.to_string().into()).into_owned());
+            // Only a tooltip that needed sanitizing gets here without changing
+            if state.tooltip == tooltip {
+                return;
+            }
+            state.tooltip = tooltip.clone();
+            tooltip
+        };
+        self.update().set_tooltip(tooltip);
+    }
+
+    /// Updates or removes the title. Sanitized like the tooltip, see [TrayIcon::set_tooltip].
+    pub fn set_title<S: Display>(&self, title: impl Into<Option<S>>) {
+        let title = title.into().map(|s| label::sanitize_label(s.to_string().into()).into_owned());
+        self.state().title = title.clone();
+        self.update().set_title(title);
+    }
+
+    /// Updates or removes the icon. Without an icon the placeholder is shown, see [TrayIconBuilder::with_placeholder_icon].
+    pub fn set_icon(&self, icon: impl Into<Option<Icon>>) {
+        let icon = icon.into();
+        self.state().icon = icon.clone();
+        self.update().set_icon(icon.or_else(|| self.0.placeholder.clone()));
+    }
+
+    /// Shows the latest version of the current icon if it is watched with [Icon::from_path_watched]
+    #[cfg(feature = "watch")]
+    fn reload_watched_icon(&self) {
+        let icon = {
+            let mut state = self.state();
+            let Some(icon) = state.icon.as_mut() else {
+                return;
+            };
+            if !icon.reload() {
+                return;
+            }
+            icon.clone()
+        };
+        self.update().set_icon(Some(icon))
+    }
+
+    /// Updates the icon from an ordered list of sources. See [TrayIconBuilder::with_icon_sources] for how they are resolved.
+    pub fn set_icon_sources<I: IntoIterator<Item=IconSource>>(&self, sources: I) {
+        match IconSource::resolve(sources) {
+            (Some(name), fallback) => {
+                self.state().icon = fallback.clone();
+                self.update().set_themed_icon(name, fallback.or_else(|| self.0.placeholder.clone()));
+            }
+            (None, icon) => self.set_icon(icon)
+        }
+    }
+
+    /// Pauses or resumes the delivery of events to the callback.
+    ///
+    /// Depending on the [PauseBehavior] of the builder, events that arrive while paused are either queued
+    /// and delivered once resumed or discarded.
+    pub fn set_events_paused(&self, paused: bool) {
+        self.0.events.set_paused(paused)
+    }
+
+    /// Whether event delivery is currently paused
+    pub fn events_paused(&self) -> bool {
+        self.0.events.is_paused()
+    }
+
+    /// Replaces the callback that receives the events of the tray icon, e.g. when an application moves from a setup phase to its main phase.
+    ///
+    /// The tray icon stays in place, so it keeps its position and identity. If the current callback is running,
+    /// it finishes the current event and the new callback receives all following events. The old callback is dropped,
+    /// which also ends the [AsyncTrayEvents] of a tray icon created with [TrayIconBuilder::build_async].
+    pub fn set_event_handler<F, R>(&self, callback: F)
+        where F: FnMut(TrayEvent<T>) -> R + Send + 'static, R: Into<ControlFlow>
+    {
+        self.0.events.set_callback(exit_on_request(&self.0.exit, callback))
+    }
+
+    /// Runs the platform event loop on the current thread until [TrayIcon::quit] is called
+    /// or the event callback returns [ControlFlow::Exit].
+    ///
+    /// This is meant for applications that don't have an event loop of their own.
+    /// On Linux this simply blocks as the events are handled by a background thread.
+    pub fn run(&self) {
+        if !self.0.exit.is_requested() {
+            self.native().run(&self.0.exit);
+        }
+    }
+
+    /// Runs `work` on a new thread while the platform event loop runs on the current thread like [TrayIcon::run]
+    ///
+    /// The event loop stops as soon as `work` returns, which lets command line tools keep their existing logic
+    /// while the status item on *Mac*, which can only be driven by the main thread, stays responsive.
+    /// If the event loop is stopped first, this waits for `work` to finish. Returns the result of `work`,
+    /// or the payload of its panic.
+    pub fn run_managed<F, R>(&self, work: F) -> std::thread::Result<R>
+        where F: FnOnce() -> R + Send + 'static, R: Send + 'static
+    {
+        // Also stops the event loop if `work` panics
+        struct ExitOnDrop(Arc<ExitSignal>);
+
+        impl Drop for ExitOnDrop {
+            fn drop(&mut self) {
+                self.0.request();
+            }
+        }
+
+        let guard = ExitOnDrop(self.0.exit.clone());
+        let worker = std::thread::spawn(move || {
+            let _guard = guard;
+            work()
+        });
+        self.run();
+        worker.join()
+    }
+
+    /// Stops the event loop started by [TrayIcon::run]
+    pub fn quit(&self) {
+        self.0.exit.request();
+    }
+
+    /// Shows a notification originating from the tray icon
+    ///
+    /// Clicks on the notification are reported as [TrayEvent::Notification].
+    pub fn show_notification(&self, notification: Notification) {
+        self.native().show_notification(notification)
+    }
+
+    /// Checks whether the user asked not to be disturbed by notifications
+    ///
+    /// On *Windows* this is the case while Focus Assist is set to quiet hours, a presentation is running or an application
+    /// is in full screen mode, but not while the session is locked. On *Linux* it's the `Inhibited` property of the notification
+    /// server, which not every server implements. The property is watched in the background, so this doesn't block, but
+    /// reports [ErrorSource::NotSupported] until the server replied. On *Mac* this always fails with [ErrorSource::NotSupported],
+    /// as Focus isn't exposed to applications without an entitlement.
+    /// Unknown states are reported as [ErrorSource::NotSupported].
+    ///
+    /// Use [Notification::with_respect_dnd] to skip notifications automatically.
+    pub fn is_do_not_disturb(&self) -> TrayResult<bool> {
+        self.native().is_do_not_disturb()
+    }
+
+    /// Returns a handle for modifying the current menu in place
+    pub fn menu(&self) -> MenuHandle<'_, T> {
+        MenuHandle(self)
+    }
+}
+
+impl<T: 'static> TrayIcon<T> {
+
+    /// Updates or removes the menu. Labels are sanitized like the tooltip, see [TrayIcon::set_tooltip].
+    pub fn set_menu(&self, menu: impl Into<Option<Menu<T>>>) {
+        let mut menu = menu.into();
+        if let Some(menu) = &mut menu {
+            menu.sanitize_labels();
+        }
+        {
+            let mut state = self.state();
+            state.menu = None;
+            state.native_menu = false;
+        }
+        self.update().set_menu(menu);
+    }
+
+    /// Replaces the menu with a `HMENU` that was built by hand, e.g. to use features that aren't wrapped by [Menu]
+    ///
+    /// Selecting an item whose command id is contained in `signals` emits a [TrayEvent::Menu] with the associated signal.
+    /// [TrayIcon::menu] can not modify a native menu. Calling [TrayIcon::set_menu] switches back to a regular menu.
+    ///
+    /// # Safety
+    /// `hmenu` must be a valid popup menu that outlives its use by the tray icon. The menu is not destroyed by the tray icon.
+    #[cfg(target_os = "windows")]
+    pub unsafe fn set_native_menu<I>(&self, hmenu: isize, signals: I)
+        where I: IntoIterator<Item=(u16, T)>
     {
-        Ok(TrayIcon(NativeTrayIcon::new(self, callback)?))
+        self.state().set_native_menu();
+        self.update().set_native_menu(hmenu, signals.into_iter().collect())
+    }
+
+    /// Replaces the menu with a `NSMenu` that was built by hand, e.g. to use features that aren't wrapped by [Menu]
+    ///
+    /// The items of the menu keep their own targets and actions, so they don't emit [TrayEvent::Menu].
+    /// [TrayIcon::menu] can not modify a native menu. Calling [TrayIcon::set_menu] switches back to a regular menu.
+    ///
+    /// # Safety
+    /// `menu` must point to a valid `NSMenu`. The tray icon retains the menu, so it may be released afterward.
+    #[cfg(target_os = "macos")]
+    pub unsafe fn set_native_menu(&self, menu: *mut std::ffi::c_void) {
+        self.state().set_native_menu();
+        self.update().set_native_menu(menu.cast())
+    }
+
+    /// Shows a short text like an unread count on the Dock icon of the application or removes it again
+    ///
+    /// The badge belongs to the application rather than the tray icon. Applications that are only shown in the menu bar
+    /// (the default when betrayer creates the `NSApplication`) have no Dock icon, so the badge is only visible if the
+    /// activation policy is changed to regular. Pass `None` to remove the badge. Only available on *Mac*.
+    #[cfg(target_os = "macos")]
+    pub fn set_dock_badge(&self, badge: Option<&str>) {
+        let badge = badge.map(|s| label::sanitize_label(s.into()).into_owned());
+        self.native().set_dock_badge(badge)
+    }
+
+    /// Bounces the Dock icon of the application while it's not active
+    ///
+    /// A `critical` request keeps bouncing until the user activates the application, otherwise it bounces once.
+    /// Does nothing if the application is already active. Only available on *Mac*.
+    #[cfg(target_os = "macos")]
+    pub fn request_user_attention(&self, critical: bool) {
+        self.native().request_user_attention(critical)
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl<T: Clone + Send + 'static> TrayIcon<T> {
+
+    /// Serves a hand written implementation of the `com.canonical.dbusmenu` interface instead of the menu generated from [Menu]
+    ///
+    /// This can be used to expose features that aren't wrapped by [Menu]. The interface handles its own events,
+    /// so it doesn't emit [TrayEvent::Menu], see [DBusMenuInterface::new].
+    /// [TrayIcon::menu] can not modify a native menu. Calling [TrayIcon::set_menu] switches back to a regular menu.
+    pub fn set_native_menu(&self, menu: DBusMenuInterface) {
+        self.state().set_native_menu();
+        self.update().set_native_menu(menu)
+    }
+}
+
+impl<T: Send + 'static> TrayIcon<T> {
+
+    /// Calls `callback` repeatedly with the given interval until the tray icon is dropped.
+    ///
+    /// The callback runs on the thread that drives the tray icon (the thread that created it on Windows and MacOS,
+    /// the background D-Bus thread on Linux), which makes it a convenient place for periodically refreshing the icon or tooltip.
+    pub fn set_interval<F>(&self, interval: Duration, mut callback: F)
+        where F: FnMut(&TrayIcon<T>) + Send + 'static
+    {
+        let tray = Arc::downgrade(&self.0);
+        self.native().set_interval(interval, Box::new(move || {
+            if let Some(tray) = tray.upgrade() {
+                callback(&TrayIcon(tray));
+            }
+        }))
+    }
+
+    /// Calls `callback` on the thread that drives the tray icon whenever the returned waker is woken from any thread
+    #[cfg(all(feature = "remote", any(target_os = "windows", target_os = "linux")))]
+    pub(crate) fn set_wakeup<F>(&self, mut callback: F) -> platform::TrayWaker
+        where F: FnMut(&TrayIcon<T>) + Send + 'static
+    {
+        let tray = Arc::downgrade(&self.0);
+        self.native().set_wakeup(Box::new(move || {
+            if let Some(tray) = tray.upgrade() {
+                callback(&TrayIcon(tray));
+            }
+        }))
+    }
+}
+
+impl<T: Clone + PartialEq + 'static> TrayIcon<T> {
+
+    /// Brings the tray icon into the given state.
+    ///
+    /// The new state is compared against the current one and only the parts that actually changed
+    /// are forwarded to the platform, so this can be called every frame or tick without causing
+    /// needless updates.
+    pub fn set_state(&self, mut state: TrayState<T>) {
+        if let Some(menu) = &mut state.menu {
+            menu.sanitize_labels();
+        }
+        let changes = self.state().changes(&state);
+        let TrayState { icon, tooltip, title, menu } = state;
+        if changes.icon {
+            self.set_icon(icon);
+        }
+        if changes.tooltip {
+            self.set_tooltip::<String>(tooltip);
+        }
+        if changes.title {
+            self.set_title::<String>(title);
+        }
+        if changes.menu {
+            {
+                let mut state = self.state();
+                state.menu = Some(menu.clone());
+                state.native_menu = false;
+            }
+            self.update().set_menu(menu);
+        }
     }
+}
+
+/// Resolves once the platform has applied the changes made before [TrayIcon::acknowledge] was called
+///
+/// On *Windows* and *Mac* changes are applied before the setter returns, so it's always ready.
+/// On *Linux* it resolves once the D-Bus properties are updated and their change signals are emitted.
+pub struct Applied(Acknowledgement);
+
+impl Future for Applied {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0).poll(cx).map(drop)
+    }
+}
+
+/// Enum for describing how a user clicked on the tray icon
+///
+/// **WARNING**: Click handling has major platform differences.
+///
+/// On *Windows* this works as you'd expect it to work
+///
+/// On *Linux* [ClickType::Double] should work as expect and [ClickType::Left] gets emitted by every action that opens the root menu. [ClickType::Right] never gets emitted.
+///
+/// On *Mac* [ClickType::Left] gets emitted by any actions that would open the menu, but **only** if no menu is present.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ClickType {
+    Left,
+    Right,
+    Double
+}
+
+/// The clicks that open the menu of a tray icon, see [TrayIconBuilder::with_menu_trigger]
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub enum MenuTrigger {
+    #[default]
+    RightClick,
+    LeftClick,
+    AnyClick
+}
+
+/// The direction in which the menu grows from the point it opens at, see [TrayIconBuilder::with_menu_placement]
+///
+/// The menu is still moved if it would otherwise leave the screen. Only *Windows* positions the menu itself,
+/// *Mac* and *Linux* ignore the placement.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct MenuPlacement {
+    pub vertical: VerticalGravity,
+    pub horizontal: HorizontalGravity
+}
+
+impl MenuPlacement {
+
+    /// Opens the menu in the given directions
+    pub fn new(vertical: VerticalGravity, horizontal: HorizontalGravity) -> Self {
+        Self { vertical, horizontal }
+    }
+
+}
+
+/// The vertical direction in which the menu grows, see [MenuPlacement]
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub enum VerticalGravity {
+    /// Grows away from the taskbar
+    #[default]
+    Automatic,
+    Up,
+    Down
+}
 
+/// The horizontal direction in which the menu grows, see [MenuPlacement]
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub enum HorizontalGravity {
+    /// Grows away from the taskbar
+    #[default]
+    Automatic,
+    Left,
+    Right
 }
 
-pub struct TrayIcon<T>(NativeTrayIcon<T>);
+/// The area a tray icon occupies on the screen in physical pixels, see [TrayEvent::ShowFlyout]
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IconRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32
+}
 
-impl<T> TrayIcon<T> {
+/// The state of the progress bar on a taskbar button, see [TrayIcon::set_taskbar_progress]
+///
+/// The values are fractions between `0.0` and `1.0`.
+#[cfg(all(target_os = "windows", feature = "taskbar-progress"))]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum TaskbarProgress {
+    /// Hides the progress bar
+    #[default]
+    None,
+    /// A pulsing bar for operations of unknown length
+    Indeterminate,
+    Normal(f32),
+    /// A yellow bar
+    Paused(f32),
+    /// A red bar
+    Error(f32)
+}
 
-    /// Updates or removes the tooltip
-    pub fn set_tooltip<S: ToString>(&self, tooltip: impl Into<Option<S>>) {
-        self.0.set_tooltip(tooltip.into().map(|s| s.to_string()))
-    }
+/// Controls whether clicks on the tray icon are interpreted as double clicks
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub enum ClickPolicy {
+    /// Double clicks are reported as [ClickType::Double]
+    #[default]
+    DetectDoubleClicks,
+    /// [ClickType::Double] is never emitted, every press is reported as [ClickType::Left] instead.
+    ///
+    /// Useful when the primary action should fire on every click. Has no effect on *Mac*, which never reports double clicks.
+    SinglesOnly
+}
 
-    pub fn set_icon(&self, icon: impl Into<Option<Icon>>) {
-        self.0.set_icon(icon.into())
-    }
+/// The platform mechanism used to display tray icons
+///
+/// Useful to adjust user facing text (e.g. "menu bar" vs "system tray") or for bug reports.
+#[non_exhaustive]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Backend {
+    /// The Windows shell notification area
+    WindowsShell,
+    /// An `NSStatusItem` in the macOS menu bar
+    MacStatusItem,
+    /// The `org.kde.StatusNotifierItem` DBus api
+    LinuxSni,
+    /// The stub backend of platforms without tray icon support. Creating a tray icon always fails.
+    Unsupported
 }
 
-impl<T: 'static> TrayIcon<T> {
+impl Backend {
 
-    /// Updates or removes the menu
-    pub fn set_menu(&self, menu: impl Into<Option<Menu<T>>>) {
-        self.0.set_menu(menu.into())
+    /// The backend selected for the current platform
+    pub const fn current() -> Self {
+        platform::BACKEND
     }
+
 }
 
-/// Enum for describing how a user clicked on the tray icon
-///
-/// **WARNING**: Click handling has major platform differences.
-///
-/// On *Windows* this works as you'd expect it to work
-///
-/// On *Linux* [ClickType::Double] should work as expect and [ClickType::Left] gets emitted by every action that opens the root menu. [ClickType::Right] never gets emitted.
-///
-/// On *Mac* [ClickType::Left] gets emitted by any actions that would open the menu, but **only** if no menu is present.
+/// The direction of a [TrayEvent::Scroll]
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
-pub enum ClickType {
-    Left,
-    Right,
-    Double
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ScrollOrientation {
+    Vertical,
+    Horizontal
 }
 
 /// An event describing how the user interacted with the tray icon or associated menu
 ///
 /// See the docs of [ClickType] for platform specific notes about mouse click events
 ///
-/// The parameter of the [TrayEvent::Menu] variant is a copy of the signal token assigned to the respective [MenuItem]
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+/// The `signal` of the [TrayEvent::Menu] variant is a copy of the signal token assigned to the respective [MenuItem]
+///
+/// With the `serde` feature events can be serialized whenever the signal type can, e.g. to forward them to another process.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TrayEvent<T> {
-    Tray(ClickType),
-    Menu(T)
+    /// The user clicked the tray icon
+    Tray {
+        click: ClickType,
+        /// The XDG activation token of the click, see [TrayEvent::Menu]
+        activation_token: Option<String>
+    },
+    /// The user selected a [MenuItem]
+    Menu {
+        signal: T,
+        /// The XDG activation token of the selection
+        ///
+        /// Wayland compositors only let applications focus their windows with a token from the interaction that caused it,
+        /// so pass it to the windowing library, e.g. to `xdg_activation_v1`.
+        /// Only provided on *Linux* and only if the host supports it.
+        activation_token: Option<String>
+    },
+    /// The user scrolled over the tray icon. See [TrayIconBuilder::with_max_scroll_rate] for rate limiting.
+    ///
+    /// Only emitted on *Linux*.
+    Scroll {
+        orientation: ScrollOrientation,
+        delta: i32
+    },
+    /// The system resumed from sleep or hibernation. The tray icon has already been restored at this point.
+    Resumed,
+    /// The taskbar (*Windows*) or the StatusNotifierWatcher (*Linux*) restarted, e.g. after `explorer.exe` crashed.
+    ///
+    /// The tray icon has already been restored at this point, but any other per-session registrations of the application
+    /// may have to be redone. Never emitted on *Mac*.
+    HostRestarted,
+    /// The user is logging out or the system is shutting down. The process may get terminated shortly after this event.
+    SessionEnding,
+    /// The user interacted with a notification shown by [TrayIcon::show_notification]
+    Notification(NotificationEvent),
+    /// The tooltip is about to be shown because the user hovers over the icon
+    ///
+    /// Use [TrayIconBuilder::with_tooltip_provider] to compute the contents of the tooltip that is shown. Only emitted on *Windows*.
+    TooltipShown,
+    /// The tooltip was hidden again. Only emitted on *Windows*.
+    TooltipHidden,
+    /// The user hovers over the icon and the application should show its flyout next to `anchor`
+    ///
+    /// Replaces [TrayEvent::TooltipShown] when [TrayIconBuilder::with_hover_flyout] is set. Only emitted on *Windows*.
+    ShowFlyout {
+        anchor: IconRect
+    },
+    /// The pointer left the icon and the flyout should be hidden again. Only emitted on *Windows*.
+    HideFlyout,
+    /// The application was started again while this instance was running, see [SingleInstance]
+    Activate
+}
+
+impl<T> TrayEvent<T> {
+
+    /// Converts the signal of a [TrayEvent::Menu] event, leaving all other events untouched
+    pub fn map<U, F>(self, func: F) -> TrayEvent<U>
+        where F: FnOnce(T) -> U
+    {
+        match self {
+            TrayEvent::Tray { click, activation_token } => TrayEvent::Tray { click, activation_token },
+            TrayEvent::Menu { signal, activation_token } => TrayEvent::Menu { signal: func(signal), activation_token },
+            TrayEvent::Scroll { orientation, delta } => TrayEvent::Scroll { orientation, delta },
+            TrayEvent::Resumed => TrayEvent::Resumed,
+            TrayEvent::HostRestarted => TrayEvent::HostRestarted,
+            TrayEvent::SessionEnding => TrayEvent::SessionEnding,
+            TrayEvent::Notification(event) => TrayEvent::Notification(event),
+            TrayEvent::TooltipShown => TrayEvent::TooltipShown,
+            TrayEvent::TooltipHidden => TrayEvent::TooltipHidden,
+            TrayEvent::ShowFlyout { anchor } => TrayEvent::ShowFlyout { anchor },
+            TrayEvent::HideFlyout => TrayEvent::HideFlyout,
+            TrayEvent::Activate => TrayEvent::Activate
+        }
+    }
+
 }
 
 /// A struct describing the layout of a tray icon menu
@@ -119,6 +1227,24 @@ pub struct Menu<T> {
     items: Vec<MenuItem<T>>
 }
 
+impl<T> Default for Menu<T> {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl<T> FromIterator<MenuItem<T>> for Menu<T> {
+    fn from_iter<I: IntoIterator<Item=MenuItem<T>>>(iter: I) -> Self {
+        Self::new(iter)
+    }
+}
+
+impl<T> Extend<MenuItem<T>> for Menu<T> {
+    fn extend<I: IntoIterator<Item=MenuItem<T>>>(&mut self, iter: I) {
+        self.items.extend(iter)
+    }
+}
+
 impl<T> Menu<T> {
 
     /// Create a new menu with the given children
@@ -130,6 +1256,15 @@ impl<T> Menu<T> {
         }
     }
 
+    /// Creates a flat menu of buttons from label/signal pairs
+    ///
+    /// `Menu::from_pairs([("Open", Signal::Open), ("Quit", Signal::Quit)])`
+    pub fn from_pairs<S, I>(pairs: I) -> Self
+        where S: ToString, I: IntoIterator<Item=(S, T)>
+    {
+        Self::new(MenuItem::buttons(pairs))
+    }
+
     /// Creates a new empty menu
     pub fn empty() -> Self {
         Self {
@@ -137,24 +1272,281 @@ impl<T> Menu<T> {
         }
     }
 
+    /// Calls `func` with every label of the menu, stopping at the first error
+    fn visit_labels<E>(&mut self, func: &mut impl FnMut(&mut String) -> Result<(), E>) -> Result<(), E> {
+        self.items
+            .iter_mut()
+            .try_for_each(|item| item.visit_labels(func))
+    }
+
+    fn sanitize_labels(&mut self) {
+        let _ = self.visit_labels(&mut |label| {
+            if let Cow::Owned(sanitized) = label::sanitize_label(Cow::Borrowed(label)) {
+                *label = sanitized;
+            }
+            Ok::<_, ()>(())
+        });
+    }
+
+    /// Converts the signals of all items, e.g. to embed a reusable menu with its own signal type into a larger menu
+    pub fn map_signal<U, F>(self, mut func: F) -> Menu<U>
+        where F: FnMut(T) -> U
+    {
+        Menu {
+            items: self.items
+                .into_iter()
+                .map(|item| item.map_signal_with(&mut func))
+                .collect(),
+        }
+    }
+
+    /// Replaces the signals with sequential indices, in the order the items appear in the menu (including submenus and alternates)
+    ///
+    /// Useful for menus that are generated from runtime lists, see [TrayIconBuilder::build_enumerated].
+    pub fn enumerated(self) -> Menu<usize> {
+        let mut next = 0;
+        self.map_signal(|_| {
+            next += 1;
+            next - 1
+        })
+    }
+
+    fn move_item(&mut self, id: &MenuItemId, new_index: usize) {
+        let Some((path, index)) = id.split() else {
+            return;
+        };
+        let items = path
+            .iter()
+            .try_fold(&mut self.items, |items, i| match items.get_mut(*i) {
+                Some(MenuItem::Menu { children, .. }) => Some(children),
+                _ => None
+            });
+        if let Some(items) = items {
+            if index < items.len() && new_index < items.len() {
+                let item = items.remove(index);
+                items.insert(new_index, item);
+            }
+        }
+    }
+
+    /// Returns the item with the given id
+    pub fn get(&self, id: &MenuItemId) -> Option<&MenuItem<T>> {
+        let (first, rest) = id.path().split_first()?;
+        rest
+            .iter()
+            .try_fold(self.items.get(*first)?, |item, i| match item {
+                MenuItem::Menu { children, .. } => children.get(*i),
+                _ => None
+            })
+    }
+
+    fn get_mut(&mut self, id: &MenuItemId) -> Option<&mut MenuItem<T>> {
+        let (first, rest) = id.path().split_first()?;
+        rest
+            .iter()
+            .try_fold(self.items.get_mut(*first)?, |item, i| match item {
+                MenuItem::Menu { children, .. } => children.get_mut(*i),
+                _ => None
+            })
+    }
+
+    /// Finds an item by following a path of labels, e.g. `&["Profiles", "Profile 1"]`
+    ///
+    /// At every level the first item with a matching label is chosen.
+    pub fn find(&self, path: &[&str]) -> Option<MenuItemId> {
+        let mut items = self.items.as_slice();
+        let mut id = Vec::with_capacity(path.len());
+        for (depth, label) in path.iter().enumerate() {
+            let (index, item) = items
+                .iter()
+                .enumerate()
+                .find(|(_, item)| item.label() == Some(*label))?;
+            id.push(index);
+            match item {
+                MenuItem::Menu { children, .. } => items = children,
+                _ if depth + 1 < path.len() => return None,
+                _ => {}
+            }
+        }
+        (!id.is_empty()).then_some(MenuItemId(id))
+    }
+
+    /// Finds the first item with the given label anywhere in the menu (depth first)
+    pub fn find_by_label(&self, label: &str) -> Option<MenuItemId> {
+        fn search<T>(items: &[MenuItem<T>], label: &str, id: &mut Vec<usize>) -> bool {
+            for (index, item) in items.iter().enumerate() {
+                id.push(index);
+                if item.label() == Some(label) {
+                    return true;
+                }
+                if let MenuItem::Menu { children, .. } = item {
+                    if search(children, label, id) {
+                        return true;
+                    }
+                }
+                id.pop();
+            }
+            false
+        }
+        let mut id = Vec::new();
+        search(&self.items, label, &mut id).then_some(MenuItemId(id))
+    }
+
+}
+
+/// A signal type that knows how to build its own [Menu]
+///
+/// With the `derive` feature enabled this trait can be derived for enums with unit variants:
+/// ```ignore
+/// #[derive(Clone, TraySignal)]
+/// enum Signal {
+///     Open,
+///     #[tray(label = "Quit", separator_before)]
+///     Exit
+/// }
+///
+/// let menu = Signal::menu();
+/// ```
+pub trait TraySignal: Sized {
+    /// Creates a menu containing the items described by this type
+    fn menu() -> Menu<Self>;
+}
+
+/// Identifies a [MenuItem] by its position inside a [Menu]
+///
+/// The id consists of the child indices leading from the root of the menu to the item,
+/// so `[1, 0]` refers to the first child of the second top-level item.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct MenuItemId(Vec<usize>);
+
+impl MenuItemId {
+
+    /// Creates a new id from a list of child indices
+    pub fn new<I>(path: I) -> Self
+        where I: IntoIterator<Item=usize>
+    {
+        Self(path.into_iter().collect())
+    }
+
+    /// The child indices leading from the root of the menu to the item
+    pub fn path(&self) -> &[usize] {
+        &self.0
+    }
+
+    /// Splits the id into the path of the parent menu and the index within that menu
+    pub(crate) fn split(&self) -> Option<(&[usize], usize)> {
+        self.0
+            .split_last()
+            .map(|(index, parent)| (parent, *index))
+    }
+
+}
+
+impl<const N: usize> From<[usize; N]> for MenuItemId {
+    fn from(value: [usize; N]) -> Self {
+        Self::new(value)
+    }
+}
+
+/// A handle for modifying the menu of a [TrayIcon] without replacing it
+///
+/// Changes made through this handle are applied directly to the native menu,
+/// which avoids rebuilding it and keeps the state of any open submenus intact.
+pub struct MenuHandle<'a, T>(&'a TrayIcon<T>);
+
+impl<'a, T> MenuHandle<'a, T> {
+
+    /// Moves the item to `new_index` within its parent menu, shifting the items in between.
+    ///
+    /// Invalid ids or indices are logged and otherwise ignored. Fails with [ErrorSource::NativeMenu] while a native menu is set.
+    pub fn move_item(&self, id: impl Into<MenuItemId>, new_index: usize) -> TrayResult<()> {
+        let id = id.into();
+        {
+            let mut state = self.0.state();
+            ensure!(!state.native_menu, TrayError::from(ErrorSource::NativeMenu));
+            if let Some(Some(menu)) = state.menu.as_mut() {
+                menu.move_item(&id, new_index);
+            }
+        }
+        self.0.update().move_item(&id, new_index);
+        Ok(())
+    }
+
+    /// Returns the id the platform uses for the item, e.g. for native menu code or automation tools
+    ///
+    /// * *Windows*: The command id that `TrackPopupMenu` returns or `WM_COMMAND` carries. Only buttons have one.
+    /// * *Mac*: The tag of the `NSMenuItem`. Only buttons have one.
+    /// * *Linux*: The id of the dbusmenu item. Menus are updated asynchronously, so the ids may lag behind
+    ///   a preceding [TrayIcon::set_menu] for a moment.
+    ///
+    /// The ids stay the same until the menu is replaced. Fails with [ErrorSource::NativeMenu] while a native menu is set.
+    pub fn native_id(&self, id: impl Into<MenuItemId>) -> TrayResult<Option<u32>> {
+        ensure!(!self.0.state().native_menu, TrayError::from(ErrorSource::NativeMenu));
+        Ok(self.0.native().native_id(&id.into()))
+    }
+
+    /// Shows or hides the item without rebuilding the menu. Hidden items keep their position.
+    ///
+    /// Invalid ids and separators are logged and otherwise ignored. Fails with [ErrorSource::NativeMenu] while a native menu is set.
+    pub fn set_visible(&self, id: impl Into<MenuItemId>, visible: bool) -> TrayResult<()> {
+        let id = id.into();
+        {
+            // Without a copy of the menu, invalid ids are reported by the platform
+            let mut state = self.0.state();
+            ensure!(!state.native_menu, TrayError::from(ErrorSource::NativeMenu));
+            if let Some(menu) = state.menu.as_mut() {
+                let flag = menu
+                    .as_mut()
+                    .and_then(|menu| menu.get_mut(&id))
+                    .and_then(MenuItem::visible_mut);
+                match flag {
+                    Some(flag) if *flag == visible => return Ok(()),
+                    Some(flag) => *flag = visible,
+                    None => {
+                        log::warn!("Can not change the visibility of menu item {id:?}");
+                        return Ok(());
+                    }
+                }
+            }
+        }
+        self.0.update().set_visible(&id, visible);
+        Ok(())
+    }
+
 }
 
 /// Various menu items that can be added to a [Menu]
+///
+/// Items are created with [MenuItem::button], [MenuItem::check_button], [MenuItem::menu] and [MenuItem::separator]
+/// and customized with the `with_*` methods, so that new options don't break existing code.
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum MenuItem<T> {
     Separator,
+    #[non_exhaustive]
     CheckButton {
         name: String,
         signal: T,
-        checked: bool
+        checked: bool,
+        shortcut: Option<Shortcut>,
+        state_images: StateImages,
+        style: ItemStyle,
+        visible: bool
     },
+    #[non_exhaustive]
     Button {
         name: String,
         signal: T,
+        shortcut: Option<Shortcut>,
+        alternate: Option<AlternateItem<T>>,
+        style: ItemStyle,
+        visible: bool
     },
+    #[non_exhaustive]
     Menu {
         name: String,
-        children: Vec<MenuItem<T>>
+        children: Vec<MenuItem<T>>,
+        visible: bool
     }
 }
 
@@ -172,9 +1564,22 @@ impl<T> MenuItem<T> {
         Self::Button {
             name: name.to_string(),
             signal,
+            shortcut: None,
+            alternate: None,
+            style: ItemStyle::Normal,
+            visible: true,
         }
     }
 
+    /// Creates a button for every label/signal pair
+    pub fn buttons<S, I>(pairs: I) -> impl Iterator<Item=Self>
+        where S: ToString, I: IntoIterator<Item=(S, T)>
+    {
+        pairs
+            .into_iter()
+            .map(|(name, signal)| Self::button(name, signal))
+    }
+
     /// A new clickable entry with label and checkmark that emits a [TrayEvent::Menu] when clicked
     pub fn check_button<S>(name: S, signal: T, checked: bool) -> Self
         where S: ToString
@@ -183,6 +1588,10 @@ impl<T> MenuItem<T> {
             name: name.to_string(),
             signal,
             checked,
+            shortcut: None,
+            state_images: StateImages::default(),
+            style: ItemStyle::Normal,
+            visible: true,
         }
     }
 
@@ -193,39 +1602,457 @@ impl<T> MenuItem<T> {
         Self::Menu {
             name: name.to_string(),
             children: children.into_iter().collect(),
+            visible: true,
+        }
+    }
+
+    /// Assigns a keyboard shortcut to a button. Does nothing for separators and submenus.
+    pub fn with_shortcut(mut self, key: Shortcut) -> Self {
+        if let MenuItem::Button { shortcut, .. } | MenuItem::CheckButton { shortcut, .. } = &mut self {
+            *shortcut = Some(key);
+        }
+        self
+    }
+
+    /// Replaces the checkmark of a check button with custom images. Does nothing for other items.
+    ///
+    /// The images are rasterized at the size of the checkmark for the current DPI, so they don't get stretched by the system.
+    /// Supported on *Mac* and *Windows*. *Windows* has no mixed state and shows nothing for a state without an image.
+    pub fn with_state_images(mut self, images: StateImages) -> Self {
+        if let MenuItem::CheckButton { state_images, .. } = &mut self {
+            *state_images = images;
+        }
+        self
+    }
+
+    /// Adds an alternate version of a button that replaces it while the option key is held down. Does nothing for other items.
+    ///
+    /// Only supported on *Mac*.
+    pub fn with_alternate<S>(mut self, name: S, signal: T) -> Self
+        where S: ToString
+    {
+        if let MenuItem::Button { alternate, .. } = &mut self {
+            *alternate = Some(AlternateItem {
+                name: name.to_string(),
+                signal,
+            });
+        }
+        self
+    }
+
+    /// Changes how a button is emphasized, e.g. to make dangerous actions stand out. Does nothing for separators and submenus.
+    pub fn with_style(mut self, item_style: ItemStyle) -> Self {
+        if let MenuItem::Button { style, .. } | MenuItem::CheckButton { style, .. } = &mut self {
+            *style = item_style;
+        }
+        self
+    }
+
+    /// Converts the signals of this item and all of its children
+    pub fn map_signal<U, F>(self, mut func: F) -> MenuItem<U>
+        where F: FnMut(T) -> U
+    {
+        self.map_signal_with(&mut func)
+    }
+
+    fn map_signal_with<U>(self, func: &mut impl FnMut(T) -> U) -> MenuItem<U> {
+        match self {
+            MenuItem::Separator => MenuItem::Separator,
+            MenuItem::CheckButton { name, signal, checked, shortcut, state_images, style, visible } => MenuItem::CheckButton {
+                name,
+                signal: func(signal),
+                checked,
+                shortcut,
+                state_images,
+                style,
+                visible,
+            },
+            MenuItem::Button { name, signal, shortcut, alternate, style, visible } => MenuItem::Button {
+                name,
+                signal: func(signal),
+                shortcut,
+                alternate: alternate.map(|alternate| AlternateItem {
+                    name: alternate.name,
+                    signal: func(alternate.signal),
+                }),
+                style,
+                visible,
+            },
+            MenuItem::Menu { name, children, visible } => MenuItem::Menu {
+                name,
+                children: children
+                    .into_iter()
+                    .map(|child| child.map_signal_with(func))
+                    .collect(),
+                visible,
+            }
+        }
+    }
+
+    /// Sets whether the item is shown. Hidden items keep their position and can be shown again with [MenuHandle::set_visible].
+    ///
+    /// Separators are always visible.
+    pub fn with_visible(mut self, visible: bool) -> Self {
+        if let Some(flag) = self.visible_mut() {
+            *flag = visible;
+        }
+        self
+    }
+
+    /// Whether the item is shown
+    pub fn is_visible(&self) -> bool {
+        match self {
+            MenuItem::Separator => true,
+            MenuItem::CheckButton { visible, .. } |
+            MenuItem::Button { visible, .. } |
+            MenuItem::Menu { visible, .. } => *visible
+        }
+    }
+
+    fn visible_mut(&mut self) -> Option<&mut bool> {
+        match self {
+            MenuItem::Separator => None,
+            MenuItem::CheckButton { visible, .. } |
+            MenuItem::Button { visible, .. } |
+            MenuItem::Menu { visible, .. } => Some(visible)
+        }
+    }
+
+    /// Calls `func` with the label of the item, its alternate and all of its children
+    fn visit_labels<E>(&mut self, func: &mut impl FnMut(&mut String) -> Result<(), E>) -> Result<(), E> {
+        match self {
+            MenuItem::Separator => Ok(()),
+            MenuItem::CheckButton { name, .. } => func(name),
+            MenuItem::Button { name, alternate, .. } => {
+                func(name)?;
+                match alternate {
+                    Some(alternate) => func(&mut alternate.name),
+                    None => Ok(())
+                }
+            }
+            MenuItem::Menu { name, children, .. } => {
+                func(name)?;
+                children
+                    .iter_mut()
+                    .try_for_each(|child| child.visit_labels(func))
+            }
+        }
+    }
+
+    /// The label of the item or `None` for separators
+    pub fn label(&self) -> Option<&str> {
+        match self {
+            MenuItem::Separator => None,
+            MenuItem::CheckButton { name, .. } |
+            MenuItem::Button { name, .. } |
+            MenuItem::Menu { name, .. } => Some(name)
         }
     }
 
 }
 
+/// A hint for how a [MenuItem] should be emphasized
+///
+/// The exact appearance depends on the platform:
+/// * *Windows*: Destructive items are drawn in red and highlighted items in bold
+/// * *Mac*: Destructive items use the system red and highlighted items a bold font
+/// * *Linux*: Mapped to the `disposition` property of dbusmenu, which hosts may render as they see fit
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ItemStyle {
+    #[default]
+    Normal,
+    /// An action that can't be undone, like deleting data or disconnecting
+    Destructive,
+    /// An action that deserves more attention than the surrounding ones
+    Highlighted
+}
+
+/// The alternate version of a [MenuItem::Button] that is shown while the option key is held down
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AlternateItem<T> {
+    pub name: String,
+    pub signal: T
+}
+
+/// Custom images that replace the checkmark of a [MenuItem::CheckButton]
+///
+/// States without an image fall back to the platform default.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct StateImages {
+    pub on: Option<Icon>,
+    pub off: Option<Icon>,
+    pub mixed: Option<Icon>
+}
+
 /// An icon struct
 #[derive(Debug, Clone, Eq, PartialEq)]
-pub struct Icon(NativeIcon);
+pub struct Icon(NativeIcon, Option<IconFile>);
+
+#[cfg(feature = "watch")]
+type IconFile = watch::WatchedFile;
+#[cfg(not(feature = "watch"))]
+type IconFile = std::convert::Infallible;
 
 impl Icon {
 
+    /// The maximum width and height of an icon
+    pub const MAX_SIZE: u32 = 1024;
+
+    fn new(native: NativeIcon) -> Self {
+        Icon(native, None)
+    }
+
     /// Creates a new icon from raw RGBA data
+    ///
+    /// Fails with an [IconError] if the buffer is empty, the dimensions don't match its length or exceed [Icon::MAX_SIZE].
     pub fn from_rgba(rgba: Vec<u8>, width: u32, height: u32) -> TrayResult<Self> {
-        ensure!(rgba.len() as u32 == width * height * 4, TrayError::custom("Invalid dimensions"));
-        Ok(Icon(NativeIcon::from_rgba(rgba, width, height)?))
+        Self::check_buffer(rgba.len(), width, height)?;
+        Ok(Icon::new(NativeIcon::from_rgba(rgba, width, height)?))
+    }
+
+    /// Creates a monochrome template icon from colored RGBA data, so a single colored asset still looks native in the menu bar
+    ///
+    /// Only the alpha channel of the image is kept. On *Mac* the icon is marked as a template image, which the system tints
+    /// to match the appearance of the menu bar. Other platforms don't tint icons, so the shape is filled with `color` instead,
+    /// e.g. white for the usually dark panels on *Linux* and *Windows*.
+    pub fn template_from_rgba(mut rgba: Vec<u8>, width: u32, height: u32, color: [u8; 3]) -> TrayResult<Self> {
+        let color = match cfg!(target_os = "macos") {
+            true => [0, 0, 0],
+            false => color
+        };
+        for pixel in rgba.chunks_exact_mut(4) {
+            pixel[..3].copy_from_slice(&color);
+        }
+        #[allow(unused_mut)]
+        let mut icon = Self::from_rgba(rgba, width, height)?;
+        #[cfg(target_os = "macos")]
+        icon.0.set_template(true);
+        Ok(icon)
     }
 
     /// Creates a new icon from png encoded image data
+    ///
+    /// Fails with an [IconError] if the buffer is empty or the image exceeds [Icon::MAX_SIZE].
     #[cfg(target_os = "linux")]
     pub fn from_png_bytes(bytes: &[u8]) -> TrayResult<Self> {
-        Ok(Icon(NativeIcon::from_png_bytes(bytes)?))
+        ensure!(!bytes.is_empty(), TrayError::from(IconError::EmptyBuffer));
+        let (width, height) = platform::png_dimensions(bytes)?;
+        Self::check_size(width, height)?;
+        Ok(Icon::new(NativeIcon::from_png_bytes(bytes)?))
+    }
+
+    /// Creates a new icon from ARGB32 data in network byte order, the format of the `IconPixmap` property of the
+    /// StatusNotifierItem api
+    ///
+    /// The data is handed to the host as is, which avoids encoding the image again when it already comes from another
+    /// toolkit (e.g. `libappindicator` or `KStatusNotifierItem` code). Fails with an [IconError] like [Icon::from_rgba].
+    ///
+    /// Only available on *Linux*.
+    #[cfg(target_os = "linux")]
+    pub fn from_pixmap(argb: Vec<u8>, width: u32, height: u32) -> TrayResult<Self> {
+        Self::check_buffer(argb.len(), width, height)?;
+        Ok(Icon::new(NativeIcon::from_pixmap(argb, width, height)))
+    }
+
+    /// Creates a new icon from a `HICON`, e.g. one that was loaded by another toolkit
+    ///
+    /// The icon is copied, so `hicon` stays owned by the caller and may be destroyed afterward.
+    ///
+    /// # Safety
+    /// `hicon` must be a valid icon handle.
+    #[cfg(target_os = "windows")]
+    pub unsafe fn from_hicon(hicon: isize) -> TrayResult<Self> {
+        Ok(Icon::new(NativeIcon::from_hicon(hicon)?))
+    }
+
+    /// Creates a new icon from a `NSImage`, e.g. one that was loaded by another toolkit
+    ///
+    /// The image is shown at the size of the menu bar, keeping its aspect ratio. Images marked as template stay templates.
+    ///
+    /// # Safety
+    /// `image` must point to a valid `NSImage` that no other thread uses during the call. The icon keeps an encoded copy of the image,
+    /// so later changes to it have no effect.
+    #[cfg(target_os = "macos")]
+    pub unsafe fn from_nsimage(image: *mut std::ffi::c_void) -> TrayResult<Self> {
+        Ok(Icon::new(NativeIcon::from_nsimage(image.cast())?))
+    }
+
+    /// Checks that a buffer with four bytes per pixel matches the dimensions of the image
+    #[track_caller]
+    fn check_buffer(len: usize, width: u32, height: u32) -> TrayResult<()> {
+        ensure!(len > 0, TrayError::from(IconError::EmptyBuffer));
+        let expected = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|pixels| pixels.checked_mul(4));
+        ensure!(width > 0 && height > 0 && expected == Some(len), TrayError::from(IconError::BadDimensions { width, height, len }));
+        Self::check_size(width, height)
+    }
+
+    #[track_caller]
+    fn check_size(width: u32, height: u32) -> TrayResult<()> {
+        ensure!(width <= Self::MAX_SIZE && height <= Self::MAX_SIZE, TrayError::from(IconError::TooLarge { width, height }));
+        Ok(())
     }
 
     /// Creates a new icon from an embedded resource
     #[cfg(target_os = "windows")]
     pub fn from_resource(resource_id: u16, size: Option<(u32, u32)>) -> TrayResult<Self> {
-        Ok(Icon(NativeIcon::from_resource(resource_id, size)?))
+        Ok(Icon::new(NativeIcon::from_resource(resource_id, size)?))
+    }
+
+    /// Loads a png image from a file and keeps watching the file for changes
+    ///
+    /// Tray icons that show the returned icon reload it shortly after the file changes, which is handy when the icon
+    /// is generated by another process. Watching stops once the icon and all of its clones are dropped.
+    #[cfg(feature = "watch")]
+    pub fn from_path_watched<P: AsRef<std::path::Path>>(path: P) -> TrayResult<Self> {
+        let (icon, file) = watch::WatchedFile::open(path.as_ref())?;
+        Ok(Icon(icon.0, Some(file)))
+    }
+
+    /// Loads the image again if it comes from a watched file that changed. Returns whether the image was updated.
+    #[cfg(feature = "watch")]
+    fn reload(&mut self) -> bool {
+        match self.1.as_mut().and_then(watch::WatchedFile::reload) {
+            Some(native) => {
+                self.0 = native;
+                true
+            }
+            None => false
+        }
+    }
+
+}
+
+/// A possible source for the image of a tray icon. See [TrayIconBuilder::with_icon_sources].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum IconSource {
+    /// The name of an icon from the freedesktop icon theme, like `network-wired`. Only supported on *Linux*.
+    ThemeName(String),
+    /// An image that is supported everywhere
+    Image(Icon)
+}
+
+impl IconSource {
+
+    /// Shorthand for [IconSource::ThemeName]
+    pub fn theme<S: ToString>(name: S) -> Self {
+        Self::ThemeName(name.to_string())
+    }
+
+    /// Returns the theme name (if supported on this platform) and the image to use
+    fn resolve<I: IntoIterator<Item=IconSource>>(sources: I) -> (Option<String>, Option<Icon>) {
+        let mut name = None;
+        for source in sources {
+            match source {
+                IconSource::ThemeName(theme_name) if cfg!(target_os = "linux") => {
+                    name.get_or_insert(theme_name);
+                }
+                IconSource::ThemeName(_) => {}
+                IconSource::Image(icon) => return (name, Some(icon))
+            }
+        }
+        (name, None)
     }
 
 }
 
+impl From<Icon> for IconSource {
+    fn from(value: Icon) -> Self {
+        Self::Image(value)
+    }
+}
+
 impl From<Icon> for NativeIcon {
     fn from(value: Icon) -> Self {
         value.0
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn current_state() -> CurrentState<u32> {
+        CurrentState {
+            icon: None,
+            tooltip: Some(String::from("Tooltip")),
+            title: None,
+            menu: Some(Some(Menu::new([MenuItem::button("Quit", 0)]))),
+            native_menu: false
+        }
+    }
+
+    fn tray_state() -> TrayState<u32> {
+        TrayState {
+            icon: None,
+            tooltip: Some(String::from("Tooltip")),
+            title: None,
+            menu: Some(Menu::new([MenuItem::button("Quit", 0)]))
+        }
+    }
+
+    #[test]
+    fn unchanged_state_has_no_changes() {
+        assert_eq!(current_state().changes(&tray_state()), StateChanges::default());
+    }
+
+    #[test]
+    fn changes_are_detected_individually() {
+        let icon = Icon::from_rgba(vec![0; 4], 1, 1).unwrap();
+        let state = TrayState { icon: Some(icon), ..tray_state() };
+        assert_eq!(current_state().changes(&state), StateChanges { icon: true, ..Default::default() });
+
+        let state = TrayState { tooltip: None, ..tray_state() };
+        assert_eq!(current_state().changes(&state), StateChanges { tooltip: true, ..Default::default() });
+
+        let state = TrayState { title: Some(String::from("Title")), ..tray_state() };
+        assert_eq!(current_state().changes(&state), StateChanges { title: true, ..Default::default() });
+
+        let state = TrayState { menu: Some(Menu::new([MenuItem::button("Exit", 0)])), ..tray_state() };
+        assert_eq!(current_state().changes(&state), StateChanges { menu: true, ..Default::default() });
+    }
+
+    #[test]
+    fn unknown_menu_is_always_replaced() {
+        let mut current = current_state();
+        current.menu = None;
+        assert!(current.changes(&tray_state()).menu);
+        assert!(current.changes(&TrayState { menu: None, ..tray_state() }).menu);
+    }
+
+    #[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
+    #[test]
+    fn native_menu_is_replaced() {
+        let mut current = current_state();
+        current.set_native_menu();
+        assert!(current.native_menu);
+        assert!(current.changes(&tray_state()).menu);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn events_survive_serialization() {
+        let events = [
+            TrayEvent::Tray { click: ClickType::Double, activation_token: None },
+            TrayEvent::Menu { signal: String::from("quit"), activation_token: Some(String::from("token")) },
+            TrayEvent::Scroll { orientation: ScrollOrientation::Horizontal, delta: -3 },
+            TrayEvent::Notification(NotificationEvent::Action(1)),
+            TrayEvent::ShowFlyout { anchor: IconRect { x: 1, y: 2, width: 3, height: 4 } },
+            TrayEvent::Activate
+        ];
+        for event in events {
+            let json = serde_json::to_string(&event).unwrap();
+            assert_eq!(serde_json::from_str::<TrayEvent<String>>(&json).unwrap(), event, "{json}");
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn events_are_externally_tagged() {
+        let event = TrayEvent::Menu { signal: 7, activation_token: None };
+        assert_eq!(serde_json::to_string(&event).unwrap(), r#"{"Menu":{"signal":7,"activation_token":null}}"#);
+        assert_eq!(serde_json::to_string(&TrayEvent::<u32>::Resumed).unwrap(), r#""Resumed""#);
+    }
+
+}
@@ -0,0 +1,30 @@
+//! Internal logging macros
+//!
+//! With the `log` feature (enabled by default) these forward to the [`log`](https://docs.rs/log) crate.
+//! Without it every log statement is compiled out, so no log messages end up in the binary.
+
+#[cfg(feature = "log")]
+pub(crate) use ::log::{debug, trace, warn};
+
+#[cfg(not(feature = "log"))]
+macro_rules! __disabled {
+    ($($arg:tt)+) => {
+        // Keeps the arguments "used" without emitting any code
+        if false {
+            let _ = format_args!($($arg)+);
+        }
+    };
+}
+
+#[cfg(not(feature = "log"))]
+pub(crate) use {__disabled as debug, __disabled as trace, __disabled as warn};
+
+/// Logs a failed operation of the platform as a warning and counts it in [TrayMetrics::native_errors](crate::TrayMetrics::native_errors)
+macro_rules! native_error {
+    ($($arg:tt)+) => {{
+        $crate::metrics::count($crate::metrics::Counter::NativeErrors);
+        $crate::log::warn!($($arg)+)
+    }};
+}
+
+pub(crate) use native_error;
@@ -0,0 +1,63 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A snapshot of counters that describe the health of the tray icons in this process, see [metrics]
+///
+/// The counters only ever increase, so exporters should report them as monotonic counters.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct TrayMetrics {
+    /// Events that were delivered to the callbacks
+    pub events_dispatched: u64,
+    /// Events that were discarded while paused with [PauseBehavior::Drop](crate::PauseBehavior::Drop)
+    pub events_dropped: u64,
+    /// How often a menu was opened. Not counted on *Mac*.
+    pub menu_shows: u64,
+    /// Changes that were forwarded to the platform, e.g. by [TrayIcon::set_tooltip](crate::TrayIcon::set_tooltip)
+    pub updates: u64,
+    /// Operations of the platform that failed. The details are logged as warnings.
+    pub native_errors: u64
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) enum Counter {
+    EventsDispatched,
+    EventsDropped,
+    MenuShows,
+    Updates,
+    NativeErrors
+}
+
+static EVENTS_DISPATCHED: AtomicU64 = AtomicU64::new(0);
+static EVENTS_DROPPED: AtomicU64 = AtomicU64::new(0);
+static MENU_SHOWS: AtomicU64 = AtomicU64::new(0);
+static UPDATES: AtomicU64 = AtomicU64::new(0);
+static NATIVE_ERRORS: AtomicU64 = AtomicU64::new(0);
+
+impl Counter {
+    fn value(self) -> &'static AtomicU64 {
+        match self {
+            Counter::EventsDispatched => &EVENTS_DISPATCHED,
+            Counter::EventsDropped => &EVENTS_DROPPED,
+            Counter::MenuShows => &MENU_SHOWS,
+            Counter::Updates => &UPDATES,
+            Counter::NativeErrors => &NATIVE_ERRORS
+        }
+    }
+}
+
+pub(crate) fn count(counter: Counter) {
+    counter.value().fetch_add(1, Ordering::Relaxed);
+}
+
+/// Returns the current counters, summed up over all tray icons of the process
+///
+/// This is meant for long-running applications that want to export the health of the tray icon into their own telemetry.
+pub fn metrics() -> TrayMetrics {
+    let load = |counter: Counter| counter.value().load(Ordering::Relaxed);
+    TrayMetrics {
+        events_dispatched: load(Counter::EventsDispatched),
+        events_dropped: load(Counter::EventsDropped),
+        menu_shows: load(Counter::MenuShows),
+        updates: load(Counter::Updates),
+        native_errors: load(Counter::NativeErrors),
+    }
+}
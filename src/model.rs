@@ -0,0 +1,68 @@
+use crate::{TrayIcon, TrayState};
+
+type View<S, T> = Box<dyn FnMut(&S) -> TrayState<T> + Send + 'static>;
+
+/// Keeps a tray icon in sync with the state of the application
+///
+/// Instead of calling the individual setters of [TrayIcon] wherever the application state changes, the appearance of
+/// the tray icon is described once by a function that maps the application state `S` to a [TrayState].
+/// Every call to [TrayModel::set] or [TrayModel::update] runs the function again and applies only the parts of the
+/// result that changed, see [TrayIcon::set_state].
+pub struct TrayModel<S, T> {
+    tray: TrayIcon<T>,
+    view: View<S, T>,
+    state: S
+}
+
+impl<S, T: Clone + PartialEq + 'static> TrayModel<S, T> {
+
+    /// Takes control of the appearance of `tray` and immediately brings it into the state derived from `state`
+    pub fn new<F>(tray: TrayIcon<T>, state: S, view: F) -> Self
+        where F: FnMut(&S) -> TrayState<T> + Send + 'static
+    {
+        let mut model = Self {
+            tray,
+            view: Box::new(view),
+            state
+        };
+        model.apply();
+        model
+    }
+
+    /// Replaces the application state and updates the tray icon
+    pub fn set(&mut self, state: S) {
+        self.state = state;
+        self.apply();
+    }
+
+    /// Modifies the application state in place and updates the tray icon
+    pub fn update<F: FnOnce(&mut S)>(&mut self, func: F) {
+        func(&mut self.state);
+        self.apply();
+    }
+
+    fn apply(&mut self) {
+        let state = (self.view)(&self.state);
+        self.tray.set_state(state);
+    }
+
+}
+
+impl<S, T> TrayModel<S, T> {
+
+    /// The current application state
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    /// The managed tray icon. Changing it directly is undone by the next update of the model.
+    pub fn tray(&self) -> &TrayIcon<T> {
+        &self.tray
+    }
+
+    /// Stops managing the tray icon and returns it
+    pub fn into_tray(self) -> TrayIcon<T> {
+        self.tray
+    }
+
+}
@@ -0,0 +1,97 @@
+use crate::Icon;
+use crate::log;
+
+/// A notification that is shown by a [TrayIcon](crate::TrayIcon)
+///
+/// On *Windows* notifications are shown as balloons, on *Linux* they are sent to the `org.freedesktop.Notifications` service
+/// and on *Mac* they are delivered through the user notification center.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Notification {
+    pub(crate) title: String,
+    pub(crate) body: String,
+    pub(crate) actions: Vec<String>,
+    pub(crate) icon: Option<Icon>,
+    pub(crate) large_icon: bool,
+    pub(crate) sound: bool,
+    pub(crate) respect_dnd: bool
+}
+
+impl Notification {
+
+    /// The maximum number of action buttons a notification can have
+    pub const MAX_ACTIONS: usize = 3;
+
+    /// Creates a new notification with a title and a body
+    pub fn new<S1, S2>(title: S1, body: S2) -> Self
+        where S1: ToString, S2: ToString
+    {
+        Self {
+            title: title.to_string(),
+            body: body.to_string(),
+            actions: Vec::new(),
+            icon: None,
+            large_icon: false,
+            sound: true,
+            respect_dnd: false,
+        }
+    }
+
+    /// Shows a custom icon inside the notification instead of the generic info icon. Only used by *Windows* balloons.
+    pub fn with_icon(mut self, icon: Icon) -> Self {
+        self.icon = Some(icon);
+        self
+    }
+
+    /// Displays the custom icon in its large variant. Only used by *Windows* balloons.
+    pub fn with_large_icon(mut self, large_icon: bool) -> Self {
+        self.large_icon = large_icon;
+        self
+    }
+
+    /// Controls whether the notification plays a sound when it appears (defaults to `true`)
+    pub fn with_sound(mut self, sound: bool) -> Self {
+        self.sound = sound;
+        self
+    }
+
+    /// Drops the notification instead of showing it while do not disturb is active (defaults to `false`)
+    ///
+    /// See [TrayIcon::is_do_not_disturb](crate::TrayIcon::is_do_not_disturb) for what counts as do not disturb on each
+    /// platform. If the state can't be determined, the notification is shown.
+    pub fn with_respect_dnd(mut self, respect_dnd: bool) -> Self {
+        self.respect_dnd = respect_dnd;
+        self
+    }
+
+    /// Adds an action button to the notification.
+    ///
+    /// Clicking the button is reported as [NotificationEvent::Action] with the index of the action.
+    /// Actions beyond [Notification::MAX_ACTIONS] are ignored.
+    ///
+    /// On *Windows* notifications with actions are shown as toasts instead of balloons. Toasts need the registered
+    /// AppUserModelID set with [TrayIconBuilder::with_app_id](crate::TrayIconBuilder::with_app_id),
+    /// without one the notification is shown as a balloon without the actions.
+    pub fn with_action<S: ToString>(mut self, label: S) -> Self {
+        if self.actions.len() < Self::MAX_ACTIONS {
+            self.actions.push(label.to_string());
+        } else {
+            log::warn!("Ignoring notification action as the limit of {} actions is reached", Self::MAX_ACTIONS);
+        }
+        self
+    }
+
+}
+
+/// Describes how the user interacted with the last [Notification]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NotificationEvent {
+    /// The user clicked on the notification
+    Clicked,
+    /// The notification was closed or timed out without being clicked
+    ///
+    /// Not reported on *Mac*.
+    Dismissed,
+    /// The user clicked the action button with the given index, see [Notification::with_action]
+    Action(usize)
+}
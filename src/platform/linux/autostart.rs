@@ -0,0 +1,88 @@
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use crate::error::{TrayError, TrayResult};
+
+fn entry_path(name: &str) -> TrayResult<PathBuf> {
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .ok_or_else(|| TrayError::custom("Neither XDG_CONFIG_HOME nor HOME is set"))?;
+    Ok(config.join("autostart").join(format!("{name}.desktop")))
+}
+
+/// Quotes an argument for the `Exec` key, which is also a string value that needs escaping itself
+fn exec_argument(arg: &str) -> String {
+    let escaped = match arg.contains(|c: char| c.is_whitespace() || "\"'\\><~|&;$*?#()`".contains(c)) {
+        true => {
+            let mut quoted = String::from("\"");
+            for c in arg.chars() {
+                if matches!(c, '"' | '`' | '$' | '\\') {
+                    quoted.push('\\');
+                }
+                quoted.push(c);
+            }
+            quoted.push('"');
+            quoted
+        }
+        false => arg.to_string()
+    };
+    escaped
+        .replace('\\', "\\\\")
+        .replace('%', "%%")
+}
+
+pub fn enable(name: &str, command: &[String]) -> TrayResult<()> {
+    let path = entry_path(name)?;
+    let exec = command
+        .iter()
+        .map(|arg| exec_argument(arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let entry = format!("[Desktop Entry]\nType=Application\nName={name}\nExec={exec}\nX-GNOME-Autostart-enabled=true\n");
+    let write = || {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, entry)
+    };
+    write().map_err(|err| TrayError::custom(format!("Failed to write {path:?}: {err}")))
+}
+
+pub fn disable(name: &str) -> TrayResult<()> {
+    let path = entry_path(name)?;
+    match std::fs::remove_file(&path) {
+        Err(err) if err.kind() != ErrorKind::NotFound => Err(TrayError::custom(format!("Failed to remove {path:?}: {err}"))),
+        _ => Ok(())
+    }
+}
+
+pub fn is_enabled(name: &str) -> TrayResult<bool> {
+    Ok(entry_path(name)?.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_arguments_are_kept() {
+        assert_eq!(exec_argument("/usr/bin/app"), "/usr/bin/app");
+        assert_eq!(exec_argument("--minimized"), "--minimized");
+    }
+
+    #[test]
+    fn percent_signs_are_doubled() {
+        assert_eq!(exec_argument("50%"), "50%%");
+    }
+
+    #[test]
+    fn reserved_characters_are_quoted() {
+        assert_eq!(exec_argument("--name=My App"), r#""--name=My App""#);
+        // Backslashes are escaped once for the quoting and once more for the string value
+        assert_eq!(exec_argument("$HOME"), r#""\\$HOME""#);
+        assert_eq!(exec_argument(r#"say "hi""#), r#""say \\"hi\\"""#);
+        assert_eq!(exec_argument(r"a\b c"), r#""a\\\\b c""#);
+    }
+
+}
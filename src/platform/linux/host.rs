@@ -0,0 +1,28 @@
+use futures_lite::StreamExt;
+use zbus::Connection;
+use zbus::fdo::DBusProxy;
+use crate::platform::linux::{register_item, TrayCallback};
+use crate::TrayEvent;
+use crate::log;
+
+const WATCHER_NAME: &str = "org.kde.StatusNotifierWatcher";
+
+/// Registers the item again whenever a new StatusNotifierWatcher shows up, e.g. because the panel restarted
+pub async fn watch_host<T>(connection: Connection, name: String, callback: TrayCallback<T>) -> zbus::Result<()> {
+    let proxy = DBusProxy::new(&connection).await?;
+    let mut owner_changes = proxy
+        .receive_name_owner_changed_with_args(&[(0, WATCHER_NAME)])
+        .await?;
+    while let Some(signal) = owner_changes.next().await {
+        if signal.args()?.new_owner().is_none() {
+            log::debug!("StatusNotifierWatcher disappeared");
+            continue;
+        }
+        log::debug!("StatusNotifierWatcher restarted, registering again");
+        match register_item(&connection, &name).await {
+            Ok(()) => (callback.lock())(TrayEvent::HostRestarted),
+            Err(err) => log::native_error!("Failed to register with the new StatusNotifierWatcher: {err}")
+        }
+    }
+    Ok(())
+}
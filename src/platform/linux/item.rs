@@ -1,37 +1,72 @@
 use std::sync::atomic::{AtomicBool, Ordering};
 use parking_lot::Mutex;
-use zbus::{dbus_interface, SignalContext};
-use zbus::zvariant::{ObjectPath, OwnedObjectPath};
-use crate::platform::linux::{MENU_PATH, TrayCallback};
-use crate::{ClickType, TrayEvent};
+use zbus::{Connection, dbus_interface, SignalContext};
+use zbus::zvariant::OwnedObjectPath;
+use crate::platform::linux::{ActivationToken, ItemIcon, MenuPaths, TrayCallback};
+use crate::platform::linux::scroll::ScrollCoalescer;
+use crate::{ClickPolicy, ClickType, ScrollOrientation, TooltipProvider, TrayEvent};
+use crate::log;
+
+/// Icon name, icon pixmaps, title and description
+type ToolTip = (String, Vec<(i32, i32, Vec<u8>)>, String, String);
 
 pub struct StatusNotifierItem<T> {
     first_activate: AtomicBool,
     tooltip: Mutex<String>,
-    icon: Mutex<String>,
+    tooltip_provider: Option<TooltipProvider>,
+    title: Mutex<String>,
+    icon: Mutex<ItemIcon>,
+    menu_paths: MenuPaths,
+    native_menu: AtomicBool,
+    scroll: ScrollCoalescer,
+    click_policy: ClickPolicy,
+    activation_token: ActivationToken,
+    id: String,
     callback: TrayCallback<T>
 }
 
 impl<T> StatusNotifierItem<T> {
-    pub fn new(icon: String, tooltip: String, callback: TrayCallback<T>) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(icon: ItemIcon, tooltip: String, tooltip_provider: Option<TooltipProvider>, title: String, scroll: ScrollCoalescer, click_policy: ClickPolicy, activation_token: ActivationToken, menu_paths: MenuPaths, id: String, callback: TrayCallback<T>) -> Self {
         Self {
             first_activate: AtomicBool::new(true),
             tooltip: Mutex::new(tooltip),
+            tooltip_provider,
+            title: Mutex::new(title),
             icon: Mutex::new(icon),
+            menu_paths,
+            native_menu: AtomicBool::new(false),
+            scroll,
+            click_policy,
+            activation_token,
+            id,
             callback,
         }
     }
 
 }
 
-impl<T: Send + 'static>  StatusNotifierItem<T> {
+impl<T: Clone + Send + 'static> StatusNotifierItem<T> {
     pub async fn update_tooltip(&self, tooltip: String, signal_context: &SignalContext<'_>) -> zbus::Result<()> {
         *self.tooltip.lock() = tooltip;
         Self::new_tool_tip(signal_context).await?;
         Ok(())
     }
 
-    pub async fn update_icon(&self, icon: String, signal_context: &SignalContext<'_>) -> zbus::Result<()> {
+    pub async fn update_title(&self, title: String, signal_context: &SignalContext<'_>) -> zbus::Result<()> {
+        *self.title.lock() = title;
+        Self::new_title(signal_context).await?;
+        Ok(())
+    }
+
+    /// Points the host to either the generated menu or the one provided by the application
+    pub async fn update_native_menu(&self, native: bool, signal_context: &SignalContext<'_>) -> zbus::Result<()> {
+        self.native_menu.store(native, Ordering::SeqCst);
+        self.menu_changed(signal_context).await?;
+        Ok(())
+    }
+
+    pub async fn update_icon(&self, icon: ItemIcon, signal_context: &SignalContext<'_>) -> zbus::Result<()> {
         *self.icon.lock() = icon;
         Self::new_icon(signal_context).await?;
         Ok(())
@@ -39,29 +74,50 @@ impl<T: Send + 'static>  StatusNotifierItem<T> {
 }
 
 #[dbus_interface(name = "org.kde.StatusNotifierItem")]
-impl<T: Send + 'static> StatusNotifierItem<T> {
+impl<T: Clone + Send + 'static> StatusNotifierItem<T> {
 
     fn activate(&self, _x: i32, _y: i32) {
-        //skipping the first activation, which triggers the construction of the menu
-        //after that every activation appears to be a double click
-        let first = self.first_activate.swap(false, Ordering::SeqCst);
-        if !first {
-            (self.callback.lock())(TrayEvent::Tray(ClickType::Double))
-        }
-    }
-
+        let click = match self.click_policy {
+            ClickPolicy::DetectDoubleClicks => {
+                //skipping the first activation, which triggers the construction of the menu
+                //after that every activation appears to be a double click
+                if self.first_activate.swap(false, Ordering::SeqCst) {
+                    return;
+                }
+                ClickType::Double
+            }
+            // Every activation is a click of its own, including the first one
+            ClickPolicy::SinglesOnly => ClickType::Left
+        };
+        let activation_token = self.activation_token.take();
+        (self.callback.lock())(TrayEvent::Tray { click, activation_token })
+    }
+
+    // Hosts that render the dbusmenu themselves never call this
     fn context_menu(&self, _x: i32, _y: i32) {
-        //println!("context menu {x} {y}");
+        log::debug!("The host asked the item to show its own menu, which is not supported");
     }
 
-    fn scroll(&self, _delta: i32, _orientation: &str) {
-        //println!("scroll {delta} {orientation}");
+    fn scroll(&self, #[zbus(connection)] connection: &Connection, delta: i32, orientation: &str) {
+        let orientation = match orientation.to_ascii_lowercase().as_str() {
+            "vertical" => ScrollOrientation::Vertical,
+            "horizontal" => ScrollOrientation::Horizontal,
+            _ => {
+                log::debug!("Unknown scroll orientation: {orientation}");
+                return;
+            }
+        };
+        self.scroll.scroll(connection, delta, orientation, &self.callback);
     }
 
     fn secondary_activate(&self, _x: i32, _y: i32) {
         //println!("secondary activate {x} {y}");
     }
 
+    /// Called by hosts on Wayland right before the interaction that the token belongs to
+    fn provide_xdg_activation_token(&self, token: String) {
+        self.activation_token.set(token);
+    }
 
     #[dbus_interface(signal)]
     async fn new_attention_icon(ctx: &SignalContext<'_>) -> zbus::Result<()> {}
@@ -103,12 +159,12 @@ impl<T: Send + 'static> StatusNotifierItem<T> {
 
     #[dbus_interface(property)]
     fn icon_name(&self) -> String {
-        self.icon.lock().clone()
+        self.icon.lock().name.clone()
     }
 
     #[dbus_interface(property)]
     fn icon_pixmap(&self) -> Vec<(i32, i32, Vec<u8>)> {
-        Default::default()
+        self.icon.lock().pixmap.clone()
     }
 
     #[dbus_interface(property)]
@@ -118,8 +174,7 @@ impl<T: Send + 'static> StatusNotifierItem<T> {
 
     #[dbus_interface(property)]
     fn id(&self) -> String {
-        //TODO How to handle this id?
-        String::from("betrayer")
+        self.id.clone()
     }
 
     #[dbus_interface(property)]
@@ -129,7 +184,9 @@ impl<T: Send + 'static> StatusNotifierItem<T> {
 
     #[dbus_interface(property)]
     fn menu(&self) -> OwnedObjectPath {
-        ObjectPath::from_str_unchecked(MENU_PATH).into()
+        self.menu_paths
+            .get(self.native_menu.load(Ordering::SeqCst))
+            .clone()
     }
 
     #[dbus_interface(property)]
@@ -149,12 +206,16 @@ impl<T: Send + 'static> StatusNotifierItem<T> {
 
     #[dbus_interface(property)]
     fn title(&self) -> String {
-        String::from("CHECKED!")
+        self.title.lock().clone()
     }
 
     #[dbus_interface(property)]
-    fn tool_tip(&self) -> (String, Vec<(i32, i32, Vec<u8>)>, String, String) {
-        (String::new(), Vec::new(), self.tooltip.lock().clone(), String::new())
+    fn tool_tip(&self) -> ToolTip {
+        let mut tooltip = self.tooltip.lock();
+        if let Some(provider) = &self.tooltip_provider {
+            *tooltip = provider.get();
+        }
+        (String::new(), Vec::new(), tooltip.clone(), String::new())
     }
 
     #[dbus_interface(property)]
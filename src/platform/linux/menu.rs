@@ -1,21 +1,28 @@
 use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::{Debug, Formatter};
 use std::mem::swap;
+use std::sync::Arc;
 use std::sync::atomic::{AtomicU32, Ordering};
 use parking_lot::Mutex;
-use zbus::{dbus_interface, SignalContext};
-use zbus::zvariant::{OwnedValue, Str, Value};
-use crate::{ClickType, Menu, MenuItem, TrayEvent};
-use crate::platform::linux::TrayCallback;
+use futures_lite::future::Boxed;
+use zbus::{Connection, dbus_interface, Interface, SignalContext};
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Str, Value};
+use crate::{ClickType, ItemStyle, Menu, MenuItem, MenuItemId, Modifiers, Shortcut, TrayEvent};
+use crate::platform::linux::{ActivationToken, TrayCallback};
+use crate::log;
+use crate::metrics::{self, Counter};
+
+type Properties = HashMap<String, OwnedValue>;
 
 #[derive(Clone)]
 struct MenuEntry<T> {
-    properties: HashMap<String, OwnedValue>,
+    properties: Properties,
     children: Vec<usize>,
     signal: Option<T>
 }
 
 impl<T> MenuEntry<T> {
-    fn get_properties(&self, requested: &[&str]) -> HashMap<String, OwnedValue> {
+    fn get_properties(&self, requested: &[&str]) -> Properties {
         self.properties
             .iter()
             .filter(|(k, _)| requested.is_empty() || requested.contains(&k.as_str()))
@@ -26,22 +33,39 @@ impl<T> MenuEntry<T> {
 
 pub struct DBusMenu<T> {
     revision: AtomicU32,
-    entries: Mutex<Vec<MenuEntry<T>>>,
+    entries: Arc<Mutex<Vec<MenuEntry<T>>>>,
+    activation_token: ActivationToken,
     callback: TrayCallback<T>
 }
 
+/// Looks up the dbusmenu ids of the entries while the menu itself is owned by the object server
+pub struct MenuIds<T>(Arc<Mutex<Vec<MenuEntry<T>>>>);
+
+impl<T> MenuIds<T> {
+    pub fn get(&self, id: &MenuItemId) -> Option<u32> {
+        resolve_path(&self.0.lock(), id.path())
+            .filter(|entry| *entry != 0)
+            .map(|entry| entry as u32)
+    }
+}
+
 impl<T> DBusMenu<T> {
-    pub fn new(menu: Menu<T>, callback: TrayCallback<T>) -> Self
+    pub fn new(menu: Menu<T>, activation_token: ActivationToken, callback: TrayCallback<T>) -> Self
     {
 
         let entries = build_menu(menu);
         Self {
             revision: AtomicU32::new(0),
-            entries: Mutex::new(entries),
+            entries: Arc::new(Mutex::new(entries)),
+            activation_token,
             callback,
         }
     }
 
+    pub fn ids(&self) -> MenuIds<T> {
+        MenuIds(self.entries.clone())
+    }
+
 }
 
 impl<T: Clone + Send + 'static> DBusMenu<T> {
@@ -63,6 +87,106 @@ impl<T: Clone + Send + 'static> DBusMenu<T> {
         }
         Ok(())
     }
+
+    pub async fn move_item(&self, id: &MenuItemId, new_index: usize, signal_context: &SignalContext<'_>) -> zbus::Result<()> {
+        let parent = {
+            let mut entries = self.entries.lock();
+            let Some((parent, index)) = id
+                .split()
+                .and_then(|(path, index)| resolve_path(&entries, path).map(|parent| (parent, index))) else {
+                log::warn!("Invalid menu item id: {id:?}");
+                return Ok(());
+            };
+            let children = &mut entries[parent].children;
+            if index >= children.len() || new_index >= children.len() {
+                log::warn!("Can not move item {index} to {new_index} (menu has {} items)", children.len());
+                return Ok(());
+            }
+            if index == new_index {
+                return Ok(());
+            }
+            let child = children.remove(index);
+            children.insert(new_index, child);
+            parent as i32
+        };
+        let revision = self.revision.fetch_add(1, Ordering::SeqCst) + 1;
+        log::trace!("Sending layout update signal (parent: {parent}, revision: {revision})");
+        Self::layout_updated(signal_context, revision, parent).await?;
+        Ok(())
+    }
+}
+
+impl<T: Clone + Send + 'static> DBusMenu<T> {
+    pub async fn set_visible(&self, id: &MenuItemId, visible: bool, signal_context: &SignalContext<'_>) -> zbus::Result<()> {
+        let entry = {
+            let mut entries = self.entries.lock();
+            let Some(entry) = resolve_path(&entries, id.path()).filter(|entry| *entry != 0) else {
+                log::warn!("Invalid menu item id: {id:?}");
+                return Ok(());
+            };
+            let properties = &mut entries[entry].properties;
+            match visible {
+                true => properties.remove("visible"),
+                false => properties.insert(String::from("visible"), OwnedValue::from(false))
+            };
+            entry as i32
+        };
+        log::trace!("Sending property update signal (visible: {visible}, entry: {entry})");
+        let updated = [(entry, HashMap::from([(String::from("visible"), OwnedValue::from(visible))]))];
+        Self::items_properties_updated(signal_context, &updated, &[]).await?;
+        Ok(())
+    }
+}
+
+type InstallMenu = Box<dyn FnOnce(Connection, OwnedObjectPath) -> Boxed<zbus::Result<RemoveMenu>> + Send>;
+pub type RemoveMenu = Box<dyn FnOnce(Connection) -> Boxed<zbus::Result<()>> + Send>;
+
+/// A hand written implementation of the `com.canonical.dbusmenu` interface for
+/// [TrayIcon::set_native_menu](crate::TrayIcon::set_native_menu)
+///
+/// Only available on *Linux*.
+pub struct DBusMenuInterface(InstallMenu);
+
+impl DBusMenuInterface {
+
+    /// Wraps an interface built with `zbus` 3, which handles its own events
+    pub fn new<I: Interface>(menu: I) -> Self {
+        Self(Box::new(move |connection, path| Box::pin(async move {
+            connection
+                .object_server()
+                .at(&path, menu)
+                .await?;
+            let remove: RemoveMenu = Box::new(|connection| Box::pin(async move {
+                connection
+                    .object_server()
+                    .remove::<I, _>(&path)
+                    .await?;
+                Ok(())
+            }));
+            Ok(remove)
+        })))
+    }
+
+    /// Serves the menu in place of the generated menu, the returned function removes it again
+    pub(crate) async fn install(self, connection: Connection, path: OwnedObjectPath) -> zbus::Result<RemoveMenu> {
+        (self.0)(connection, path).await
+    }
+
+}
+
+impl Debug for DBusMenuInterface {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DBusMenuInterface").finish_non_exhaustive()
+    }
+}
+
+fn resolve_path<T>(entries: &[MenuEntry<T>], path: &[usize]) -> Option<usize> {
+    path
+        .iter()
+        .try_fold(0, |current, i| entries[current]
+            .children
+            .get(*i)
+            .copied())
 }
 
 fn build_menu<T>(menu: Menu<T>) -> Vec<MenuEntry<T>> {
@@ -86,29 +210,27 @@ fn build_menu<T>(menu: Menu<T>) -> Vec<MenuEntry<T>> {
                 children: vec![],
                 signal: None,
             },
-            MenuItem::CheckButton { name, signal, checked } => MenuEntry {
-                properties:
-                    HashMap::from([
-                        (String::from("label"), OwnedValue::from(Str::from(name))),
-                        (String::from("toggle-type"), OwnedValue::from(Str::from_static("checkmark"))),
-                        (String::from("toggle-state"), OwnedValue::from(if checked {1i32 } else { 0i32 }))
-                    ]),
+            MenuItem::CheckButton { name, signal, checked, shortcut, style, visible, .. } => MenuEntry {
+                properties: with_visible(visible, with_style(style, with_shortcut(shortcut, HashMap::from([
+                    (String::from("label"), OwnedValue::from(Str::from(name))),
+                    (String::from("toggle-type"), OwnedValue::from(Str::from_static("checkmark"))),
+                    (String::from("toggle-state"), OwnedValue::from(if checked {1i32 } else { 0i32 }))
+                ])))),
                 children: vec![],
                 signal: Some(signal),
             },
-            MenuItem::Button { name, signal } => MenuEntry {
-                properties:
-                    HashMap::from([
-                        (String::from("label"), OwnedValue::from(Str::from(name))),
-                    ]),
+            MenuItem::Button { name, signal, shortcut, style, visible, .. } => MenuEntry {
+                properties: with_visible(visible, with_style(style, with_shortcut(shortcut, HashMap::from([
+                    (String::from("label"), OwnedValue::from(Str::from(name))),
+                ])))),
                 children: vec![],
                 signal: Some(signal),
             },
-            MenuItem::Menu { name, children } => MenuEntry {
-                properties: HashMap::from([
+            MenuItem::Menu { name, children, visible } => MenuEntry {
+                properties: with_visible(visible, HashMap::from([
                     (String::from("label"), OwnedValue::from(Str::from(name))),
                     (String::from("children-display"), OwnedValue::from(Str::from_static("submenu")))
-                ]),
+                ])),
                 children: {
                     let start = 1 + entries.len() + items.len();
                     items.extend(children);
@@ -123,7 +245,47 @@ fn build_menu<T>(menu: Menu<T>) -> Vec<MenuEntry<T>> {
     entries
 }
 
-fn generate_diff<T>(new: &Vec<MenuEntry<T>>, old: &Vec<MenuEntry<T>>) -> (Option<i32>, Vec<(i32, HashMap<String, OwnedValue>)>, Vec<(i32, Vec<String>)>) {
+fn with_visible(visible: bool, mut properties: Properties) -> Properties {
+    if !visible {
+        properties.insert(String::from("visible"), OwnedValue::from(false));
+    }
+    properties
+}
+
+fn with_style(style: ItemStyle, mut properties: Properties) -> Properties {
+    // dbusmenu has no notion of styles, the disposition is the closest thing hosts know how to render
+    let disposition = match style {
+        ItemStyle::Normal => return properties,
+        ItemStyle::Destructive => "alert",
+        ItemStyle::Highlighted => "informative"
+    };
+    properties.insert(String::from("disposition"), OwnedValue::from(Str::from_static(disposition)));
+    properties
+}
+
+fn with_shortcut(shortcut: Option<Shortcut>, mut properties: Properties) -> Properties {
+    if let Some(shortcut) = shortcut {
+        let modifiers = [
+            (Modifiers::CONTROL, "Control"),
+            (Modifiers::OPTION, "Alt"),
+            (Modifiers::SHIFT, "Shift"),
+            (Modifiers::COMMAND, "Super")
+        ];
+        let keys: Vec<String> = modifiers
+            .into_iter()
+            .filter(|(modifier, _)| shortcut.modifiers.contains(*modifier))
+            .map(|(_, name)| String::from(name))
+            .chain([shortcut.key.to_string()])
+            .collect();
+        properties.insert(String::from("shortcut"), Value::new(vec![keys]).to_owned());
+    }
+    properties
+}
+
+/// The root of the changed layout, the updated properties and the removed properties
+type MenuDiff = (Option<i32>, Vec<(i32, Properties)>, Vec<(i32, Vec<String>)>);
+
+fn generate_diff<T>(new: &[MenuEntry<T>], old: &[MenuEntry<T>]) -> MenuDiff {
     let mut updated = Vec::new();
     let mut removed = Vec::new();
     let mut changed = HashSet::new();
@@ -136,7 +298,7 @@ fn generate_diff<T>(new: &Vec<MenuEntry<T>>, old: &Vec<MenuEntry<T>>) -> (Option
         if !r.is_empty() {
             removed.push((i as i32, r));
         }
-        let n: HashMap<String, OwnedValue> = new.properties
+        let n: Properties = new.properties
             .iter()
             .filter(|(k, v)| !old.properties
                 .get(*k)
@@ -158,7 +320,7 @@ fn generate_diff<T>(new: &Vec<MenuEntry<T>>, old: &Vec<MenuEntry<T>>) -> (Option
     (changed, updated, removed)
 }
 
-fn find_common_root<T>(entries: &Vec<MenuEntry<T>>, changed: &HashSet<usize>) -> usize {
+fn find_common_root<T>(entries: &[MenuEntry<T>], changed: &HashSet<usize>) -> usize {
     let mut cache = HashMap::new();
     for (i, entry) in entries.iter().enumerate().rev() {
         let c: u32 = u32::from(changed.contains(&i)) + entry
@@ -177,7 +339,7 @@ fn find_common_root<T>(entries: &Vec<MenuEntry<T>>, changed: &HashSet<usize>) ->
         .expect("There should be a common root")
 }
 
-fn collect<T>(ids: &Vec<usize>, entries: &Vec<MenuEntry<T>>, property_names: &Vec<&str>, depth: u32) -> Vec<OwnedValue> {
+fn collect<T>(ids: &[usize], entries: &[MenuEntry<T>], property_names: &[&str], depth: u32) -> Vec<OwnedValue> {
     match depth {
         0 => Vec::new(),
         _ => ids
@@ -197,7 +359,7 @@ fn collect<T>(ids: &Vec<usize>, entries: &Vec<MenuEntry<T>>, property_names: &Ve
 #[dbus_interface(name = "com.canonical.dbusmenu")]
 impl<T: Clone + Send + 'static> DBusMenu<T> {
 
-    fn get_layout(&self, parent_id: i32, recursion_depth: i32, property_names: Vec<&str>) -> (u32, (i32, HashMap<String, OwnedValue>, Vec<OwnedValue>)) {
+    fn get_layout(&self, parent_id: i32, recursion_depth: i32, property_names: Vec<&str>) -> (u32, (i32, Properties, Vec<OwnedValue>)) {
         log::trace!("get_layout({}, {}, {:?})", parent_id, recursion_depth, property_names);
         let depth = u32::try_from(recursion_depth)
             .unwrap_or(u32::MAX);
@@ -207,14 +369,14 @@ impl<T: Clone + Send + 'static> DBusMenu<T> {
         (revision, (parent_id, entry.get_properties(&property_names), collect(&entry.children, &entries, &property_names, depth)))
     }
 
-    fn get_group_properties(&self, ids: Vec<i32>, property_names: Vec<&str>) -> Vec<(i32, HashMap<String, OwnedValue>)> {
+    fn get_group_properties(&self, ids: Vec<i32>, property_names: Vec<&str>) -> Vec<(i32, Properties)> {
         log::trace!("get_group_properties({:?}, {:?})", ids, property_names);
         let entries = self.entries.lock();
         entries
             .iter()
             .enumerate()
-            .filter_map(|(i, e)| (ids.is_empty() || ids.contains(&(i as i32)))
-                .then(|| (i as i32, e.get_properties(&property_names))))
+            .filter(|(i, _)| ids.is_empty() || ids.contains(&(*i as i32)))
+            .map(|(i, e)| (i as i32, e.get_properties(&property_names)))
             .collect()
     }
 
@@ -239,11 +401,14 @@ impl<T: Clone + Send + 'static> DBusMenu<T> {
                     .get(id as usize)
                     .and_then(|e|e.signal.clone());
                 if let Some(signal) = signal {
-                    (self.callback.lock())(TrayEvent::Menu(signal));
+                    let activation_token = self.activation_token.take();
+                    (self.callback.lock())(TrayEvent::Menu { signal, activation_token });
                 }
             },
             "opened" if id == 0 => {
-                (self.callback.lock())(TrayEvent::Tray(ClickType::Left));
+                metrics::count(Counter::MenuShows);
+                let activation_token = self.activation_token.take();
+                (self.callback.lock())(TrayEvent::Tray { click: ClickType::Left, activation_token });
             }
             _ => {}
         }
@@ -270,7 +435,7 @@ impl<T: Clone + Send + 'static> DBusMenu<T> {
     async fn item_activation_requested(ctx: &SignalContext<'_>, id: i32, timestamp: u32) -> zbus::Result<()> { }
 
     #[dbus_interface(signal)]
-    async fn items_properties_updated(ctx: &SignalContext<'_>, updated_props: &[(i32, HashMap<String, OwnedValue>, )], removed_props: &[(i32, Vec<String>)]) -> zbus::Result<()> { }
+    async fn items_properties_updated(ctx: &SignalContext<'_>, updated_props: &[(i32, Properties, )], removed_props: &[(i32, Vec<String>)]) -> zbus::Result<()> { }
 
     #[dbus_interface(signal)]
     async fn layout_updated(ctx: &SignalContext<'_>, revision: u32, parent: i32) -> zbus::Result<()> { }
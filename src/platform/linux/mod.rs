@@ -1,85 +1,239 @@
 mod menu;
 mod item;
+mod session;
+mod notification;
+mod scroll;
+mod host;
+#[cfg(feature = "remote")]
+mod remote;
+#[cfg(feature = "autostart")]
+pub mod autostart;
 
-use std::cell::Cell;
 use std::path::PathBuf;
 use std::sync::{Arc, OnceLock};
 use std::sync::atomic::{AtomicU32, Ordering};
-use flume::Sender;
+use std::time::Duration;
+use async_io::Timer;
+use std::future::Future;
+use flume::{Receiver, Sender};
+use futures_lite::FutureExt;
 use parking_lot::Mutex;
-use png::{BitDepth, ColorType, Encoder};
-use zbus::{ConnectionBuilder, dbus_proxy, Task};
-use crate::error::{ErrorSource, TrayResult};
-use crate::{Icon, Menu, TrayEvent, TrayIconBuilder};
+use png::{BitDepth, ColorType, Encoder, Transformations};
+use zbus::{Connection, ConnectionBuilder, dbus_proxy, Task};
+use zbus::zvariant::{ObjectPath, OwnedObjectPath};
+use crate::error::{ErrorSource, TrayError, TrayResult};
+use crate::{Backend, Icon, Menu, MenuItemId, Notification, TrayEvent, TrayIconBuilder};
+use crate::control_flow::ExitSignal;
 use crate::platform::linux::item::StatusNotifierItem;
-use crate::platform::linux::menu::DBusMenu;
+use crate::platform::linux::menu::{DBusMenu, MenuIds, RemoveMenu};
+use crate::platform::linux::notification::{ActiveNotifications, InhibitedState};
+use crate::platform::linux::scroll::ScrollCoalescer;
+use crate::log;
 
-static MENU_PATH: &'static str = "/MenuBar";
-static ITEM_PATH: &'static str = "/StatusNotifierItem";
+pub use menu::DBusMenuInterface;
+#[cfg(feature = "remote")]
+pub use remote::{DBUS_INTERFACE, DBUS_PATH, RemoteTransport};
+
+static ITEM_PATH: &str = "/StatusNotifierItem";
 static COUNTER: AtomicU32 = AtomicU32::new(1);
 
 enum TrayUpdate<T> {
     Menu(Menu<T>),
+    NativeMenu(DBusMenuInterface),
+    MoveItem(MenuItemId, usize),
+    Visibility(MenuItemId, bool),
     Tooltip(String),
-    Icon(String)
+    Title(String),
+    Icon(ItemIcon),
+    Notification(Notification),
+    Acknowledge(Sender<()>)
+}
+
+/// The icon as presented to the host
+///
+/// The name is either an icon from the theme or a path to an image. Hosts that can not resolve it fall back to the pixmap.
+#[derive(Default, Clone)]
+pub struct ItemIcon {
+    name: String,
+    pixmap: Vec<(i32, i32, Vec<u8>)>
 }
 
 pub type TrayCallback<T> = Arc<Mutex<dyn FnMut(TrayEvent<T>) + Send + 'static>>;
 
+/// The object paths of the generated menu and of a menu provided by the application
+///
+/// Both are namespaced by the app id if there is one.
+#[derive(Clone)]
+pub struct MenuPaths {
+    generated: OwnedObjectPath,
+    native: OwnedObjectPath
+}
+
+impl MenuPaths {
+    fn new(app_id: Option<&str>) -> Self {
+        let base: String = app_id
+            .into_iter()
+            .flat_map(|id| id.split('.'))
+            .filter(|element| !element.is_empty())
+            .map(|element| format!("/{}", dbus_element(element)))
+            .collect();
+        Self {
+            generated: ObjectPath::from_string_unchecked(format!("{base}/MenuBar")).into(),
+            native: ObjectPath::from_string_unchecked(format!("{base}/MenuBar/Native")).into(),
+        }
+    }
+
+    pub fn get(&self, native: bool) -> &OwnedObjectPath {
+        match native {
+            true => &self.native,
+            false => &self.generated
+        }
+    }
+}
+
+/// Replaces the characters that aren't allowed in the elements of D-Bus names and object paths
+fn dbus_element(element: &str) -> String {
+    element
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c,
+            false => '_'
+        })
+        .collect()
+}
+
+/// The XDG activation token that the host passed along with the latest interaction
+#[derive(Clone, Default)]
+pub struct ActivationToken(Arc<Mutex<Option<String>>>);
+
+impl ActivationToken {
+    pub fn set(&self, token: String) {
+        log::trace!("Received activation token: {token}");
+        *self.0.lock() = Some(token);
+    }
+
+    pub fn take(&self) -> Option<String> {
+        self.0.lock().take()
+    }
+}
+
+pub const BACKEND: Backend = Backend::LinuxSni;
+
+pub type IntervalCallback = Box<dyn FnMut() + Send + 'static>;
+
+pub type Acknowledgement = flume::r#async::RecvFut<'static, ()>;
+
 pub struct NativeTrayIcon<T> {
     id: (u32, u32),
+    name: String,
+    connection: Connection,
     sender: Sender<TrayUpdate<T>>,
-    tmp_icon_file: Cell<Option<TmpFileRaiiHandle>>,
-    tmp_icon_counter: Cell<u32>,
+    menu_ids: MenuIds<T>,
+    tmp_icon_file: Mutex<Option<TmpFileRaiiHandle>>,
+    tmp_icon_counter: Mutex<u32>,
+    interval_tasks: Mutex<Vec<Task<()>>>,
+    shutdown: (Sender<()>, Receiver<()>),
     _update_task: Task<()>,
+    _session_task: Task<()>,
+    _host_task: Task<()>,
+    _notification_task: Task<()>,
+    inhibited: InhibitedState
 }
 
 impl<T: Clone + Send + 'static> NativeTrayIcon<T> {
 
-    pub async fn new_async<F>(builder: TrayIconBuilder<T>, callback: F) -> TrayResult<Self>
+    /// Without the internal executor the returned tray icon only works while [NativeTrayIcon::drive] is being polled
+    pub async fn new_async<F>(builder: TrayIconBuilder<T>, callback: F, internal_executor: bool) -> TrayResult<Self>
         where F: FnMut(TrayEvent<T>) + Send + 'static
     {
         let pid = std::process::id();
         let id = COUNTER.fetch_add(1, Ordering::AcqRel);
-        let name = format!("org.kde.StatusNotifierItem-{pid}-{id}");
+        let name = match &builder.app_id {
+            Some(app_id) => format!("org.kde.StatusNotifierItem-{pid}-{id}-{}", dbus_element(app_id)),
+            None => format!("org.kde.StatusNotifierItem-{pid}-{id}")
+        };
+        let menu_paths = MenuPaths::new(builder.app_id.as_deref());
 
         let mut tmp_icon_counter = 0;
-        let (icon, tmp_icon_path) = builder
-            .icon
-            .map(NativeIcon::from)
-            .map(|icon| icon.write_to_disk((pid, id), &mut tmp_icon_counter))
-            .unzip();
+        let (icon, tmp_icon_path) = match builder.icon_name {
+            Some(name) => (themed_icon(name, builder.icon), None),
+            None => image_icon(builder.icon, (pid, id), &mut tmp_icon_counter)
+        };
 
         let callback = Arc::new(Mutex::new(callback));
+        let activation_token = ActivationToken::default();
+        let menu = DBusMenu::new(builder.menu.unwrap_or_else(Menu::empty), activation_token.clone(), callback.clone());
+        let menu_ids = menu.ids();
         //"/home/simon/headset-controller/resources/icon.png"
         let conn = ConnectionBuilder::session()?
             .name(name.clone())?
             .serve_at(ITEM_PATH, StatusNotifierItem::new(
-                icon.unwrap_or_default(),
+                icon,
                 builder.tooltip.unwrap_or_default(),
+                builder.tooltip_provider,
+                builder.title.unwrap_or_default(),
+                ScrollCoalescer::new(builder.max_scroll_rate),
+                builder.click_policy,
+                activation_token,
+                menu_paths.clone(),
+                builder.app_id.clone().unwrap_or_else(|| String::from("betrayer")),
                 callback.clone()))?
-            .serve_at(MENU_PATH, DBusMenu::new(
-                builder.menu.unwrap_or_else(Menu::empty),
-                callback))?
-            .internal_executor(true)
+            .serve_at(menu_paths.get(false).as_str(), menu)?
+            .internal_executor(internal_executor)
             .build()
             .await?;
 
+        let active_notifications = ActiveNotifications::default();
         let (sender, receiver) = flume::unbounded();
         let receiver_task = {
 
             let connection = conn.clone();
+            let active_notifications = active_notifications.clone();
+            let app_id = builder.app_id;
             conn.executor().spawn(async move {
+                let menu_path = menu_paths.get(false).as_str();
+                let mut foreign_menu: Option<RemoveMenu> = None;
                 while let Ok(event) = receiver.recv_async().await {
                     match event {
                         TrayUpdate::Menu(menu) => {
+                            if let Some(remove) = foreign_menu.take() {
+                                remove_foreign_menu::<T>(&connection, remove).await;
+                            }
                             let iface = connection
                                 .object_server()
-                                .interface::<_, DBusMenu<T>>(MENU_PATH)
+                                .interface::<_, DBusMenu<T>>(menu_path)
                                 .await.unwrap();
                             let iref = iface.get().await;
                             iref.update_menu(menu, iface.signal_context()).await.unwrap();
                         },
+                        TrayUpdate::NativeMenu(install) => {
+                            if let Some(remove) = foreign_menu.take() {
+                                remove_foreign_menu::<T>(&connection, remove).await;
+                            }
+                            match install.install(connection.clone(), menu_paths.get(true).clone()).await {
+                                Ok(remove) => {
+                                    foreign_menu = Some(remove);
+                                    set_native_menu::<T>(&connection, true).await;
+                                }
+                                Err(err) => log::native_error!("Failed to serve native menu: {err}")
+                            }
+                        }
+                        TrayUpdate::MoveItem(id, new_index) => {
+                            let iface = connection
+                                .object_server()
+                                .interface::<_, DBusMenu<T>>(menu_path)
+                                .await.unwrap();
+                            let iref = iface.get().await;
+                            iref.move_item(&id, new_index, iface.signal_context()).await.unwrap();
+                        },
+                        TrayUpdate::Visibility(id, visible) => {
+                            let iface = connection
+                                .object_server()
+                                .interface::<_, DBusMenu<T>>(menu_path)
+                                .await.unwrap();
+                            let iref = iface.get().await;
+                            iref.set_visible(&id, visible, iface.signal_context()).await.unwrap();
+                        },
                         TrayUpdate::Tooltip(tooltip) => {
                             let iface = connection
                                 .object_server()
@@ -88,6 +242,14 @@ impl<T: Clone + Send + 'static> NativeTrayIcon<T> {
                             let iref = iface.get().await;
                             iref.update_tooltip(tooltip, iface.signal_context()).await.unwrap();
                         }
+                        TrayUpdate::Title(title) => {
+                            let iface = connection
+                                .object_server()
+                                .interface::<_, StatusNotifierItem<T>>(ITEM_PATH)
+                                .await.unwrap();
+                            let iref = iface.get().await;
+                            iref.update_title(title, iface.signal_context()).await.unwrap();
+                        }
                         TrayUpdate::Icon(icon) => {
                             let iface = connection
                                 .object_server()
@@ -96,25 +258,79 @@ impl<T: Clone + Send + 'static> NativeTrayIcon<T> {
                             let iref = iface.get().await;
                             iref.update_icon(icon, iface.signal_context()).await.unwrap();
                         }
+                        TrayUpdate::Notification(notification) => {
+                            notification::show_notification(&connection, notification, app_id.as_deref(), &active_notifications)
+                                .await
+                                .unwrap_or_else(|err| log::native_error!("Failed to show notification: {err}"));
+                        }
+                        // Updates are applied in order, so everything sent before this has been emitted
+                        TrayUpdate::Acknowledge(ack) => {
+                            let _ = ack.send(());
+                        }
                     }
                 }
             }, "event receiver")
         };
 
-        let proxy = StatusNotifierWatcherProxy::builder(&conn)
-            .path("/StatusNotifierWatcher")?
-            .build()
-            .await?;
+        let inhibited = InhibitedState::default();
+        let notification_task = {
+            let connection = conn.clone();
+            let callback = callback.clone();
+            let inhibited = inhibited.clone();
+            conn.executor().spawn(async move {
+                let inhibited = async {
+                    notification::watch_inhibited(&connection, inhibited)
+                        .await
+                        .unwrap_or_else(|err| log::debug!("Failed to watch the do not disturb state: {err}"));
+                };
+                let notifications = async {
+                    notification::watch_notifications(connection.clone(), active_notifications, callback)
+                        .await
+                        .unwrap_or_else(|err| log::debug!("Failed to watch notifications: {err}"));
+                };
+                futures_lite::future::zip(inhibited, notifications).await;
+            }, "notification watcher")
+        };
+
+        let host_task = {
+            let connection = conn.clone();
+            let name = name.clone();
+            let callback = callback.clone();
+            conn.executor().spawn(async move {
+                host::watch_host(connection, name, callback)
+                    .await
+                    .unwrap_or_else(|err| log::debug!("Failed to watch the StatusNotifierWatcher: {err}"));
+            }, "host watcher")
+        };
 
-        proxy.register_status_notifier_item(&name).await?;
+        let session_task = {
+            let connection = conn.clone();
+            let name = name.clone();
+            conn.executor().spawn(async move {
+                session::watch_session(connection, name, callback)
+                    .await
+                    .unwrap_or_else(|err| log::debug!("Failed to watch the login session: {err}"));
+            }, "session watcher")
+        };
 
+        // Tick the executor ourselves in case there is no internal executor yet to receive the replies
+        tick_while(&conn, register_item(&conn, &name)).await?;
 
         Ok(Self {
             id: (pid, id),
+            name,
+            connection: conn,
             sender,
-            tmp_icon_file: Cell::new(tmp_icon_path.flatten()),
-            tmp_icon_counter: Cell::new(tmp_icon_counter),
-            _update_task: receiver_task
+            menu_ids,
+            tmp_icon_file: Mutex::new(tmp_icon_path),
+            tmp_icon_counter: Mutex::new(tmp_icon_counter),
+            interval_tasks: Mutex::new(Vec::new()),
+            shutdown: flume::bounded(0),
+            _update_task: receiver_task,
+            _session_task: session_task,
+            _host_task: host_task,
+            _notification_task: notification_task,
+            inhibited
         })
 
     }
@@ -122,7 +338,7 @@ impl<T: Clone + Send + 'static> NativeTrayIcon<T> {
     pub fn new<F>(builder: TrayIconBuilder<T>, callback: F) -> TrayResult<Self>
         where F: FnMut(TrayEvent<T>) + Send + 'static
     {
-        async_io::block_on(Self::new_async(builder, callback))
+        async_io::block_on(Self::new_async(builder, callback, true))
     }
 
 }
@@ -134,27 +350,193 @@ impl<T> NativeTrayIcon<T> {
             .unwrap_or_else(|err| log::warn!("Failed to send update: {err}"));
     }
 
+    pub fn set_title(&self, title: Option<String>) {
+        self.sender
+            .send(TrayUpdate::Title(title.unwrap_or_default()))
+            .unwrap_or_else(|err| log::warn!("Failed to send update: {err}"));
+    }
+
     pub fn set_menu(&self, menu: Option<Menu<T>>) {
         self.sender
             .send(TrayUpdate::Menu(menu.unwrap_or_else(Menu::empty)))
             .unwrap_or_else(|err| log::warn!("Failed to send update: {err}"));
     }
 
+    pub fn set_native_menu(&self, menu: DBusMenuInterface) {
+        self.sender
+            .send(TrayUpdate::NativeMenu(menu))
+            .unwrap_or_else(|err| log::warn!("Failed to send update: {err}"));
+    }
+
+    pub fn move_item(&self, id: &MenuItemId, new_index: usize) {
+        self.sender
+            .send(TrayUpdate::MoveItem(id.clone(), new_index))
+            .unwrap_or_else(|err| log::warn!("Failed to send update: {err}"));
+    }
+
+    pub fn set_visible(&self, id: &MenuItemId, visible: bool) {
+        self.sender
+            .send(TrayUpdate::Visibility(id.clone(), visible))
+            .unwrap_or_else(|err| log::warn!("Failed to send update: {err}"));
+    }
+
+    pub fn native_id(&self, id: &MenuItemId) -> Option<u32> {
+        self.menu_ids.get(id)
+    }
+
     pub fn set_icon(&self, icon: Option<Icon>) {
-        let mut counter = self.tmp_icon_counter.get();
-        let (icon, tmp_icon_path) = icon
-            .map(NativeIcon::from)
-            .map(|icon| icon.write_to_disk(self.id, &mut counter))
-            .unzip();
-        self.tmp_icon_counter.set(counter);
-        self.tmp_icon_file.set(tmp_icon_path.flatten());
+        let (icon, tmp_icon_path) = image_icon(icon, self.id, &mut self.tmp_icon_counter.lock());
+        *self.tmp_icon_file.lock() = tmp_icon_path;
+        self.sender
+            .send(TrayUpdate::Icon(icon))
+            .unwrap_or_else(|err| log::warn!("Failed to send update: {err}"));
+    }
+
+    /// Uses the named icon from the icon theme, hosts that can not find it show the fallback image instead
+    pub fn set_themed_icon(&self, name: String, fallback: Option<Icon>) {
+        *self.tmp_icon_file.lock() = None;
+        self.sender
+            .send(TrayUpdate::Icon(themed_icon(name, fallback)))
+            .unwrap_or_else(|err| log::warn!("Failed to send update: {err}"));
+    }
+
+    pub fn show_notification(&self, notification: Notification) {
+        self.sender
+            .send(TrayUpdate::Notification(notification))
+            .unwrap_or_else(|err| log::warn!("Failed to send update: {err}"));
+    }
+
+    /// Reads the cached state, as blocking on the server would stall the D-Bus thread when called from the event callback
+    pub fn is_do_not_disturb(&self) -> TrayResult<bool> {
+        self.inhibited
+            .get()
+            .ok_or_else(|| TrayError::from(ErrorSource::NotSupported))
+    }
+
+    /// Resolves once all updates that were sent before have been applied
+    pub fn acknowledge(&self) -> Acknowledgement {
+        let (sender, receiver) = flume::bounded(1);
         self.sender
-            .send(TrayUpdate::Icon(icon.unwrap_or_default()))
+            .send(TrayUpdate::Acknowledge(sender))
             .unwrap_or_else(|err| log::warn!("Failed to send update: {err}"));
+        // A closed channel also resolves the future, as there is nothing left to wait for
+        receiver.into_recv_async()
+    }
+
+    pub fn id(&self) -> String {
+        format!("{}{ITEM_PATH}", self.name)
+    }
+
+    pub fn run(&self, exit: &ExitSignal) {
+        exit.wait();
+    }
+
+    /// Drives the DBus connection until the tray icon is dropped
+    #[cfg_attr(not(feature = "async-io"), allow(dead_code))]
+    pub fn drive(&self) -> impl Future<Output=()> + Send + 'static {
+        let connection = self.connection.clone();
+        let shutdown = self.shutdown.1.clone();
+        async move {
+            let _ = tick_while(&connection, shutdown.recv_async()).await;
+        }
+    }
+
+    pub fn set_interval(&self, interval: Duration, mut callback: IntervalCallback) {
+        let task = self.connection.executor().spawn(async move {
+            let mut timer = Timer::interval(interval);
+            loop {
+                (&mut timer).await;
+                callback();
+            }
+        }, "interval timer");
+        self.interval_tasks.lock().push(task);
+    }
+
+    /// Calls `callback` on the D-Bus thread after the returned waker was woken
+    #[cfg(feature = "remote")]
+    pub fn set_wakeup(&self, mut callback: IntervalCallback) -> TrayWaker {
+        // Wakes that arrive while one is still pending are handled together
+        let (sender, receiver) = flume::bounded(1);
+        let task = self.connection.executor().spawn(async move {
+            while receiver.recv_async().await.is_ok() {
+                callback();
+            }
+        }, "wakeup");
+        self.interval_tasks.lock().push(task);
+        TrayWaker(sender)
     }
 
 }
 
+/// Makes the tray icon run the callback passed to [NativeTrayIcon::set_wakeup]
+#[cfg(feature = "remote")]
+#[derive(Clone)]
+pub struct TrayWaker(Sender<()>);
+
+#[cfg(feature = "remote")]
+impl TrayWaker {
+    pub fn wake(&self) {
+        let _ = self.0.try_send(());
+    }
+}
+
+fn image_icon(icon: Option<Icon>, id: (u32, u32), counter: &mut u32) -> (ItemIcon, Option<TmpFileRaiiHandle>) {
+    let Some(icon) = icon.map(NativeIcon::from) else {
+        return (ItemIcon::default(), None);
+    };
+    icon.to_item_icon(id, counter)
+}
+
+fn themed_icon(name: String, fallback: Option<Icon>) -> ItemIcon {
+    let pixmap = fallback
+        .map(|icon| NativeIcon::from(icon).to_pixmap())
+        .unwrap_or_default();
+    ItemIcon { name, pixmap }
+}
+
+/// Removes the menu of the application and points the host back to the generated menu
+async fn remove_foreign_menu<T: Clone + Send + 'static>(connection: &Connection, remove: RemoveMenu) {
+    remove(connection.clone())
+        .await
+        .unwrap_or_else(|err| log::native_error!("Failed to remove native menu: {err}"));
+    set_native_menu::<T>(connection, false).await;
+}
+
+async fn set_native_menu<T: Clone + Send + 'static>(connection: &Connection, native: bool) {
+    let result = async {
+        let iface = connection
+            .object_server()
+            .interface::<_, StatusNotifierItem<T>>(ITEM_PATH)
+            .await?;
+        let iref = iface.get().await;
+        iref.update_native_menu(native, iface.signal_context()).await
+    };
+    result
+        .await
+        .unwrap_or_else(|err| log::native_error!("Failed to update the menu path: {err}"));
+}
+
+async fn register_item(connection: &Connection, name: &str) -> zbus::Result<()> {
+    let proxy = StatusNotifierWatcherProxy::builder(connection)
+        .path("/StatusNotifierWatcher")?
+        .build()
+        .await?;
+    proxy.register_status_notifier_item(name).await
+}
+
+/// Runs the given future while also ticking the executor of the connection
+async fn tick_while<R>(connection: &Connection, future: impl Future<Output=R>) -> R {
+    future.or(async {
+        loop {
+            connection.executor().tick().await;
+        }
+    }).await
+}
+
+pub fn wake_event_loop() {
+    // The event loop is just a blocking wait on the exit signal
+}
+
 #[dbus_proxy(interface = "org.kde.StatusNotifierWatcher", assume_defaults = true)]
 trait StatusNotifierWatcher {
 
@@ -188,7 +570,13 @@ trait StatusNotifierWatcher {
 pub enum NativeIcon {
     #[allow(dead_code)]
     Path(String),
-    Pixels(Vec<u8>)
+    Pixels(Vec<u8>),
+    /// ARGB32 data in network byte order, which is passed to the host without writing a file
+    Pixmap {
+        argb: Arc<[u8]>,
+        width: u32,
+        height: u32
+    }
 }
 
 impl NativeIcon {
@@ -207,18 +595,74 @@ impl NativeIcon {
     pub fn from_png_bytes(bytes: &[u8]) -> TrayResult<Self> {
         Ok(Self::Pixels(bytes.to_vec()))
     }
-    fn write_to_disk(&self, id: (u32, u32), counter: &mut u32) -> (String, Option<TmpFileRaiiHandle>) {
+
+    pub fn from_pixmap(argb: Vec<u8>, width: u32, height: u32) -> Self {
+        Self::Pixmap { argb: argb.into(), width, height }
+    }
+
+    fn to_item_icon(&self, id: (u32, u32), counter: &mut u32) -> (ItemIcon, Option<TmpFileRaiiHandle>) {
         match self {
-            NativeIcon::Path(path) => (path.clone(), None),
+            NativeIcon::Path(path) => (ItemIcon { name: path.clone(), pixmap: Vec::new() }, None),
             NativeIcon::Pixels(pixels) => {
                 let tmp_path = get_tmp_icon_path(id, *counter);
                 *counter += 1;
                 //std::fs::create_dir_all(&tmp_path).unwrap();
                 std::fs::write(&tmp_path, pixels).unwrap();
-                (tmp_path.clone(), Some(TmpFileRaiiHandle(tmp_path)))
+                (ItemIcon { name: tmp_path.clone(), pixmap: Vec::new() }, Some(TmpFileRaiiHandle(tmp_path)))
             }
+            NativeIcon::Pixmap { .. } => (ItemIcon { name: String::new(), pixmap: self.to_pixmap() }, None)
         }
     }
+
+    /// Decodes the image into the ARGB32 format (in network byte order) of the `IconPixmap` property
+    fn to_pixmap(&self) -> Vec<(i32, i32, Vec<u8>)> {
+        let bytes = match self {
+            NativeIcon::Path(_) => return Vec::new(),
+            NativeIcon::Pixels(bytes) => bytes,
+            NativeIcon::Pixmap { argb, width, height } => return vec![(*width as i32, *height as i32, argb.to_vec())]
+        };
+        let mut decoder = png::Decoder::new(bytes.as_slice());
+        decoder.set_transformations(Transformations::normalize_to_color8() | Transformations::ALPHA);
+        let decoded = decoder
+            .read_info()
+            .and_then(|mut reader| {
+                let mut buffer = vec![0; reader.output_buffer_size()];
+                let info = reader.next_frame(&mut buffer)?;
+                buffer.truncate(info.buffer_size());
+                Ok((info, buffer))
+            });
+        let (info, buffer) = match decoded {
+            Ok(decoded) => decoded,
+            Err(err) => {
+                log::warn!("Failed to decode icon: {err}");
+                return Vec::new();
+            }
+        };
+        let argb = match info.color_type {
+            ColorType::Rgba => buffer
+                .chunks_exact(4)
+                .flat_map(|p| [p[3], p[0], p[1], p[2]])
+                .collect(),
+            ColorType::GrayscaleAlpha => buffer
+                .chunks_exact(2)
+                .flat_map(|p| [p[1], p[0], p[0], p[0]])
+                .collect(),
+            other => {
+                log::warn!("Unsupported icon color type: {other:?}");
+                return Vec::new();
+            }
+        };
+        vec![(info.width as i32, info.height as i32, argb)]
+    }
+}
+
+/// Reads the width and height from the header of a png image
+pub fn png_dimensions(bytes: &[u8]) -> TrayResult<(u32, u32)> {
+    let reader = png::Decoder::new(bytes)
+        .read_info()
+        .map_err(|err| TrayError::custom(format!("Invalid png image: {err}")))?;
+    let info = reader.info();
+    Ok((info.width, info.height))
 }
 
 struct TmpFileRaiiHandle(String);
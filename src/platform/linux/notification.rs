@@ -0,0 +1,131 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use futures_lite::StreamExt;
+use parking_lot::Mutex;
+use zbus::{Connection, dbus_proxy};
+use zbus::zvariant::Value;
+use crate::platform::linux::TrayCallback;
+use crate::{Notification, NotificationEvent, TrayEvent};
+use crate::log;
+
+const DEFAULT_ACTION: &str = "default";
+
+#[dbus_proxy(
+    interface = "org.freedesktop.Notifications",
+    default_service = "org.freedesktop.Notifications",
+    default_path = "/org/freedesktop/Notifications"
+)]
+trait Notifications {
+    #[allow(clippy::too_many_arguments)]
+    fn notify(&self, app_name: &str, replaces_id: u32, app_icon: &str, summary: &str, body: &str,
+              actions: &[&str], hints: HashMap<&str, Value<'_>>, expire_timeout: i32) -> zbus::Result<u32>;
+
+    #[dbus_proxy(property)]
+    fn inhibited(&self) -> zbus::Result<bool>;
+
+    #[dbus_proxy(signal)]
+    fn action_invoked(&self, id: u32, action_key: &str) -> zbus::Result<()>;
+
+    #[dbus_proxy(signal)]
+    fn notification_closed(&self, id: u32, reason: u32) -> zbus::Result<()>;
+}
+
+/// Keeps track of the notifications that are currently shown, so that signals
+/// caused by notifications of other applications can be ignored
+#[derive(Default, Clone)]
+pub struct ActiveNotifications(Arc<Mutex<HashSet<u32>>>);
+
+/// The last known `Inhibited` property of the notification server, which is only supported by some servers
+///
+/// `None` until the server replied or if it doesn't support the property.
+#[derive(Default, Clone)]
+pub struct InhibitedState(Arc<Mutex<Option<bool>>>);
+
+impl InhibitedState {
+    pub fn get(&self) -> Option<bool> {
+        *self.0.lock()
+    }
+}
+
+/// Keeps the state up to date, so it can be read without waiting for the server
+pub async fn watch_inhibited(connection: &Connection, state: InhibitedState) -> zbus::Result<()> {
+    let proxy = NotificationsProxy::new(connection).await?;
+    let mut changes = proxy.receive_inhibited_changed().await;
+    *state.0.lock() = proxy.inhibited().await.ok();
+    while let Some(change) = changes.next().await {
+        *state.0.lock() = change.get().await.ok();
+    }
+    Ok(())
+}
+
+pub async fn show_notification(connection: &Connection, notification: Notification, app_id: Option<&str>, active: &ActiveNotifications) -> zbus::Result<()> {
+    let proxy = NotificationsProxy::new(connection).await?;
+    if notification.respect_dnd && proxy.inhibited().await.unwrap_or(false) {
+        log::debug!("Dropping notification as do not disturb is active");
+        return Ok(());
+    }
+    // Actions are sent as a flat list of key/label pairs. The keys are the indices of the actions.
+    let keys: Vec<String> = (0..notification.actions.len())
+        .map(|i| i.to_string())
+        .collect();
+    let actions: Vec<&str> = [DEFAULT_ACTION, ""]
+        .into_iter()
+        .chain(keys
+            .iter()
+            .zip(&notification.actions)
+            .flat_map(|(key, label)| [key.as_str(), label.as_str()]))
+        .collect();
+    let mut hints = HashMap::from([
+        ("suppress-sound", Value::from(!notification.sound))
+    ]);
+    // Lets the notification server look up the name and icon of the application
+    if let Some(app_id) = app_id {
+        hints.insert("desktop-entry", Value::from(app_id));
+    }
+    let id = proxy
+        .notify(app_id.unwrap_or("betrayer"), 0, "", &notification.title, &notification.body, &actions, hints, -1)
+        .await?;
+    log::trace!("Showing notification {id}");
+    active.0.lock().insert(id);
+    Ok(())
+}
+
+pub async fn watch_notifications<T>(connection: Connection, active: ActiveNotifications, callback: TrayCallback<T>) -> zbus::Result<()> {
+    enum Signal {
+        Action(u32, NotificationEvent),
+        Closed(u32)
+    }
+
+    let proxy = NotificationsProxy::new(&connection).await?;
+    let actions = proxy
+        .receive_action_invoked()
+        .await?
+        .map(|signal| signal
+            .args()
+            .map(|args| match args.action_key {
+                DEFAULT_ACTION => Some(Signal::Action(args.id, NotificationEvent::Clicked)),
+                key => key
+                    .parse()
+                    .ok()
+                    .map(|index| Signal::Action(args.id, NotificationEvent::Action(index)))
+            }));
+    let closed = proxy
+        .receive_notification_closed()
+        .await?
+        .map(|signal| signal
+            .args()
+            .map(|args| Some(Signal::Closed(args.id))));
+    let mut signals = actions.or(closed);
+    while let Some(signal) = signals.next().await {
+        // An action is always followed by a close signal, so the notification is forgotten after the first one
+        let event = match signal? {
+            Some(Signal::Action(id, event)) => active.0.lock().remove(&id).then_some(event),
+            Some(Signal::Closed(id)) => active.0.lock().remove(&id).then_some(NotificationEvent::Dismissed),
+            None => None
+        };
+        if let Some(event) = event {
+            (callback.lock())(TrayEvent::Notification(event));
+        }
+    }
+    Ok(())
+}
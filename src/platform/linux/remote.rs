@@ -0,0 +1,100 @@
+use zbus::{Connection, ConnectionBuilder, dbus_interface, fdo, MessageHeader, SignalContext, Task};
+use zbus::names::BusName;
+use crate::error::TrayResult;
+use crate::remote::{RemoteIcon, RemoteMenuItem, RemoteRequest, Update, UpdateSender};
+use crate::log;
+
+pub const DBUS_PATH: &str = "/io/github/sidit77/Betrayer/Remote";
+pub const DBUS_INTERFACE: &str = "io.github.sidit77.Betrayer.Remote";
+
+pub type EventSender = flume::Sender<String>;
+
+/// Serves the remote interface on its own connection, so the name can be chosen freely
+pub struct RemoteTransport {
+    _connection: Connection,
+    _event_task: Task<()>
+}
+
+impl RemoteTransport {
+    pub fn start(name: &str, updates: UpdateSender) -> TrayResult<(Self, EventSender)> {
+        let connection = async_io::block_on(ConnectionBuilder::session()?
+            .name(name.to_owned())?
+            .serve_at(DBUS_PATH, RemoteInterface { updates })?
+            .build())?;
+        let (sender, receiver) = flume::unbounded::<String>();
+        let ctx = SignalContext::new(&connection, DBUS_PATH)?.into_owned();
+        // A single task keeps the events in order
+        let event_task = connection.executor().spawn(async move {
+            while let Ok(event) = receiver.recv_async().await {
+                RemoteInterface::event(&ctx, &event)
+                    .await
+                    .unwrap_or_else(|err| log::native_error!("Failed to forward event: {err}"));
+            }
+        }, "remote events");
+        Ok((Self { _connection: connection, _event_task: event_task }, sender))
+    }
+}
+
+struct RemoteInterface {
+    updates: UpdateSender
+}
+
+impl RemoteInterface {
+    async fn request(&self, connection: &Connection, header: &MessageHeader<'_>, request: RemoteRequest) -> fdo::Result<()> {
+        check_caller(connection, header).await?;
+        let update = Update::try_from(request)
+            .map_err(|err| fdo::Error::InvalidArgs(err.to_string()))?;
+        self.updates
+            .send(update)
+            .map_err(|_| fdo::Error::Failed(String::from("The tray icon no longer exists")))
+    }
+}
+
+/// Rejects other users, in case the session bus accepts connections from them
+async fn check_caller(connection: &Connection, header: &MessageHeader<'_>) -> fdo::Result<()> {
+    let sender = header
+        .sender()?
+        .ok_or_else(|| fdo::Error::AccessDenied(String::from("Unknown sender")))?;
+    let uid = fdo::DBusProxy::new(connection)
+        .await?
+        .get_connection_unix_user(BusName::from(sender.clone()))
+        .await?;
+    match uid == 0 || uid == unsafe { libc::getuid() } {
+        true => Ok(()),
+        false => {
+            log::warn!("Rejecting remote request from {sender} of user {uid}");
+            Err(fdo::Error::AccessDenied(String::from("The tray icon belongs to another user")))
+        }
+    }
+}
+
+fn non_empty(string: String) -> Option<String> {
+    (!string.is_empty()).then_some(string)
+}
+
+#[dbus_interface(name = "io.github.sidit77.Betrayer.Remote")]
+impl RemoteInterface {
+
+    async fn set_tooltip(&self, #[zbus(connection)] connection: &Connection, #[zbus(header)] header: MessageHeader<'_>, tooltip: String) -> fdo::Result<()> {
+        self.request(connection, &header, RemoteRequest::SetTooltip(non_empty(tooltip))).await
+    }
+
+    async fn set_title(&self, #[zbus(connection)] connection: &Connection, #[zbus(header)] header: MessageHeader<'_>, title: String) -> fdo::Result<()> {
+        self.request(connection, &header, RemoteRequest::SetTitle(non_empty(title))).await
+    }
+
+    async fn set_icon(&self, #[zbus(connection)] connection: &Connection, #[zbus(header)] header: MessageHeader<'_>, width: u32, height: u32, rgba: Vec<u8>) -> fdo::Result<()> {
+        let icon = (!rgba.is_empty()).then_some(RemoteIcon { rgba, width, height });
+        self.request(connection, &header, RemoteRequest::SetIcon(icon)).await
+    }
+
+    async fn set_menu(&self, #[zbus(connection)] connection: &Connection, #[zbus(header)] header: MessageHeader<'_>, menu: String) -> fdo::Result<()> {
+        let menu: Option<Vec<RemoteMenuItem>> = serde_json::from_str(&menu)
+            .map_err(|err| fdo::Error::InvalidArgs(format!("Invalid menu: {err}")))?;
+        self.request(connection, &header, RemoteRequest::SetMenu(menu)).await
+    }
+
+    #[dbus_interface(signal)]
+    async fn event(ctx: &SignalContext<'_>, event: &str) -> zbus::Result<()> {}
+
+}
@@ -0,0 +1,84 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use async_io::Timer;
+use parking_lot::Mutex;
+use zbus::Connection;
+use crate::{ScrollOrientation, TrayEvent};
+use crate::platform::linux::TrayCallback;
+
+/// Sums up the deltas of incoming scroll events and limits how often they get delivered
+pub struct ScrollCoalescer {
+    interval: Option<Duration>,
+    state: Arc<Mutex<ScrollState>>
+}
+
+#[derive(Default)]
+struct ScrollState {
+    last_emit: Option<Instant>,
+    flush_scheduled: bool,
+    vertical: i32,
+    horizontal: i32
+}
+
+impl ScrollState {
+    fn take_events<T>(&mut self) -> impl Iterator<Item=TrayEvent<T>> {
+        self.last_emit = Some(Instant::now());
+        let vertical = std::mem::take(&mut self.vertical);
+        let horizontal = std::mem::take(&mut self.horizontal);
+        [(ScrollOrientation::Vertical, vertical), (ScrollOrientation::Horizontal, horizontal)]
+            .into_iter()
+            .filter(|(_, delta)| *delta != 0)
+            .map(|(orientation, delta)| TrayEvent::Scroll { orientation, delta })
+    }
+}
+
+impl ScrollCoalescer {
+    pub fn new(max_rate: Option<u32>) -> Self {
+        Self {
+            interval: max_rate
+                .filter(|rate| *rate > 0)
+                .map(|rate| Duration::from_secs(1) / rate),
+            state: Arc::default(),
+        }
+    }
+
+    pub fn scroll<T: Send + 'static>(&self, connection: &Connection, delta: i32, orientation: ScrollOrientation, callback: &TrayCallback<T>) {
+        let Some(interval) = self.interval else {
+            (callback.lock())(TrayEvent::Scroll { orientation, delta });
+            return;
+        };
+        let mut state = self.state.lock();
+        match orientation {
+            ScrollOrientation::Vertical => state.vertical = state.vertical.saturating_add(delta),
+            ScrollOrientation::Horizontal => state.horizontal = state.horizontal.saturating_add(delta)
+        }
+        if state.flush_scheduled {
+            return;
+        }
+        let next_emit = state
+            .last_emit
+            .map(|last| last + interval)
+            .filter(|next| *next > Instant::now());
+        match next_emit {
+            None => {
+                let events: Vec<_> = state.take_events().collect();
+                drop(state);
+                events.into_iter().for_each(|event| (callback.lock())(event));
+            }
+            Some(next_emit) => {
+                state.flush_scheduled = true;
+                let state = self.state.clone();
+                let callback = callback.clone();
+                connection.executor().spawn(async move {
+                    Timer::at(next_emit).await;
+                    let events: Vec<_> = {
+                        let mut state = state.lock();
+                        state.flush_scheduled = false;
+                        state.take_events().collect()
+                    };
+                    events.into_iter().for_each(|event| (callback.lock())(event));
+                }, "scroll flush").detach();
+            }
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use futures_lite::StreamExt;
+use zbus::{Connection, ConnectionBuilder, dbus_proxy};
+use crate::platform::linux::{register_item, tick_while, TrayCallback};
+use crate::TrayEvent;
+use crate::log;
+
+#[dbus_proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait LoginManager {
+    #[dbus_proxy(signal)]
+    fn prepare_for_sleep(&self, start: bool) -> zbus::Result<()>;
+
+    #[dbus_proxy(signal)]
+    fn prepare_for_shutdown(&self, start: bool) -> zbus::Result<()>;
+}
+
+/// Forwards sleep and shutdown signals of logind, `connection` is the session bus the item is registered on
+pub async fn watch_session<T>(connection: Connection, name: String, callback: TrayCallback<T>) -> zbus::Result<()> {
+    // The system connection is driven by this task instead of a thread of its own,
+    // so that it runs on the same executor as the rest of the tray icon
+    let system = ConnectionBuilder::system()?
+        .internal_executor(false)
+        .build()
+        .await?;
+    tick_while(&system, forward_session_events(&system, connection, name, callback)).await
+}
+
+async fn forward_session_events<T>(system: &Connection, connection: Connection, name: String, callback: TrayCallback<T>) -> zbus::Result<()> {
+    let proxy = LoginManagerProxy::new(system).await?;
+    let sleep = proxy
+        .receive_prepare_for_sleep()
+        .await?
+        .map(|signal| signal
+            .args()
+            .map(|args| (!args.start).then_some(TrayEvent::Resumed)));
+    let shutdown = proxy
+        .receive_prepare_for_shutdown()
+        .await?
+        .map(|signal| signal
+            .args()
+            .map(|args| args.start.then_some(TrayEvent::SessionEnding)));
+    let mut events = sleep.or(shutdown);
+    while let Some(event) = events.next().await {
+        if let Some(event) = event? {
+            log::trace!("Received login session event");
+            if matches!(event, TrayEvent::Resumed) {
+                // The watcher may have lost the item while suspended, registering twice is harmless
+                register_item(&connection, &name)
+                    .await
+                    .unwrap_or_else(|err| log::native_error!("Failed to register again after resuming: {err}"));
+            }
+            (callback.lock())(event);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use crate::error::{TrayError, TrayResult};
+
+fn agent_path(name: &str) -> TrayResult<PathBuf> {
+    let home = std::env::var_os("HOME")
+        .ok_or_else(|| TrayError::custom("HOME is not set"))?;
+    Ok(PathBuf::from(home).join("Library/LaunchAgents").join(format!("{name}.plist")))
+}
+
+fn xml_escape(text: &str) -> String {
+    text
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+pub fn enable(name: &str, command: &[String]) -> TrayResult<()> {
+    let path = agent_path(name)?;
+    let arguments: String = command
+        .iter()
+        .map(|arg| format!("        <string>{}</string>\n", xml_escape(arg)))
+        .collect();
+    // launchd picks up the agent at the next login
+    let agent = format!(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n",
+        "<plist version=\"1.0\">\n",
+        "<dict>\n",
+        "    <key>Label</key>\n",
+        "    <string>{}</string>\n",
+        "    <key>ProgramArguments</key>\n",
+        "    <array>\n",
+        "{}",
+        "    </array>\n",
+        "    <key>RunAtLoad</key>\n",
+        "    <true/>\n",
+        "</dict>\n",
+        "</plist>\n"
+    ), xml_escape(name), arguments);
+    let write = || {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, agent)
+    };
+    write().map_err(|err| TrayError::custom(format!("Failed to write {path:?}: {err}")))
+}
+
+pub fn disable(name: &str) -> TrayResult<()> {
+    let path = agent_path(name)?;
+    match std::fs::remove_file(&path) {
+        Err(err) if err.kind() != ErrorKind::NotFound => Err(TrayError::custom(format!("Failed to remove {path:?}: {err}"))),
+        _ => Ok(())
+    }
+}
+
+pub fn is_enabled(name: &str) -> TrayResult<bool> {
+    Ok(agent_path(name)?.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markup_is_escaped() {
+        assert_eq!(xml_escape("/Applications/App.app"), "/Applications/App.app");
+        assert_eq!(xml_escape("a<b>&c"), "a&lt;b&gt;&amp;c");
+        assert_eq!(xml_escape("&lt;"), "&amp;lt;");
+    }
+
+}
@@ -2,12 +2,14 @@ use std::ptr::NonNull;
 
 use block2::{Block, ConcreteBlock, RcBlock};
 use icrate::AppKit::NSControl;
+use icrate::Foundation::{NSObjectProtocol, NSUserNotification, NSUserNotificationActivationTypeActionButtonClicked, NSUserNotificationActivationTypeAdditionalActionClicked, NSUserNotificationCenter, NSUserNotificationCenterDelegate};
 use objc2::{ClassType, declare_class, msg_send_id, msg_send, sel};
 use objc2::runtime::{NSObject, Sel};
 use objc2::declare::{Ivar, IvarDrop};
 use objc2::ffi::NSInteger;
 use objc2::mutability::InteriorMutable;
 use objc2::rc::Id;
+use crate::platform::macos::{TAG_NOTIFICATION_ACTION_BASE, TAG_NOTIFICATION_CLICKED};
 
 declare_class!(
     #[derive(Debug)]
@@ -43,6 +45,28 @@ declare_class!(
             }
         }
     }
+
+    unsafe impl NSObjectProtocol for SystemTrayCallback {}
+
+    unsafe impl NSUserNotificationCenterDelegate for SystemTrayCallback {
+        #[method(userNotificationCenter:didActivateNotification:)]
+        unsafe fn did_activate_notification(&self, _center: &NSUserNotificationCenter, notification: &NSUserNotification) {
+            // The first action is the regular action button, all others are additional actions identified by their index
+            let action = match notification.activationType() {
+                NSUserNotificationActivationTypeActionButtonClicked => Some(0),
+                NSUserNotificationActivationTypeAdditionalActionClicked => notification
+                    .additionalActivationAction()
+                    .and_then(|action| action.identifier())
+                    .and_then(|identifier| identifier.to_string().parse::<NSInteger>().ok()),
+                _ => None
+            };
+            let tag = match action {
+                Some(index) => TAG_NOTIFICATION_ACTION_BASE - index,
+                None => TAG_NOTIFICATION_CLICKED
+            };
+            self.callback.call((tag,));
+        }
+    }
 );
 
 impl SystemTrayCallback {
@@ -55,6 +79,10 @@ impl SystemTrayCallback {
         Self::from_block(&*callback_block)
     }
 
+    pub fn call(&self, tag: NSInteger) {
+        self.callback.call((tag,));
+    }
+
     pub fn selector() -> Sel {
         sel!(call_control:)
     }
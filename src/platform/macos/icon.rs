@@ -0,0 +1,122 @@
+use std::borrow::Cow;
+use std::ptr::null_mut;
+use std::sync::Arc;
+use icrate::AppKit::{NSBitmapImageRep, NSDeviceRGBColorSpace, NSImage, NSScreen};
+use icrate::Foundation::{NSData, NSSize};
+use objc2::ClassType;
+use objc2::rc::Id;
+use crate::error::{TrayError, TrayResult};
+use crate::log;
+use crate::utils::resize_rgba;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct NativeIcon {
+    image: IconImage,
+    template: bool
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum IconImage {
+    Pixels {
+        rgba: Arc<[u8]>,
+        width: u32,
+        height: u32
+    },
+    /// An image that was created by the application, encoded as TIFF as AppKit objects can't be shared between threads
+    Encoded {
+        data: Arc<[u8]>,
+        template: bool
+    }
+}
+
+impl NativeIcon {
+    pub fn from_rgba(rgba: Vec<u8>, width: u32, height: u32) -> TrayResult<Self> {
+        Ok(Self {
+            image: IconImage::Pixels {
+                rgba: rgba.into(),
+                width,
+                height
+            },
+            template: false
+        })
+    }
+
+    pub unsafe fn from_nsimage(image: *mut NSImage) -> TrayResult<Self> {
+        let image = image
+            .as_ref()
+            .ok_or_else(|| TrayError::custom("The image is null"))?;
+        let data = image
+            .TIFFRepresentation()
+            .ok_or_else(|| TrayError::custom("Failed to encode the image"))?;
+        Ok(Self {
+            image: IconImage::Encoded {
+                data: data.bytes().into(),
+                template: image.isTemplate()
+            },
+            template: false
+        })
+    }
+
+    /// Template images only use their alpha channel and are tinted by the system to match the menu bar
+    pub fn set_template(&mut self, template: bool) {
+        self.template = template;
+    }
+
+    /// Creates a new `NSImage` with the given size in points
+    ///
+    /// With a size the image is rasterized at the resolution of the main screen, as AppKit would otherwise stretch it.
+    pub fn to_nsimage(&self, size: Option<f64>) -> Id<NSImage> {
+        log::trace!("Creating new native image");
+        let (rgba, width, height) = match &self.image {
+            IconImage::Pixels { rgba, width, height } => (rgba, *width, *height),
+            IconImage::Encoded { data, template } => unsafe {
+                let image = NSImage::initWithData(NSImage::alloc(), &NSData::with_bytes(data))
+                    .expect("Failed to decode image");
+                if let Some(size) = size {
+                    let original = image.size();
+                    if original.height > 0.0 {
+                        image.setSize(NSSize::new(size * original.width / original.height, size));
+                    }
+                }
+                image.setTemplate(self.template || *template);
+                return image;
+            }
+        };
+        unsafe {
+            let (pixels, pixels_wide, pixels_high) = match size {
+                Some(size) => {
+                    let scale = NSScreen::mainScreen()
+                        .map(|screen| screen.backingScaleFactor())
+                        .unwrap_or(1.0);
+                    let pixels_high = (size * scale).round().max(1.0) as u32;
+                    let pixels_wide = (pixels_high as u64 * width as u64 / height as u64).max(1) as u32;
+                    (Cow::Owned(resize_rgba(rgba, width, height, pixels_wide, pixels_high)), pixels_wide, pixels_high)
+                }
+                None => (Cow::Borrowed(&**rgba), width, height)
+            };
+            let rep = NSBitmapImageRep::initWithBitmapDataPlanes_pixelsWide_pixelsHigh_bitsPerSample_samplesPerPixel_hasAlpha_isPlanar_colorSpaceName_bytesPerRow_bitsPerPixel(
+                NSBitmapImageRep::alloc(),
+                null_mut(),
+                pixels_wide as _,
+                pixels_high as _,
+                8,
+                4,
+                true,
+                false,
+                NSDeviceRGBColorSpace,
+                4 * pixels_wide as isize,
+                32
+            ).expect("Failed to create bitmap");
+            std::ptr::copy_nonoverlapping(pixels.as_ptr(), rep.bitmapData(), pixels.len());
+
+            let size = match size {
+                Some(size) => NSSize::new(size * width as f64 / height as f64, size),
+                None => NSSize::new(width as f64, height as f64)
+            };
+            let image = NSImage::initWithSize(NSImage::alloc(), size);
+            image.addRepresentation(&rep);
+            image.setTemplate(self.template);
+            image
+        }
+    }
+}
@@ -1,35 +1,71 @@
-use icrate::AppKit::{NSControlStateValueOff, NSControlStateValueOn, NSMenu, NSMenuItem};
-use icrate::Foundation::NSString;
+use icrate::AppKit::{NSColor, NSControlStateValueOff, NSControlStateValueOn, NSEventModifierFlagCommand, NSEventModifierFlagControl, NSEventModifierFlagOption, NSEventModifierFlags, NSEventModifierFlagShift, NSFont, NSFontAttributeName, NSForegroundColorAttributeName, NSMenu, NSMenuItem};
+use icrate::Foundation::{NSAttributedString, NSAttributedStringKey, NSDictionary, NSObject, NSString};
 use objc2::ClassType;
 use objc2::ffi::NSInteger;
 use objc2::rc::Id;
-use crate::{Menu, MenuItem};
+use crate::{ItemStyle, Menu, MenuItem, Modifiers, Shortcut};
 use crate::platform::macos::callback::SystemTrayCallback;
 
-pub unsafe fn build_menu_item<T>(item: MenuItem<T>, callback: &SystemTrayCallback, signal_map: &mut Vec<T>) -> Id<NSMenuItem> {
+unsafe fn add_menu_item<T>(menu: &NSMenu, item: MenuItem<T>, callback: &SystemTrayCallback, signal_map: &mut Vec<T>) {
+    let hidden = !item.is_visible();
+    match item {
+        MenuItem::Button { name, signal, shortcut, alternate: Some(alternate), style, .. } => {
+            let primary = build_button(&name, signal, shortcut, style, callback, signal_map);
+            primary.setHidden(hidden);
+            menu.addItem(&primary);
+            // Alternates have to share the key equivalent of the primary item and differ only by the option modifier
+            let button = build_button(&alternate.name, alternate.signal, shortcut, style, callback, signal_map);
+            button.setKeyEquivalentModifierMask(shortcut.map_or(0, modifier_mask) | NSEventModifierFlagOption);
+            button.setAlternate(true);
+            button.setHidden(hidden);
+            menu.addItem(&button);
+        },
+        item => {
+            let native = build_menu_item(item, callback, signal_map);
+            native.setHidden(hidden);
+            menu.addItem(&native);
+        }
+    }
+}
+
+/// Converts the index of a [MenuItem] into the index of the native item, skipping over the items of alternates
+pub unsafe fn native_index(menu: &NSMenu, index: usize) -> Option<NSInteger> {
+    (0..menu.numberOfItems())
+        .filter(|i| menu
+            .itemAtIndex(*i)
+            .is_some_and(|item| !item.isAlternate()))
+        .nth(index)
+}
+
+unsafe fn build_menu_item<T>(item: MenuItem<T>, callback: &SystemTrayCallback, signal_map: &mut Vec<T>) -> Id<NSMenuItem> {
     match item {
         MenuItem::Separator => NSMenuItem::separatorItem(),
-        MenuItem::Button { name, checked, signal } => {
-            let button = NSMenuItem::initWithTitle_action_keyEquivalent(
-                NSMenuItem::alloc(),
-                &NSString::from_str(&name),
-                None,
-                &NSString::from_str("")
-            );
+        MenuItem::Button { name, signal, shortcut, style, .. } => {
+            build_button(&name, signal, shortcut, style, callback, signal_map)
+        },
+        MenuItem::CheckButton { name, signal, checked, shortcut, state_images, style, .. } => {
+            let button = build_button(&name, signal, shortcut, style, callback, signal_map);
             button.setState(match checked {
                 true => NSControlStateValueOn,
                 false => NSControlStateValueOff
             });
-            button.setTarget(Some(callback));
-            button.setAction(Some(SystemTrayCallback::selector()));
-            button.setTag(signal_map.len() as NSInteger);
-            signal_map.push(signal);
+            // Menu items are displayed at roughly the height of the system font
+            let size = Some(NSFont::menuFontOfSize(0.0).pointSize());
+            if let Some(image) = state_images.on {
+                button.setOnStateImage(Some(&image.0.to_nsimage(size)));
+            }
+            if let Some(image) = state_images.off {
+                button.setOffStateImage(Some(&image.0.to_nsimage(size)));
+            }
+            if let Some(image) = state_images.mixed {
+                button.setMixedStateImage(Some(&image.0.to_nsimage(size)));
+            }
             button
         },
-        MenuItem::Menu { name, children } => {
+        MenuItem::Menu { name, children, .. } => {
             let sub = NSMenu::new();
             for item in children {
-                sub.addItem(&build_menu_item(item, callback, signal_map));
+                add_menu_item(&sub, item, callback, signal_map);
             }
             let button = NSMenuItem::initWithTitle_action_keyEquivalent(
                 NSMenuItem::alloc(),
@@ -43,12 +79,64 @@ pub unsafe fn build_menu_item<T>(item: MenuItem<T>, callback: &SystemTrayCallbac
     }
 }
 
+unsafe fn build_button<T>(name: &str, signal: T, shortcut: Option<Shortcut>, style: ItemStyle, callback: &SystemTrayCallback, signal_map: &mut Vec<T>) -> Id<NSMenuItem> {
+    let key_equivalent = shortcut
+        .map(|shortcut| shortcut.key.to_string())
+        .unwrap_or_default();
+    let button = NSMenuItem::initWithTitle_action_keyEquivalent(
+        NSMenuItem::alloc(),
+        &NSString::from_str(name),
+        None,
+        &NSString::from_str(&key_equivalent)
+    );
+    if let Some(shortcut) = shortcut {
+        button.setKeyEquivalentModifierMask(modifier_mask(shortcut));
+    }
+    if let Some(title) = styled_title(name, style) {
+        button.setAttributedTitle(Some(&title));
+    }
+    button.setTarget(Some(callback));
+    button.setAction(Some(SystemTrayCallback::selector()));
+    button.setTag(signal_map.len() as NSInteger);
+    signal_map.push(signal);
+    button
+}
+
+unsafe fn styled_title(name: &str, style: ItemStyle) -> Option<Id<NSAttributedString>> {
+    let (key, value): (&NSAttributedStringKey, Id<NSObject>) = match style {
+        ItemStyle::Normal => return None,
+        ItemStyle::Destructive => (NSForegroundColorAttributeName, Id::into_super(NSColor::systemRedColor())),
+        ItemStyle::Highlighted => {
+            let size = NSFont::menuFontOfSize(0.0).pointSize();
+            (NSFontAttributeName, Id::into_super(NSFont::boldSystemFontOfSize(size)))
+        }
+    };
+    let attributes = NSDictionary::from_keys_and_objects(&[key], vec![value]);
+    Some(NSAttributedString::initWithString_attributes(
+        NSAttributedString::alloc(),
+        &NSString::from_str(name),
+        Some(&attributes)
+    ))
+}
+
+fn modifier_mask(shortcut: Shortcut) -> NSEventModifierFlags {
+    [
+        (Modifiers::SHIFT, NSEventModifierFlagShift),
+        (Modifiers::CONTROL, NSEventModifierFlagControl),
+        (Modifiers::OPTION, NSEventModifierFlagOption),
+        (Modifiers::COMMAND, NSEventModifierFlagCommand)
+    ]
+        .into_iter()
+        .filter(|(modifier, _)| shortcut.modifiers.contains(*modifier))
+        .fold(0, |mask, (_, flag)| mask | flag)
+}
+
 pub fn construct_native_menu<T>(menu: Menu<T>, callback: &SystemTrayCallback) -> (Id<NSMenu>, Vec<T>) {
     unsafe {
         let mut signal_map = Vec::new();
         let native_menu = NSMenu::new();
         for item in menu.items {
-            native_menu.addItem(&build_menu_item(item, callback, &mut signal_map));
+            add_menu_item(&native_menu, item, callback, &mut signal_map);
         }
         (native_menu, signal_map)
     }
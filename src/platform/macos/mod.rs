@@ -1,21 +1,51 @@
 mod menu;
 mod callback;
+mod icon;
+#[cfg(feature = "autostart")]
+pub mod autostart;
 
 use std::cell::{Cell, RefCell};
+use std::ptr::NonNull;
 use std::rc::Rc;
-use icrate::AppKit::{NSApplication, NSStatusBar, NSStatusItem, NSVariableStatusItemLength};
-use icrate::Foundation::NSString;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+use block2::ConcreteBlock;
+use icrate::AppKit::{NSApp, NSApplication, NSApplicationActivationPolicyAccessory, NSCriticalRequest, NSEvent, NSEventTypeApplicationDefined, NSInformationalRequest, NSMenu, NSStatusBar, NSStatusItem, NSVariableStatusItemLength, NSWorkspace, NSWorkspaceDidWakeNotification, NSWorkspaceWillPowerOffNotification};
+use icrate::Foundation::{NSArray, NSBundle, NSNotification, NSObject, NSPoint, NSProcessInfo, NSString, NSThread, NSTimer, NSUserNotification, NSUserNotificationAction, NSUserNotificationCenter, NSUserNotificationDefaultSoundName};
+use objc2::runtime::ProtocolObject;
+use objc2::ffi::NSInteger;
 use objc2::rc::Id;
-use crate::error::TrayResult;
-use crate::{ClickType, Menu, TrayEvent, TrayIconBuilder, Icon};
+use crate::error::{ErrorSource, TrayError, TrayResult};
+use crate::{Backend, ClickType, ensure, Menu, MenuItemId, Notification, NotificationEvent, TrayEvent, TrayIconBuilder, Icon};
 use crate::platform::macos::callback::SystemTrayCallback;
-use crate::platform::macos::menu::construct_native_menu;
+use crate::platform::macos::menu::{construct_native_menu, native_index};
 use crate::utils::OptionCellExt;
+use crate::control_flow::ExitSignal;
+use crate::log;
+
+pub const BACKEND: Backend = Backend::MacStatusItem;
+
+pub type IntervalCallback = Box<dyn FnMut() + 'static>;
+
+pub type Acknowledgement = std::future::Ready<()>;
+
+static STATUS_ITEM_COUNTER: AtomicU32 = AtomicU32::new(1);
+
+const TAG_TRAY: NSInteger = -1;
+const TAG_RESUMED: NSInteger = -2;
+const TAG_SESSION_ENDING: NSInteger = -3;
+pub(crate) const TAG_NOTIFICATION_CLICKED: NSInteger = -4;
+pub(crate) const TAG_NOTIFICATION_ACTION_BASE: NSInteger = -100;
+
+/// The height of the icon in the menu bar in points
+const STATUS_ICON_SIZE: f64 = 18.0;
 
 pub struct NativeTrayIcon<T> {
     status_item: Id<NSStatusItem>,
     signal_map: Rc<Cell<Option<Vec<T>>>>,
-    callback: Id<SystemTrayCallback>
+    callback: Id<SystemTrayCallback>,
+    timers: RefCell<Vec<Id<NSTimer>>>,
+    observers: Vec<Id<NSObject>>
 }
 
 impl<T: Clone + 'static> NativeTrayIcon<T> {
@@ -23,10 +53,27 @@ impl<T: Clone + 'static> NativeTrayIcon<T> {
         where F: FnMut(TrayEvent<T>) + Send + 'static
     {
         unsafe {
-            NSApplication::sharedApplication();
+            ensure!(NSThread::isMainThread_class(), TrayError::custom("The tray icon must be created on the main thread"));
+            if NSApp.is_none() {
+                // Without an existing application (e.g. in command line tools) the status item would never show up.
+                // The accessory policy keeps the application out of the dock and the app switcher.
+                // Nothing runs its event loop either, which has to be done with TrayIcon::run or TrayIcon::run_managed
+                log::debug!("No NSApplication found, creating a new one");
+                let app = NSApplication::sharedApplication();
+                app.setActivationPolicy(NSApplicationActivationPolicyAccessory);
+                app.finishLaunching();
+            }
 
             let status_bar = NSStatusBar::systemStatusBar();
             let status_item = status_bar.statusItemWithLength(NSVariableStatusItemLength);
+            let id = STATUS_ITEM_COUNTER.fetch_add(1, Ordering::Relaxed);
+            // A name that stays the same across launches lets macOS remember where the user placed the icon
+            let app_id = builder.app_id
+                .clone()
+                .or_else(|| NSBundle::mainBundle().bundleIdentifier().map(|id| id.to_string()))
+                .unwrap_or_else(|| NSProcessInfo::processInfo().processName().to_string());
+            let autosave_name = format!("{app_id}-{id}");
+            status_item.setAutosaveName(Some(&NSString::from_str(&autosave_name)));
 
             let signal_map = Rc::new(Cell::new(None));
 
@@ -34,14 +81,23 @@ impl<T: Clone + 'static> NativeTrayIcon<T> {
                 let signal_map = signal_map.clone();
                 let callback = RefCell::new(callback);
                 SystemTrayCallback::new(move |tag| {
-                    if tag == -1 {
-                        callback.borrow_mut()(TrayEvent::Tray(ClickType::Left));
+                    if tag == TAG_TRAY {
+                        callback.borrow_mut()(TrayEvent::Tray { click: ClickType::Left, activation_token: None });
+                    } else if tag == TAG_RESUMED {
+                        callback.borrow_mut()(TrayEvent::Resumed);
+                    } else if tag == TAG_SESSION_ENDING {
+                        callback.borrow_mut()(TrayEvent::SessionEnding);
+                    } else if tag == TAG_NOTIFICATION_CLICKED {
+                        callback.borrow_mut()(TrayEvent::Notification(NotificationEvent::Clicked));
+                    } else if tag <= TAG_NOTIFICATION_ACTION_BASE {
+                        let index = (TAG_NOTIFICATION_ACTION_BASE - tag) as usize;
+                        callback.borrow_mut()(TrayEvent::Notification(NotificationEvent::Action(index)));
                     } else {
                         let signal = signal_map
                             .with(|map: &mut Vec<T> | map.get(tag as usize).cloned())
                             .flatten();
                         if let Some(signal) = signal {
-                            callback.borrow_mut()(TrayEvent::Menu(signal));
+                            callback.borrow_mut()(TrayEvent::Menu { signal, activation_token: None });
                         } else {
                             log::debug!("Failed to get signal for tag {}", tag);
                         }
@@ -50,10 +106,13 @@ impl<T: Clone + 'static> NativeTrayIcon<T> {
             };
 
             if let Some(button) = status_item.button() {
-                button.setTitle(&NSString::from_str("TEST BUTTON"));
+                button.setTitle(&NSString::from_str(builder.title.as_deref().unwrap_or_default()));
+                if let Some(icon) = &builder.icon {
+                    button.setImage(Some(&icon.0.to_nsimage(Some(STATUS_ICON_SIZE))));
+                }
                 button.setTarget(Some(&callback));
                 button.setAction(Some(SystemTrayCallback::selector()));
-                button.setTag(-1);
+                button.setTag(TAG_TRAY);
             }
 
             if let Some((menu, map)) = builder.menu.map(|menu| construct_native_menu(menu, &callback)) {
@@ -61,10 +120,20 @@ impl<T: Clone + 'static> NativeTrayIcon<T> {
                 signal_map.set(Some(map));
             }
 
+            NSUserNotificationCenter::defaultUserNotificationCenter()
+                .setDelegate(Some(ProtocolObject::from_ref(&*callback)));
+
+            let observers = vec![
+                observe_workspace(NSWorkspaceDidWakeNotification, &callback, TAG_RESUMED),
+                observe_workspace(NSWorkspaceWillPowerOffNotification, &callback, TAG_SESSION_ENDING)
+            ];
+
             Ok(Self {
                 status_item,
                 signal_map,
-                callback
+                callback,
+                timers: RefCell::new(Vec::new()),
+                observers
             })
         }
     }
@@ -73,6 +142,13 @@ impl<T: Clone + 'static> NativeTrayIcon<T> {
 impl<T> Drop for NativeTrayIcon<T> {
     fn drop(&mut self) {
         unsafe {
+            for timer in self.timers.take() {
+                timer.invalidate();
+            }
+            let notification_center = NSWorkspace::sharedWorkspace().notificationCenter();
+            for observer in &self.observers {
+                notification_center.removeObserver(observer);
+            }
             let status_bar = self
                 .status_item
                 .statusBar()
@@ -87,8 +163,193 @@ impl<T> NativeTrayIcon<T> {
 
     }
 
-    pub fn set_icon(&self, _icon: Option<Icon>) {
+    pub fn set_title(&self, title: Option<String>) {
+        unsafe {
+            if let Some(button) = self.status_item.button() {
+                button.setTitle(&NSString::from_str(title.as_deref().unwrap_or_default()));
+            }
+        }
+    }
+
+    pub fn set_icon(&self, icon: Option<Icon>) {
+        unsafe {
+            if let Some(button) = self.status_item.button() {
+                let image = icon.map(|icon| icon.0.to_nsimage(Some(STATUS_ICON_SIZE)));
+                button.setImage(image.as_deref());
+            }
+        }
+    }
+
+    /// There is no icon theme, so this always shows the fallback
+    pub fn set_themed_icon(&self, _name: String, fallback: Option<Icon>) {
+        self.set_icon(fallback)
+    }
+
+    pub fn show_notification(&self, notification: Notification) {
+        if notification.respect_dnd && self.is_do_not_disturb().unwrap_or(false) {
+            log::debug!("Dropping notification as do not disturb is active");
+            return;
+        }
+        unsafe {
+            let native = NSUserNotification::new();
+            native.setTitle(Some(&NSString::from_str(&notification.title)));
+            native.setInformativeText(Some(&NSString::from_str(&notification.body)));
+            if notification.sound {
+                native.setSoundName(Some(NSUserNotificationDefaultSoundName));
+            }
+            if let Some((first, additional)) = notification.actions.split_first() {
+                native.setHasActionButton(true);
+                native.setActionButtonTitle(&NSString::from_str(first));
+                let additional: Vec<_> = additional
+                    .iter()
+                    .enumerate()
+                    .map(|(i, label)| NSUserNotificationAction::actionWithIdentifier_title(
+                        Some(&NSString::from_str(&(i + 1).to_string())),
+                        Some(&NSString::from_str(label))))
+                    .collect();
+                native.setAdditionalActions(Some(&NSArray::from_vec(additional)));
+            }
+            NSUserNotificationCenter::defaultUserNotificationCenter().deliverNotification(&native);
+        }
+    }
+
+    /// Focus replaced do not disturb and its state is only exposed through the Intents framework, which requires an
+    /// entitlement and the permission of the user
+    pub fn is_do_not_disturb(&self) -> TrayResult<bool> {
+        Err(TrayError::from(ErrorSource::NotSupported))
+    }
+
+    /// Updates are applied synchronously, so they are always acknowledged
+    pub fn acknowledge(&self) -> Acknowledgement {
+        std::future::ready(())
+    }
+
+    pub fn id(&self) -> String {
+        unsafe { self.status_item.autosaveName() }.to_string()
+    }
+
+    pub fn run(&self, exit: &ExitSignal) {
+        // Other code may stop the application as well, so only an exit request ends the loop
+        while !exit.is_requested() {
+            unsafe {
+                NSApplication::sharedApplication().run();
+            }
+        }
+    }
+
+    pub fn set_interval(&self, interval: Duration, callback: IntervalCallback) {
+        let callback = RefCell::new(callback);
+        let block = ConcreteBlock::new(move |_: NonNull<NSTimer>| {
+            match callback.try_borrow_mut() {
+                Ok(mut callback) => callback(),
+                Err(_) => log::debug!("Skipping re-entrant interval callback")
+            }
+        }).copy();
+        let timer = unsafe {
+            NSTimer::scheduledTimerWithTimeInterval_repeats_block(interval.as_secs_f64(), true, &block)
+        };
+        self.timers.borrow_mut().push(timer);
+    }
 
+    pub fn set_visible(&self, id: &MenuItemId, visible: bool) {
+        let Some((path, index)) = id.split() else {
+            log::warn!("Invalid menu item id: {id:?}");
+            return;
+        };
+        unsafe {
+            let Some((menu, index)) = self
+                .submenu(path)
+                .and_then(|menu| native_index(&menu, index).map(|index| (menu, index))) else {
+                log::warn!("Invalid menu item id: {id:?}");
+                return;
+            };
+            // The alternate directly follows its primary item
+            let alternate = menu
+                .itemAtIndex(index + 1)
+                .filter(|item| item.isAlternate());
+            for item in [menu.itemAtIndex(index), alternate].into_iter().flatten() {
+                item.setHidden(!visible);
+            }
+        }
+    }
+
+    pub fn native_id(&self, id: &MenuItemId) -> Option<u32> {
+        let (path, index) = id.split()?;
+        unsafe {
+            let menu = self.submenu(path)?;
+            let item = menu.itemAtIndex(native_index(&menu, index)?)?;
+            // Only buttons are tagged with the index of their signal
+            item.action()?;
+            u32::try_from(item.tag()).ok()
+        }
+    }
+
+    unsafe fn submenu(&self, path: &[usize]) -> Option<Id<NSMenu>> {
+        path
+            .iter()
+            .try_fold(self.status_item.menu()?, |menu, i| menu
+                .itemAtIndex(native_index(&menu, *i)?)?
+                .submenu())
+    }
+
+    pub fn move_item(&self, id: &MenuItemId, new_index: usize) {
+        let Some((path, index)) = id.split() else {
+            log::warn!("Invalid menu item id: {id:?}");
+            return;
+        };
+        unsafe {
+            let Some(menu) = self.submenu(path) else {
+                log::warn!("Invalid menu item id: {id:?}");
+                return;
+            };
+            // Alternates are separate native items, so the indices have to skip over them
+            let (Some(from), Some(_)) = (native_index(&menu, index), native_index(&menu, new_index)) else {
+                log::warn!("Can not move item {index} to {new_index}");
+                return;
+            };
+            let Some(item) = menu.itemAtIndex(from) else {
+                return;
+            };
+            // The alternate directly follows its primary item and has to stay there
+            let alternate = menu
+                .itemAtIndex(from + 1)
+                .filter(|item| item.isAlternate());
+            if alternate.is_some() {
+                menu.removeItemAtIndex(from + 1);
+            }
+            menu.removeItemAtIndex(from);
+            let to = native_index(&menu, new_index).unwrap_or_else(|| menu.numberOfItems());
+            menu.insertItem_atIndex(&item, to);
+            if let Some(alternate) = alternate {
+                menu.insertItem_atIndex(&alternate, to + 1);
+            }
+        }
+    }
+
+    pub unsafe fn set_native_menu(&self, menu: *mut NSMenu) {
+        let Some(menu) = Id::retain(menu) else {
+            log::warn!("Tried to set a null menu");
+            return;
+        };
+        self.status_item.setMenu(Some(&menu));
+        self.signal_map.set(None);
+    }
+
+    pub fn set_dock_badge(&self, badge: Option<String>) {
+        let badge = badge.map(|badge| NSString::from_str(&badge));
+        unsafe {
+            NSApplication::sharedApplication()
+                .dockTile()
+                .setBadgeLabel(badge.as_deref());
+        }
+    }
+
+    pub fn request_user_attention(&self, critical: bool) {
+        let kind = match critical {
+            true => NSCriticalRequest,
+            false => NSInformationalRequest
+        };
+        unsafe { NSApplication::sharedApplication().requestUserAttention(kind) };
     }
 
     pub fn set_menu(&self, menu: Option<Menu<T>>) {
@@ -107,13 +368,38 @@ impl<T> NativeTrayIcon<T> {
 
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
-pub struct NativeIcon;
-
-impl NativeIcon {
-    pub fn from_rgba(_rgba: Vec<u8>, _width: u32, _height: u32) -> TrayResult<Self> {
-        Ok(NativeIcon)
+pub fn wake_event_loop() {
+    unsafe {
+        let app = NSApplication::sharedApplication();
+        app.stop(None);
+        // stop only takes effect after the next event has been processed, which may never come
+        // if the exit was requested from another thread
+        let event = NSEvent::otherEventWithType_location_modifierFlags_timestamp_windowNumber_context_subtype_data1_data2(
+            NSEventTypeApplicationDefined,
+            NSPoint::new(0.0, 0.0),
+            0,
+            0.0,
+            0,
+            None,
+            0,
+            0,
+            0
+        );
+        match event {
+            Some(event) => app.postEvent_atStart(&event, true),
+            None => log::native_error!("Failed to create wake up event")
+        }
     }
 }
 
+unsafe fn observe_workspace(name: &NSString, callback: &Id<SystemTrayCallback>, tag: NSInteger) -> Id<NSObject> {
+    let callback = callback.clone();
+    let block = ConcreteBlock::new(move |_: NonNull<NSNotification>| callback.call(tag)).copy();
+    NSWorkspace::sharedWorkspace()
+        .notificationCenter()
+        .addObserverForName_object_queue_usingBlock(Some(name), None, None, &block)
+}
+
+pub use icon::NativeIcon;
+
 pub type PlatformError = ();
\ No newline at end of file
@@ -4,12 +4,38 @@ mod windows;
 mod linux;
 #[cfg(target_os = "macos")]
 mod macos;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+mod unix;
+#[cfg(not(any(target_os = "windows", target_os = "linux", target_os = "macos")))]
+mod unsupported;
 
 #[cfg(target_os = "windows")]
-pub use windows::{NativeTrayIcon, NativeIcon, PlatformError};
+pub use windows::{Acknowledgement, BACKEND, NativeTrayIcon, NativeIcon, PlatformError, wake_event_loop};
+#[cfg(all(target_os = "windows", feature = "remote"))]
+pub use windows::{RemoteTransport, TrayWaker};
+#[cfg(all(target_os = "windows", feature = "autostart"))]
+pub use windows::autostart;
+#[cfg(all(target_os = "windows", feature = "single-instance"))]
+pub use windows::instance;
 
 #[cfg(target_os = "linux")]
-pub use linux::{NativeTrayIcon, NativeIcon, PlatformError};
+pub use linux::{Acknowledgement, BACKEND, DBusMenuInterface, NativeTrayIcon, NativeIcon, PlatformError, png_dimensions, wake_event_loop};
+#[cfg(all(target_os = "linux", feature = "remote"))]
+pub use linux::{DBUS_INTERFACE, DBUS_PATH, RemoteTransport, TrayWaker};
+#[cfg(all(target_os = "linux", feature = "autostart"))]
+pub use linux::autostart;
+
+#[cfg(all(any(target_os = "linux", target_os = "macos"), feature = "single-instance"))]
+pub use unix::instance;
 
 #[cfg(target_os = "macos")]
-pub use macos::{NativeTrayIcon, NativeIcon, PlatformError};
\ No newline at end of file
+pub use macos::{Acknowledgement, BACKEND, NativeTrayIcon, NativeIcon, PlatformError, wake_event_loop};
+#[cfg(all(target_os = "macos", feature = "autostart"))]
+pub use macos::autostart;
+
+#[cfg(not(any(target_os = "windows", target_os = "linux", target_os = "macos")))]
+pub use unsupported::{Acknowledgement, BACKEND, NativeTrayIcon, NativeIcon, PlatformError, wake_event_loop};
+#[cfg(all(not(any(target_os = "windows", target_os = "linux", target_os = "macos")), feature = "autostart"))]
+pub use unsupported::autostart;
+#[cfg(all(not(any(target_os = "windows", target_os = "linux", target_os = "macos")), feature = "single-instance"))]
+pub use unsupported::instance;
@@ -0,0 +1,116 @@
+use std::fs::{DirBuilder, File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use crate::ensure;
+use crate::error::{TrayError, TrayResult};
+use crate::log;
+
+/// Held by the primary instance. The socket receives the activations of other instances.
+pub struct InstanceLock {
+    path: PathBuf,
+    lock_path: PathBuf,
+    stop: Arc<AtomicBool>
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        // Keeps a starting instance from binding a new socket that would then be removed here
+        let _lock = lock_file(&self.lock_path)
+            .map_err(|err| log::warn!("{err}"));
+        self.stop.store(true, Ordering::Release);
+        // Wakes up the thread that waits for the next instance
+        let _ = UnixStream::connect(&self.path);
+        std::fs::remove_file(&self.path)
+            .unwrap_or_else(|err| log::warn!("Failed to remove {}: {err}", self.path.display()));
+    }
+}
+
+/// The directory of the socket, which must only be accessible by the current user
+fn runtime_dir() -> TrayResult<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_RUNTIME_DIR") {
+        return Ok(PathBuf::from(dir));
+    }
+    let uid = unsafe { libc::getuid() };
+    let dir = std::env::temp_dir().join(format!("betrayer-{uid}"));
+    match DirBuilder::new().mode(0o700).create(&dir) {
+        Err(err) if err.kind() != ErrorKind::AlreadyExists =>
+            return Err(TrayError::custom(format!("Failed to create {}: {err}", dir.display()))),
+        _ => {}
+    }
+    // The temporary directory is shared on Linux, so another user could have created the directory first
+    let metadata = std::fs::symlink_metadata(&dir)
+        .map_err(|err| TrayError::custom(format!("Failed to inspect {}: {err}", dir.display())))?;
+    ensure!(metadata.is_dir() && metadata.uid() == uid && metadata.mode() & 0o077 == 0,
+        TrayError::custom(format!("{} is not private to the current user", dir.display())));
+    Ok(dir)
+}
+
+/// Takes an exclusive lock on the file, which is released when the returned file is closed
+fn lock_file(path: &Path) -> TrayResult<File> {
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .mode(0o600)
+        .open(path)
+        .map_err(|err| TrayError::custom(format!("Failed to open {}: {err}", path.display())))?;
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+        return Err(TrayError::custom(format!("Failed to lock {}: {}", path.display(), std::io::Error::last_os_error())));
+    }
+    Ok(file)
+}
+
+pub fn acquire(name: &str, activations: Sender<()>) -> TrayResult<Option<InstanceLock>> {
+    let valid = !name.is_empty() && !name.contains('/') && !name.contains(char::is_control);
+    ensure!(valid, TrayError::custom(format!("Invalid instance name: {name:?}")));
+    let dir = runtime_dir()?;
+    let path = dir.join(format!("{name}.instance"));
+    let lock_path = dir.join(format!("{name}.lock"));
+    // Starting instances take turns, so that only one of them can replace a stale socket
+    let _lock = lock_file(&lock_path)?;
+    if let Ok(mut stream) = UnixStream::connect(&path) {
+        stream
+            .write_all(b"activate\n")
+            .map_err(|err| TrayError::custom(format!("Failed to activate the running instance: {err}")))?;
+        return Ok(None);
+    }
+    let listener = match UnixListener::bind(&path) {
+        // Nobody is listening, so the socket was left behind by an instance that didn't exit cleanly
+        Err(err) if err.kind() == ErrorKind::AddrInUse => {
+            log::debug!("Removing stale socket {}", path.display());
+            let _ = std::fs::remove_file(&path);
+            UnixListener::bind(&path)
+        }
+        result => result
+    }.map_err(|err| TrayError::custom(format!("Failed to bind {}: {err}", path.display())))?;
+    let stop = Arc::new(AtomicBool::new(false));
+    std::thread::Builder::new()
+        .name(String::from("instance listener"))
+        .spawn({
+            let stop = stop.clone();
+            move || for stream in listener.incoming() {
+                if stop.load(Ordering::Acquire) {
+                    return;
+                }
+                match stream {
+                    Ok(mut stream) => {
+                        // The other instance closes the connection after writing its request
+                        let _ = stream.read_to_end(&mut Vec::new());
+                        if activations.send(()).is_err() {
+                            return;
+                        }
+                    }
+                    Err(err) => log::warn!("Failed to accept another instance: {err}")
+                }
+            }
+        })
+        .map_err(|err| TrayError::custom(format!("Failed to spawn thread: {err}")))?;
+    // Created last, as dropping it takes the lock that is still held here
+    Ok(Some(InstanceLock { path, lock_path, stop }))
+}
@@ -0,0 +1,4 @@
+//! Code that is shared between the *Linux* and *Mac* backends
+
+#[cfg(feature = "single-instance")]
+pub mod instance;
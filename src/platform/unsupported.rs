@@ -0,0 +1,107 @@
+use std::marker::PhantomData;
+use std::time::Duration;
+use crate::error::{ErrorSource, TrayError, TrayResult};
+use crate::{Backend, Icon, Menu, MenuItemId, Notification, TrayEvent, TrayIconBuilder};
+use crate::control_flow::ExitSignal;
+
+// Allows downstream applications to compile on targets without a system tray.
+// Creating a tray icon always fails at runtime with ErrorSource::NotSupported.
+
+pub const BACKEND: Backend = Backend::Unsupported;
+
+pub type IntervalCallback = Box<dyn FnMut() + 'static>;
+
+pub type Acknowledgement = std::future::Ready<()>;
+
+pub struct NativeTrayIcon<T> {
+    _signal_type: PhantomData<T>
+}
+
+impl<T: Clone + Send + 'static> NativeTrayIcon<T> {
+    pub fn new<F>(_builder: TrayIconBuilder<T>, _callback: F) -> TrayResult<Self>
+        where F: FnMut(TrayEvent<T>) + Send + 'static
+    {
+        Err(TrayError::from(ErrorSource::NotSupported))
+    }
+}
+
+impl<T> NativeTrayIcon<T> {
+    pub fn set_tooltip(&self, _tooltip: Option<String>) {}
+
+    pub fn set_title(&self, _title: Option<String>) {}
+
+    pub fn set_icon(&self, _icon: Option<Icon>) {}
+
+    pub fn set_themed_icon(&self, _name: String, _fallback: Option<Icon>) {}
+
+    pub fn set_menu(&self, _menu: Option<Menu<T>>) {}
+
+    pub fn move_item(&self, _id: &MenuItemId, _new_index: usize) {}
+
+    pub fn set_visible(&self, _id: &MenuItemId, _visible: bool) {}
+
+    pub fn native_id(&self, _id: &MenuItemId) -> Option<u32> {
+        None
+    }
+
+    pub fn show_notification(&self, _notification: Notification) {}
+
+    pub fn is_do_not_disturb(&self) -> TrayResult<bool> {
+        Err(TrayError::from(ErrorSource::NotSupported))
+    }
+
+    /// Updates are applied synchronously, so they are always acknowledged
+    pub fn acknowledge(&self) -> Acknowledgement {
+        std::future::ready(())
+    }
+
+    pub fn id(&self) -> String {
+        String::new()
+    }
+
+    pub fn run(&self, _exit: &ExitSignal) {}
+
+    pub fn set_interval(&self, _interval: Duration, _callback: IntervalCallback) {}
+}
+
+pub fn wake_event_loop() {}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct NativeIcon;
+
+impl NativeIcon {
+    pub fn from_rgba(_rgba: Vec<u8>, _width: u32, _height: u32) -> TrayResult<Self> {
+        Ok(NativeIcon)
+    }
+}
+
+pub type PlatformError = ();
+
+#[cfg(feature = "autostart")]
+pub mod autostart {
+    use crate::error::{ErrorSource, TrayError, TrayResult};
+
+    pub fn enable(_name: &str, _command: &[String]) -> TrayResult<()> {
+        Err(TrayError::from(ErrorSource::NotSupported))
+    }
+
+    pub fn disable(_name: &str) -> TrayResult<()> {
+        Err(TrayError::from(ErrorSource::NotSupported))
+    }
+
+    pub fn is_enabled(_name: &str) -> TrayResult<bool> {
+        Err(TrayError::from(ErrorSource::NotSupported))
+    }
+}
+
+#[cfg(feature = "single-instance")]
+pub mod instance {
+    use std::sync::mpsc::Sender;
+    use crate::error::{ErrorSource, TrayError, TrayResult};
+
+    pub struct InstanceLock;
+
+    pub fn acquire(_name: &str, _activations: Sender<()>) -> TrayResult<Option<InstanceLock>> {
+        Err(TrayError::from(ErrorSource::NotSupported))
+    }
+}
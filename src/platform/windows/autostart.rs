@@ -0,0 +1,114 @@
+use windows::core::{PCWSTR, w};
+use windows::Win32::Foundation::{ERROR_FILE_NOT_FOUND, ERROR_SUCCESS};
+use windows::Win32::System::Registry::{HKEY, HKEY_CURRENT_USER, KEY_QUERY_VALUE, KEY_SET_VALUE, REG_SAM_FLAGS, REG_SZ, RegCloseKey, RegDeleteValueW, RegOpenKeyExW, RegQueryValueExW, RegSetValueExW};
+use crate::error::{TrayError, TrayResult};
+use crate::platform::windows::encode_wide;
+use crate::log;
+
+const RUN_KEY: PCWSTR = w!(r"Software\Microsoft\Windows\CurrentVersion\Run");
+
+/// The `Run` key of the current user, which is closed when dropped
+struct RunKey(HKEY);
+
+impl RunKey {
+    fn open(access: REG_SAM_FLAGS) -> TrayResult<Self> {
+        let mut key = HKEY::default();
+        unsafe { RegOpenKeyExW(HKEY_CURRENT_USER, RUN_KEY, 0, access, &mut key).ok()? };
+        Ok(Self(key))
+    }
+}
+
+impl Drop for RunKey {
+    fn drop(&mut self) {
+        unsafe {
+            RegCloseKey(self.0)
+                .ok()
+                .unwrap_or_else(|err| log::native_error!("Failed to close registry key: {err}"));
+        }
+    }
+}
+
+/// Quotes an argument so that `CommandLineToArgvW` splits it back into the original
+fn quote_argument(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        return arg.to_string();
+    }
+    let mut quoted = String::from("\"");
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                // Backslashes are only special in front of a quote, where they have to be doubled and the quote escaped
+                quoted.extend(std::iter::repeat('\\').take(backslashes + 1));
+                backslashes = 0;
+            }
+            _ => backslashes = 0
+        }
+        quoted.push(c);
+    }
+    quoted.extend(std::iter::repeat('\\').take(backslashes));
+    quoted.push('"');
+    quoted
+}
+
+pub fn enable(name: &str, command: &[String]) -> TrayResult<()> {
+    let key = RunKey::open(KEY_SET_VALUE)?;
+    let command_line = command
+        .iter()
+        .map(|arg| quote_argument(arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let data: Vec<u8> = encode_wide(&command_line)
+        .into_iter()
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    let name = encode_wide(name);
+    unsafe { RegSetValueExW(key.0, PCWSTR(name.as_ptr()), 0, REG_SZ, Some(&data)).ok()? };
+    Ok(())
+}
+
+pub fn disable(name: &str) -> TrayResult<()> {
+    let key = RunKey::open(KEY_SET_VALUE)?;
+    let name = encode_wide(name);
+    match unsafe { RegDeleteValueW(key.0, PCWSTR(name.as_ptr())) } {
+        ERROR_FILE_NOT_FOUND => Ok(()),
+        result => Ok(result.ok()?)
+    }
+}
+
+pub fn is_enabled(name: &str) -> TrayResult<bool> {
+    let key = RunKey::open(KEY_QUERY_VALUE)?;
+    let name = encode_wide(name);
+    match unsafe { RegQueryValueExW(key.0, PCWSTR(name.as_ptr()), None, None, None, None) } {
+        ERROR_SUCCESS => Ok(true),
+        ERROR_FILE_NOT_FOUND => Ok(false),
+        result => result.ok().map(|_| true).map_err(TrayError::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_arguments_are_kept() {
+        assert_eq!(quote_argument("--minimized"), "--minimized");
+        assert_eq!(quote_argument(r"C:\Apps\app.exe"), r"C:\Apps\app.exe");
+    }
+
+    #[test]
+    fn empty_and_spaced_arguments_are_quoted() {
+        assert_eq!(quote_argument(""), r#""""#);
+        assert_eq!(quote_argument("My App"), r#""My App""#);
+    }
+
+    #[test]
+    fn backslashes_are_only_doubled_before_quotes() {
+        assert_eq!(quote_argument(r#"a"b"#), r#""a\"b""#);
+        assert_eq!(quote_argument(r#"a\\"b"#), r#""a\\\\\"b""#);
+        assert_eq!(quote_argument(r"C:\Program Files\"), r#""C:\Program Files\\""#);
+        assert_eq!(quote_argument(r"C:\Program Files\app.exe"), r#""C:\Program Files\app.exe""#);
+    }
+
+}
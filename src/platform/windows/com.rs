@@ -0,0 +1,18 @@
+use windows::Win32::System::Com::{CoInitializeEx, COINIT_APARTMENTTHREADED, CoUninitialize};
+
+/// Makes sure COM is initialized for the duration of a call without touching an existing initialization
+pub struct ComGuard(bool);
+
+impl ComGuard {
+    pub fn new() -> Self {
+        Self(unsafe { CoInitializeEx(None, COINIT_APARTMENTTHREADED).is_ok() })
+    }
+}
+
+impl Drop for ComGuard {
+    fn drop(&mut self) {
+        if self.0 {
+            unsafe { CoUninitialize() };
+        }
+    }
+}
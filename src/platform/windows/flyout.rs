@@ -0,0 +1,36 @@
+use windows::Win32::Foundation::{HWND, POINT, RECT, SIZE};
+use windows::Win32::UI::WindowsAndMessaging::{CalculatePopupWindowPosition, GetWindowRect, HWND_TOPMOST, IsWindow, SetWindowPos, ShowWindow, SW_HIDE, SWP_NOACTIVATE, SWP_NOSIZE, SWP_SHOWWINDOW};
+use crate::{ensure, MenuPlacement};
+use crate::error::{TrayError, TrayResult};
+use crate::platform::windows::menu::popup_placement;
+
+/// Moves `window` next to `anchor` without covering it and shows it without taking the focus
+pub fn show_flyout(window: HWND, anchor: RECT, placement: MenuPlacement) -> TrayResult<()> {
+    unsafe {
+        let mut bounds = RECT::default();
+        GetWindowRect(window, &mut bounds)?;
+        let size = SIZE {
+            cx: bounds.right - bounds.left,
+            cy: bounds.bottom - bounds.top,
+        };
+        let center = POINT {
+            x: anchor.left + (anchor.right - anchor.left) / 2,
+            y: anchor.top + (anchor.bottom - anchor.top) / 2,
+        };
+        // Flyouts follow the same rules as menus, so both open in the same direction
+        let (center, flags) = popup_placement(center, placement);
+        let mut position = RECT::default();
+        CalculatePopupWindowPosition(&center, &size, flags.0, Some(&anchor), &mut position)?;
+        SetWindowPos(window, HWND_TOPMOST, position.left, position.top, 0, 0, SWP_NOSIZE | SWP_NOACTIVATE | SWP_SHOWWINDOW)?;
+    }
+    Ok(())
+}
+
+pub fn hide_flyout(window: HWND) -> TrayResult<()> {
+    unsafe {
+        ensure!(IsWindow(window).as_bool(), TrayError::custom("Invalid flyout window"));
+        // Returns whether the window was visible before, which isn't an error
+        ShowWindow(window, SW_HIDE);
+    }
+    Ok(())
+}
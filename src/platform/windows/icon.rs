@@ -1,17 +1,25 @@
 use std::fmt::{Debug, Formatter};
+use std::mem::size_of;
+use std::ptr::null_mut;
 use std::sync::Arc;
 use windows::core::PCWSTR;
-use windows::Win32::UI::WindowsAndMessaging::{CreateIcon, DestroyIcon, HICON, IMAGE_ICON, LoadImageW, LR_DEFAULTSIZE};
+use windows::Win32::Graphics::Gdi::{BI_RGB, BITMAPINFO, BITMAPINFOHEADER, CreateCompatibleDC, CreateDIBSection, DeleteDC, DeleteObject, DIB_RGB_COLORS, HBITMAP, SelectObject};
+use windows::Win32::UI::WindowsAndMessaging::{CopyIcon, CreateIcon, DestroyIcon, DI_NORMAL, DrawIconEx, HICON, IMAGE_ICON, LoadImageW, LR_DEFAULTSIZE};
 use crate::error::TrayResult;
 use crate::platform::windows::get_instance_handle;
+use crate::utils::resize_rgba;
+use crate::log;
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct NativeIcon {
-    handle: Arc<NativeIconHandle>
+    handle: Arc<NativeIconHandle>,
+    // Kept around to rasterize the icon at other sizes, resource icons are drawn by the system instead
+    pixels: Option<(Arc<[u8]>, u32, u32)>
 }
 
 impl NativeIcon {
     pub fn from_rgba(mut rgba: Vec<u8>, width: u32, height: u32) -> TrayResult<Self> {
+        let pixels = (Arc::from(rgba.as_slice()), width, height);
         let mut mask = Vec::with_capacity(rgba.len() / 4);
         let bgra = {
             rgba
@@ -37,6 +45,7 @@ impl NativeIcon {
         };
         Ok(Self {
             handle: Arc::new(NativeIconHandle(handle)),
+            pixels: Some(pixels)
         })
     }
 
@@ -57,6 +66,17 @@ impl NativeIcon {
         };
         Ok(Self {
             handle: Arc::new(NativeIconHandle(handle)),
+            pixels: None
+        })
+    }
+
+    /// Copies an icon that is owned by someone else
+    pub fn from_hicon(hicon: isize) -> TrayResult<Self> {
+        log::trace!("Creating new native icon");
+        let handle = unsafe { CopyIcon(HICON(hicon))? };
+        Ok(Self {
+            handle: Arc::new(NativeIconHandle(handle)),
+            pixels: None
         })
     }
 
@@ -64,6 +84,49 @@ impl NativeIcon {
         self.handle.0
     }
 
+    /// Rasterizes the icon into a 32 bit bitmap with the given size in pixels, e.g. for menu items
+    pub fn to_bitmap(&self, width: u32, height: u32) -> TrayResult<Bitmap> {
+        log::trace!("Creating new native bitmap");
+        let info = BITMAPINFO {
+            bmiHeader: BITMAPINFOHEADER {
+                biSize: size_of::<BITMAPINFOHEADER>() as u32,
+                biWidth: width as i32,
+                // Negative heights create top-down bitmaps
+                biHeight: -(height as i32),
+                biPlanes: 1,
+                biBitCount: 32,
+                biCompression: BI_RGB.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut bits = null_mut();
+        let bitmap = Bitmap(unsafe { CreateDIBSection(None, &info, DIB_RGB_COLORS, &mut bits, None, 0)? });
+        match &self.pixels {
+            Some((rgba, source_width, source_height)) => {
+                let resized = resize_rgba(rgba, *source_width, *source_height, width, height);
+                let target = unsafe { std::slice::from_raw_parts_mut(bits as *mut u8, resized.len()) };
+                // Bitmaps with an alpha channel use premultiplied BGRA
+                for (target, pixel) in target.chunks_exact_mut(4).zip(resized.chunks_exact(4)) {
+                    let alpha = pixel[3] as u32;
+                    target[0] = (pixel[2] as u32 * alpha / 255) as u8;
+                    target[1] = (pixel[1] as u32 * alpha / 255) as u8;
+                    target[2] = (pixel[0] as u32 * alpha / 255) as u8;
+                    target[3] = pixel[3];
+                }
+            }
+            None => unsafe {
+                let hdc = CreateCompatibleDC(None);
+                let previous = SelectObject(hdc, bitmap.0);
+                let result = DrawIconEx(hdc, 0, 0, self.handle(), width as i32, height as i32, 0, None, DI_NORMAL);
+                SelectObject(hdc, previous);
+                DeleteDC(hdc);
+                result?;
+            }
+        }
+        Ok(bitmap)
+    }
+
 }
 
 #[derive(Eq, PartialEq)]
@@ -74,7 +137,7 @@ impl Drop for NativeIconHandle {
         log::trace!("Dropping native icon");
         unsafe {
             DestroyIcon(self.0)
-                .unwrap_or_else(|err| log::warn!("Failed to destroy native icon: {err}"));
+                .unwrap_or_else(|err| log::native_error!("Failed to destroy native icon: {err}"));
         }
     }
 }
@@ -83,4 +146,21 @@ impl Debug for NativeIconHandle {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         self.0.fmt(f)
     }
-}
\ No newline at end of file
+}
+/// A GDI bitmap that is deleted when dropped
+pub struct Bitmap(HBITMAP);
+
+impl Bitmap {
+    pub fn handle(&self) -> HBITMAP {
+        self.0
+    }
+}
+
+impl Drop for Bitmap {
+    fn drop(&mut self) {
+        log::trace!("Dropping native bitmap");
+        unsafe {
+            DeleteObject(self.0);
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Read, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+use windows::core::PCWSTR;
+use windows::Win32::Foundation::{CloseHandle, ERROR_ALREADY_EXISTS, GetLastError, HANDLE};
+use windows::Win32::Storage::FileSystem::PIPE_ACCESS_INBOUND;
+use windows::Win32::System::Threading::CreateMutexW;
+use windows::Win32::UI::WindowsAndMessaging::{AllowSetForegroundWindow, ASFW_ANY};
+use crate::error::{TrayError, TrayResult};
+use crate::platform::windows::{encode_wide, session_id};
+use crate::platform::windows::pipe::{accept, create_pipe, disconnect};
+use crate::log;
+
+const CONNECT_ATTEMPTS: u32 = 20;
+const RETRY_DELAY: Duration = Duration::from_millis(50);
+
+/// Held by the primary instance. The mutex tells other instances that it's running and the pipe receives their activations.
+pub struct InstanceLock {
+    mutex: HANDLE,
+    pipe: String,
+    stop: Arc<AtomicBool>
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        // Wakes up the thread that waits for the next instance
+        let _ = OpenOptions::new().write(true).open(&self.pipe);
+        unsafe {
+            CloseHandle(self.mutex)
+                .unwrap_or_else(|err| log::native_error!("Failed to close the instance mutex: {err}"));
+        }
+    }
+}
+
+pub fn acquire(name: &str, activations: Sender<()>) -> TrayResult<Option<InstanceLock>> {
+    // Pipes are visible across sessions, unlike the local mutex
+    let pipe = format!(r"\\.\pipe\{name}.{}.instance", session_id()?);
+    // Local objects are per session, so every logged in user can run their own instance
+    let mutex_name = encode_wide(&format!(r"Local\{name}"));
+    let mutex = unsafe { CreateMutexW(None, false, PCWSTR(mutex_name.as_ptr()))? };
+    if unsafe { GetLastError() } == ERROR_ALREADY_EXISTS {
+        unsafe {
+            CloseHandle(mutex)
+                .unwrap_or_else(|err| log::native_error!("Failed to close the instance mutex: {err}"));
+        }
+        activate(&pipe)?;
+        return Ok(None);
+    }
+    let stop = Arc::new(AtomicBool::new(false));
+    let lock = InstanceLock { mutex, pipe, stop: stop.clone() };
+    let server = create_pipe(&lock.pipe, PIPE_ACCESS_INBOUND)?;
+    std::thread::Builder::new()
+        .name(String::from("instance listener"))
+        .spawn(move || loop {
+            if let Err(err) = accept(&server) {
+                log::native_error!("Failed to wait for another instance: {err}");
+                return;
+            }
+            if stop.load(Ordering::Acquire) {
+                return;
+            }
+            // The other instance closes the pipe after writing its request
+            let _ = (&server).read_to_end(&mut Vec::new());
+            if activations.send(()).is_err() || !disconnect(&server) {
+                return;
+            }
+        })
+        .map_err(|err| TrayError::custom(format!("Failed to spawn thread: {err}")))?;
+    Ok(Some(lock))
+}
+
+/// Asks the primary instance to activate itself
+fn activate(pipe: &str) -> TrayResult<()> {
+    // The primary instance is usually expected to bring a window to the front, which it's only allowed to with our permission
+    unsafe {
+        AllowSetForegroundWindow(ASFW_ANY)
+            .unwrap_or_else(|err| log::debug!("Failed to allow the other instance to take the focus: {err}"));
+    }
+    for _ in 0..CONNECT_ATTEMPTS {
+        match OpenOptions::new().write(true).open(pipe) {
+            Ok(mut file) => return file
+                .write_all(b"activate\n")
+                .map_err(|err| TrayError::custom(format!("Failed to activate the running instance: {err}"))),
+            // The pipe doesn't exist yet or is busy with another instance
+            Err(err) if matches!(err.kind(), ErrorKind::NotFound) || err.raw_os_error() == Some(231) => std::thread::sleep(RETRY_DELAY),
+            Err(err) => return Err(TrayError::custom(format!("Failed to reach the running instance: {err}")))
+        }
+    }
+    Err(TrayError::custom("The running instance doesn't respond"))
+}
@@ -1,30 +1,372 @@
 use std::any::Any;
-use windows::core::PCWSTR;
-use windows::Win32::Foundation::{HWND, POINT};
-use windows::Win32::UI::WindowsAndMessaging::{AppendMenuW, CreatePopupMenu, DestroyMenu, GetCursorPos, HMENU, MF_CHECKED, MF_POPUP, MF_SEPARATOR, MF_STRING, SetForegroundWindow, TPM_BOTTOMALIGN, TPM_LEFTALIGN, TrackPopupMenu};
-use crate::{Menu, MenuItem};
+use std::cell::{Ref, RefCell};
+use std::collections::HashMap;
+use std::mem::size_of;
+use windows::core::{PCWSTR, PWSTR};
+use windows::Win32::Foundation::{COLORREF, HWND, POINT, RECT, SIZE, TRUE};
+use windows::Win32::Graphics::Gdi::{COLOR_HIGHLIGHT, COLOR_HIGHLIGHTTEXT, COLOR_MENU, COLOR_MENUTEXT, CreateFontIndirectW, DeleteObject, DFC_MENU, DFCS_MENUCHECK, DrawFrameControl, DrawTextW, DT_LEFT, DT_SINGLELINE, DT_VCENTER, FillRect, FW_BOLD, GetDC, GetSysColor, GetSysColorBrush, GetMonitorInfoW, GetTextExtentPoint32W, HBITMAP, HFONT, MONITOR_DEFAULTTONEAREST, MonitorFromPoint, MONITORINFO, ReleaseDC, SelectObject, SetBkMode, SetTextColor, TRANSPARENT};
+use windows::Win32::UI::Controls::{DRAWITEMSTRUCT, MEASUREITEMSTRUCT, ODS_CHECKED, ODS_SELECTED, ODT_MENU};
+use windows::Win32::UI::WindowsAndMessaging::{AppendMenuW, CheckMenuItem, CreatePopupMenu, DestroyMenu, GetCursorPos, GetMenuItemInfoW, GetSystemMetrics, HMENU, InsertMenuItemW, MENUITEMINFOW, MENU_ITEM_FLAGS, MF_BYPOSITION, MF_CHECKED, MF_OWNERDRAW, MF_BYCOMMAND, MF_POPUP, MF_SEPARATOR, MF_STRING, MF_UNCHECKED, MFS_CHECKED, MFT_OWNERDRAW, MIIM_CHECKMARKS, MIIM_DATA, MIIM_FTYPE, MIIM_ID, MIIM_STATE, MIIM_STRING, MIIM_SUBMENU, NONCLIENTMETRICSW, RemoveMenu, SetForegroundWindow, SetMenuItemBitmaps, SetMenuItemInfoW, SM_CXMENUCHECK, SM_CYMENU, SM_CYMENUCHECK, SPI_GETNONCLIENTMETRICS, SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS, SystemParametersInfoW, TPM_BOTTOMALIGN, TPM_LEFTALIGN, TPM_RETURNCMD, TPM_RIGHTALIGN, TPM_TOPALIGN, TPM_WORKAREA, TPMPARAMS, TrackPopupMenuEx, TRACK_POPUP_MENU_FLAGS};
+use crate::{ensure, HorizontalGravity, Icon, ItemStyle, Menu, MenuItem, MenuItemId, MenuPlacement, StateImages, VerticalGravity};
 use crate::error::{TrayError, TrayResult};
 use crate::platform::windows::encode_wide;
+use crate::platform::windows::icon::Bitmap;
+use crate::log;
+use crate::metrics::{self, Counter};
 
 pub struct NativeMenu {
     hmenu: HMENU,
-    signals_map: Box<dyn SignalMap>
+    // Menus provided by the application are destroyed by the application
+    owned: bool,
+    layout: RefCell<Vec<LayoutNode>>,
+    // The native items only hold pointers to these, so they have to stay at the same address
+    #[allow(clippy::vec_box)]
+    _styled_labels: Vec<Box<StyledLabel>>,
+    // The menu items show these bitmaps without taking ownership of them
+    _bitmaps: Vec<Bitmap>,
+    signals_map: RefCell<Box<dyn SignalMap>>
+}
+
+/// The item data of owner-drawn items, which are used to render styled labels
+struct StyledLabel {
+    text: Vec<u16>,
+    style: ItemStyle
+}
+
+/// Mirrors the logical structure of the menu, as Win32 menus have no concept of hidden items.
+/// Hidden items are removed from the native menu and reinserted when they become visible again.
+struct LayoutNode {
+    command: Option<u16>,
+    submenu: Option<HMENU>,
+    hidden: Option<DetachedItem>,
+    source: ItemSource,
+    children: Vec<LayoutNode>
+}
+
+/// The parts of a [MenuItem] that a node was built from, to decide whether a new menu can be applied in place
+struct ItemSource {
+    kind: ItemKind,
+    label: String,
+    checked: bool,
+    style: ItemStyle,
+    state_images: StateImages
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum ItemKind {
+    Separator,
+    Button,
+    CheckButton,
+    Menu
+}
+
+impl LayoutNode {
+    fn is_visible(&self) -> bool {
+        self.hidden.is_none()
+    }
+
+    /// Whether the node can be turned into `item` by changing its label, check state and visibility
+    fn accepts<T>(&self, item: &MenuItem<T>) -> bool {
+        let source = &self.source;
+        // The size of owner-drawn items is only measured once, so their label can't change
+        let keeps_label = |name: &str, style: ItemStyle| source.style == style && (style == ItemStyle::Normal || source.label == name);
+        match item {
+            MenuItem::Separator => source.kind == ItemKind::Separator,
+            MenuItem::Button { name, style, .. } => source.kind == ItemKind::Button && keeps_label(name, *style),
+            MenuItem::CheckButton { name, style, state_images, .. } => source.kind == ItemKind::CheckButton
+                && keeps_label(name, *style)
+                && source.state_images == *state_images,
+            MenuItem::Menu { children, .. } => source.kind == ItemKind::Menu && accepts_all(&self.children, children)
+        }
+    }
+
+    unsafe fn set_label(&mut self, hmenu: HMENU, position: u32, label: String) -> TrayResult<()> {
+        if self.source.label == label {
+            return Ok(());
+        }
+        let mut wide = encode_wide(&label);
+        match &mut self.hidden {
+            Some(item) => item.label = wide,
+            None => {
+                let info = MENUITEMINFOW {
+                    cbSize: size_of::<MENUITEMINFOW>() as u32,
+                    fMask: MIIM_STRING,
+                    dwTypeData: PWSTR(wide.as_mut_ptr()),
+                    ..Default::default()
+                };
+                SetMenuItemInfoW(hmenu, position, TRUE, &info)?;
+            }
+        }
+        self.source.label = label;
+        Ok(())
+    }
+
+    unsafe fn set_checked(&mut self, hmenu: HMENU, position: u32, checked: bool) -> TrayResult<()> {
+        if self.source.checked == checked {
+            return Ok(());
+        }
+        match &mut self.hidden {
+            Some(item) if checked => item.info.fState |= MFS_CHECKED,
+            Some(item) => item.info.fState &= !MFS_CHECKED,
+            None => {
+                let flags = checked
+                    .then_some(MF_CHECKED)
+                    .unwrap_or(MF_UNCHECKED);
+                // Returns the previous state or -1 if the item doesn't exist
+                ensure!(CheckMenuItem(hmenu, position, (MF_BYPOSITION | flags).0) != u32::MAX, TrayError::custom("Failed to check menu item"));
+            }
+        }
+        self.source.checked = checked;
+        Ok(())
+    }
+}
+
+fn accepts_all<T>(layout: &[LayoutNode], items: &[MenuItem<T>]) -> bool {
+    layout.len() == items.len() && layout
+        .iter()
+        .zip(items)
+        .all(|(node, item)| node.accepts(item))
+}
+
+/// Changes the nodes into `items`, which must have been accepted by [accepts_all]
+unsafe fn update_all<T>(hmenu: HMENU, layout: &mut Vec<LayoutNode>, items: Vec<MenuItem<T>>, signals: &mut Vec<T>) -> TrayResult<()> {
+    for (index, item) in items.into_iter().enumerate() {
+        let visible = item.is_visible();
+        let position = native_position(layout, index);
+        let node = &mut layout[index];
+        match item {
+            MenuItem::Separator => {}
+            MenuItem::Button { name, signal, .. } => {
+                node.set_label(hmenu, position, name)?;
+                signals.push(signal);
+            }
+            MenuItem::CheckButton { name, signal, checked, .. } => {
+                node.set_label(hmenu, position, name)?;
+                node.set_checked(hmenu, position, checked)?;
+                signals.push(signal);
+            }
+            MenuItem::Menu { name, children, .. } => {
+                node.set_label(hmenu, position, name)?;
+                let submenu = node.submenu
+                    .ok_or_else(|| TrayError::custom("Menu item has no submenu"))?;
+                update_all(submenu, &mut node.children, children, signals)?;
+            }
+        }
+        set_node_visible(hmenu, layout, index, visible)?;
+    }
+    Ok(())
+}
+
+fn set_node_visible(hmenu: HMENU, siblings: &mut [LayoutNode], index: usize, visible: bool) -> TrayResult<()> {
+    if siblings[index].is_visible() == visible {
+        return Ok(());
+    }
+    let position = native_position(siblings, index);
+    let node = &mut siblings[index];
+    match node.hidden.take() {
+        Some(item) => unsafe { item.insert(hmenu, position)? },
+        None => node.hidden = Some(unsafe { DetachedItem::remove(hmenu, position)? })
+    }
+    Ok(())
+}
+
+/// A native menu item that has been removed from its menu
+struct DetachedItem {
+    info: MENUITEMINFOW,
+    label: Vec<u16>
+}
+
+impl DetachedItem {
+
+    unsafe fn remove(hmenu: HMENU, position: u32) -> TrayResult<Self> {
+        let mut info = MENUITEMINFOW {
+            cbSize: size_of::<MENUITEMINFOW>() as u32,
+            fMask: MIIM_FTYPE | MIIM_STRING,
+            ..Default::default()
+        };
+        GetMenuItemInfoW(hmenu, position, TRUE, &mut info)?;
+        let mut label = vec![0u16; info.cch as usize + 1];
+        info.fMask = MIIM_FTYPE | MIIM_STATE | MIIM_ID | MIIM_SUBMENU | MIIM_DATA | MIIM_CHECKMARKS;
+        // Owner-drawn items have no text, their label lives in the item data
+        if (info.fType & MFT_OWNERDRAW).0 == 0 {
+            info.fMask |= MIIM_STRING;
+            info.dwTypeData = PWSTR(label.as_mut_ptr());
+            info.cch = label.len() as u32;
+        }
+        GetMenuItemInfoW(hmenu, position, TRUE, &mut info)?;
+        // RemoveMenu (unlike DeleteMenu) keeps the submenu alive so it can be reinserted
+        RemoveMenu(hmenu, position, MF_BYPOSITION)?;
+        Ok(Self { info, label })
+    }
+
+    unsafe fn insert(mut self, hmenu: HMENU, position: u32) -> TrayResult<()> {
+        if (self.info.fMask & MIIM_STRING).0 != 0 {
+            self.info.dwTypeData = PWSTR(self.label.as_mut_ptr());
+        }
+        InsertMenuItemW(hmenu, position, TRUE, &self.info)?;
+        Ok(())
+    }
+
+}
+
+/// The position of the item in the native menu, which doesn't contain the hidden items
+fn native_position(siblings: &[LayoutNode], index: usize) -> u32 {
+    siblings[..index]
+        .iter()
+        .filter(|node| node.is_visible())
+        .count() as u32
+}
+
+/// Keeps the anchor within the work area of its monitor and opens the menu in the requested direction,
+/// or away from the taskbar if there is none
+pub unsafe fn popup_placement(anchor: POINT, placement: MenuPlacement) -> (POINT, TRACK_POPUP_MENU_FLAGS) {
+    let monitor = MonitorFromPoint(anchor, MONITOR_DEFAULTTONEAREST);
+    let mut info = MONITORINFO {
+        cbSize: size_of::<MONITORINFO>() as u32,
+        ..Default::default()
+    };
+    if !GetMonitorInfoW(monitor, &mut info).as_bool() {
+        log::debug!("Failed to get the monitor of the menu");
+        info.rcWork = RECT { left: i32::MIN, top: i32::MIN, right: i32::MAX, bottom: i32::MAX };
+        info.rcMonitor = info.rcWork;
+    }
+    // The taskbar sits at the edge where the work area is smaller than the monitor
+    let (work, screen) = (info.rcWork, info.rcMonitor);
+    let vertical = match placement.vertical {
+        VerticalGravity::Automatic if work.top > screen.top => TPM_TOPALIGN,
+        VerticalGravity::Automatic => TPM_BOTTOMALIGN,
+        VerticalGravity::Up => TPM_BOTTOMALIGN,
+        VerticalGravity::Down => TPM_TOPALIGN
+    };
+    let horizontal = match placement.horizontal {
+        HorizontalGravity::Automatic if work.right < screen.right => TPM_RIGHTALIGN,
+        HorizontalGravity::Automatic => TPM_LEFTALIGN,
+        HorizontalGravity::Left => TPM_RIGHTALIGN,
+        HorizontalGravity::Right => TPM_LEFTALIGN
+    };
+    let anchor = POINT {
+        x: anchor.x.clamp(work.left, work.right - 1),
+        y: anchor.y.clamp(work.top, work.bottom - 1),
+    };
+    (anchor, vertical | horizontal | TPM_WORKAREA)
 }
 
 impl NativeMenu {
 
-    pub fn show_on_cursor(&self, hwnd: HWND) -> TrayResult<()> {
+    /// Wraps a menu that was built by the application. Selecting the command ids in `signals` emits the associated signal.
+    pub fn from_native<T: 'static>(hmenu: HMENU, signals: HashMap<u16, T>) -> Self {
+        Self {
+            hmenu,
+            owned: false,
+            layout: RefCell::new(Vec::new()),
+            _styled_labels: Vec::new(),
+            _bitmaps: Vec::new(),
+            signals_map: RefCell::new(Box::new(signals)),
+        }
+    }
+
+    /// Whether [NativeMenu::update] can apply `menu` without rebuilding the native menu
+    ///
+    /// This is the case if only labels, check states and the visibility of items changed.
+    pub fn accepts<T>(&self, menu: &Menu<T>) -> bool {
+        self.owned && accepts_all(&self.layout.borrow(), &menu.items)
+    }
+
+    /// Applies a menu that was accepted by [NativeMenu::accepts] to the existing native menu
+    pub fn update<T: 'static>(&self, menu: Menu<T>) -> TrayResult<()> {
+        log::trace!("Updating native menu in place");
+        let mut signals = Vec::<T>::new();
+        unsafe { update_all(self.hmenu, &mut self.layout.borrow_mut(), menu.items, &mut signals)? };
+        // The items keep their command ids, as buttons are numbered in the same order
+        *self.signals_map.borrow_mut() = Box::new(signals);
+        Ok(())
+    }
+
+    /// Shows the menu and blocks until it is closed. Returns the id of the selected item, if any.
+    pub fn show_on_cursor(&self, hwnd: HWND, placement: MenuPlacement) -> TrayResult<Option<u16>> {
         let mut cursor = POINT::default();
+        unsafe { GetCursorPos(&mut cursor)? };
+        self.show_at(hwnd, cursor, None, placement)
+    }
+
+    /// Opens the menu next to the given rectangle without covering it
+    pub fn show_on_rect(&self, hwnd: HWND, rect: RECT, placement: MenuPlacement) -> TrayResult<Option<u16>> {
+        let center = POINT {
+            x: rect.left + (rect.right - rect.left) / 2,
+            y: rect.top + (rect.bottom - rect.top) / 2,
+        };
+        self.show_at(hwnd, center, Some(rect), placement)
+    }
+
+    fn show_at(&self, hwnd: HWND, anchor: POINT, exclude: Option<RECT>, placement: MenuPlacement) -> TrayResult<Option<u16>> {
+        let params = exclude.map(|rect| TPMPARAMS {
+            cbSize: size_of::<TPMPARAMS>() as u32,
+            rcExclude: rect,
+        });
         unsafe {
-            GetCursorPos(&mut cursor)?;
             SetForegroundWindow(hwnd).ok()?;
-            TrackPopupMenu(self.hmenu, TPM_BOTTOMALIGN | TPM_LEFTALIGN, cursor.x, cursor.y, 0, hwnd, None).ok()?;
+            metrics::count(Counter::MenuShows);
+            let (anchor, alignment) = popup_placement(anchor, placement);
+            // Returning the selected id instead of posting WM_COMMAND tells us which tray icon the menu belongs to
+            let id = TrackPopupMenuEx(self.hmenu, (alignment | TPM_RETURNCMD).0, anchor.x, anchor.y, hwnd, params.as_ref().map(|p| p as *const _)).0;
+            // Items of menus provided by the application may use ids that don't fit, which can't be ours
+            Ok(u16::try_from(id).ok().filter(|id| *id != 0))
+        }
+    }
+
+    pub fn map(&self, id: u16) -> Option<Ref<'_, dyn Any>> {
+        Ref::filter_map(self.signals_map.borrow(), |map| map.map(id)).ok()
+    }
+
+    /// Returns the native menu and the logical siblings of the item with the given id
+    fn resolve<'a>(&self, layout: &'a mut Vec<LayoutNode>, id: &'a MenuItemId) -> TrayResult<(HMENU, &'a mut Vec<LayoutNode>, usize)> {
+        let (path, index) = id
+            .split()
+            .ok_or_else(|| TrayError::custom("Empty menu item id"))?;
+        let mut hmenu = self.hmenu;
+        let mut siblings = layout;
+        for i in path {
+            let node = siblings
+                .get_mut(*i)
+                .ok_or_else(|| TrayError::custom("Menu item index out of bounds"))?;
+            hmenu = node.submenu
+                .ok_or_else(|| TrayError::custom("Menu item id does not point to a submenu"))?;
+            siblings = &mut node.children;
+        }
+        ensure!(index < siblings.len(), TrayError::custom("Menu item index out of bounds"));
+        Ok((hmenu, siblings, index))
+    }
+
+    /// The command id of a button, as returned by `TrackPopupMenu` or sent with `WM_COMMAND`
+    pub fn command_id(&self, id: &MenuItemId) -> TrayResult<Option<u16>> {
+        let mut layout = self.layout.borrow_mut();
+        let (_, siblings, index) = self.resolve(&mut layout, id)?;
+        Ok(siblings[index].command)
+    }
+
+    pub fn move_item(&self, id: &MenuItemId, new_index: usize) -> TrayResult<()> {
+        let mut layout = self.layout.borrow_mut();
+        let (hmenu, siblings, index) = self.resolve(&mut layout, id)?;
+        ensure!(new_index < siblings.len(), TrayError::custom("Menu item index out of bounds"));
+        if index == new_index {
+            return Ok(());
+        }
+        let item = match siblings[index].is_visible() {
+            true => Some(unsafe { DetachedItem::remove(hmenu, native_position(siblings, index))? }),
+            false => None
+        };
+        let node = siblings.remove(index);
+        siblings.insert(new_index, node);
+        if let Some(item) = item {
+            unsafe { item.insert(hmenu, native_position(siblings, new_index))? };
         }
         Ok(())
     }
 
-    pub fn map(&self, id: u16) -> Option<&dyn Any> {
-        self.signals_map.map(id)
+    pub fn set_visible(&self, id: &MenuItemId, visible: bool) -> TrayResult<()> {
+        let mut layout = self.layout.borrow_mut();
+        let (hmenu, siblings, index) = self.resolve(&mut layout, id)?;
+        set_node_visible(hmenu, siblings, index, visible)
     }
 
 }
@@ -32,35 +374,121 @@ impl NativeMenu {
 impl Drop for NativeMenu {
     fn drop(&mut self) {
         log::trace!("Destroying native menu");
+        // Hidden submenus are no longer attached to the menu and have to be destroyed separately
+        fn destroy_hidden(nodes: &[LayoutNode]) {
+            for node in nodes {
+                if let (Some(_), Some(submenu)) = (&node.hidden, node.submenu) {
+                    unsafe {
+                        DestroyMenu(submenu)
+                            .unwrap_or_else(|err| log::native_error!("Failed to destroy hidden submenu: {err}"));
+                    }
+                } else {
+                    destroy_hidden(&node.children);
+                }
+            }
+        }
+        destroy_hidden(self.layout.get_mut());
+        if !self.owned {
+            return;
+        }
         unsafe {
             DestroyMenu(self.hmenu)
-                .unwrap_or_else(|err| log::warn!("Failed to destroy native menu: {err}"));
+                .unwrap_or_else(|err| log::native_error!("Failed to destroy native menu: {err}"));
         }
     }
 }
 
-fn add_all<T>(hmenu: HMENU, signals: &mut Vec<T>, items: Vec<MenuItem<T>>) -> TrayResult<()> {
+#[allow(clippy::vec_box)]
+fn add_all<T>(hmenu: HMENU, signals: &mut Vec<T>, styled_labels: &mut Vec<Box<StyledLabel>>, bitmaps: &mut Vec<Bitmap>, items: Vec<MenuItem<T>>) -> TrayResult<Vec<LayoutNode>> {
+    let mut layout = Vec::with_capacity(items.len());
+    let mut visible = Vec::with_capacity(items.len());
     for item in items {
-        match item {
+        visible.push(item.is_visible());
+        let mut command = None;
+        let mut submenu = None;
+        let mut children = Vec::new();
+        let source = match item {
             MenuItem::Separator => {
                 unsafe { AppendMenuW(hmenu, MF_SEPARATOR, 0, None)? };
+                ItemSource::new(ItemKind::Separator, String::new())
+            }
+            MenuItem::Button { name, signal, style, .. } => {
+                let id = command_id(signals.len())?;
+                append_button(hmenu, &name, MF_STRING, style, id, styled_labels)?;
+                signals.push(signal);
+                command = Some(id);
+                ItemSource { style, ..ItemSource::new(ItemKind::Button, name) }
             }
-            MenuItem::Button { name, signal, checked } => {
-                let checked = checked
+            MenuItem::CheckButton { name, signal, checked, style, state_images, .. } => {
+                let flags = checked
                     .then_some(MF_CHECKED)
-                    .unwrap_or_default();
-                let wide = encode_wide(&name);
-                unsafe { AppendMenuW(hmenu, MF_STRING | checked, signals.len(), PCWSTR(wide.as_ptr()))? };
+                    .unwrap_or(MF_UNCHECKED);
+                let id = command_id(signals.len())?;
+                append_button(hmenu, &name, MF_STRING | flags, style, id, styled_labels)?;
+                set_state_images(hmenu, id, &state_images, bitmaps)?;
                 signals.push(signal);
+                command = Some(id);
+                ItemSource { checked, style, state_images, ..ItemSource::new(ItemKind::CheckButton, name) }
             }
-            MenuItem::Menu { name, children } => {
-                let submenu = unsafe { CreatePopupMenu()? };
-                add_all(submenu, signals, children)?;
+            MenuItem::Menu { name, children: items, .. } => {
+                let hsubmenu = unsafe { CreatePopupMenu()? };
+                children = add_all(hsubmenu, signals, styled_labels, bitmaps, items)?;
                 let wide = encode_wide(&name);
-                unsafe { AppendMenuW(hmenu, MF_POPUP, submenu.0 as _, PCWSTR(wide.as_ptr()))? };
+                unsafe { AppendMenuW(hmenu, MF_POPUP, hsubmenu.0 as _, PCWSTR(wide.as_ptr()))? };
+                submenu = Some(hsubmenu);
+                ItemSource::new(ItemKind::Menu, name)
             }
+        };
+        layout.push(LayoutNode {
+            command,
+            submenu,
+            hidden: None,
+            source,
+            children,
+        });
+    }
+    // Going backwards keeps the positions of the remaining items valid
+    for (position, node) in layout.iter_mut().enumerate().rev() {
+        if !visible[position] {
+            node.hidden = Some(unsafe { DetachedItem::remove(hmenu, position as u32)? });
         }
     }
+    Ok(layout)
+}
+
+#[allow(clippy::vec_box)]
+fn append_button(hmenu: HMENU, name: &str, flags: MENU_ITEM_FLAGS, style: ItemStyle, id: u16, styled_labels: &mut Vec<Box<StyledLabel>>) -> TrayResult<()> {
+    let wide = encode_wide(name);
+    match style {
+        ItemStyle::Normal => unsafe { AppendMenuW(hmenu, flags, id as usize, PCWSTR(wide.as_ptr()))? },
+        style => {
+            let label = Box::new(StyledLabel { text: wide, style });
+            // For owner-drawn items the last parameter is passed back as item data
+            unsafe { AppendMenuW(hmenu, flags | MF_OWNERDRAW, id as usize, PCWSTR(label.as_ref() as *const StyledLabel as _))? };
+            styled_labels.push(label);
+        }
+    }
+    Ok(())
+}
+
+/// Replaces the checkmark with the images, rasterized at the size of the checkmark for the current DPI
+fn set_state_images(hmenu: HMENU, id: u16, images: &StateImages, bitmaps: &mut Vec<Bitmap>) -> TrayResult<()> {
+    if images.on.is_none() && images.off.is_none() {
+        return Ok(());
+    }
+    let (width, height) = unsafe { (GetSystemMetrics(SM_CXMENUCHECK), GetSystemMetrics(SM_CYMENUCHECK)) };
+    let mut rasterize = |image: &Option<Icon>| -> TrayResult<HBITMAP> {
+        let Some(image) = image else {
+            return Ok(HBITMAP::default());
+        };
+        let bitmap = image.0.to_bitmap(width as u32, height as u32)?;
+        let handle = bitmap.handle();
+        bitmaps.push(bitmap);
+        Ok(handle)
+    };
+    let unchecked = rasterize(&images.off)?;
+    let checked = rasterize(&images.on)?;
+    unsafe { SetMenuItemBitmaps(hmenu, id as u32, MF_BYCOMMAND, unchecked, checked)? };
     Ok(())
 }
 
@@ -71,22 +499,134 @@ impl<T: 'static> TryFrom<Menu<T>> for NativeMenu {
         log::trace!("Creating new native menu");
         let hmenu = unsafe { CreatePopupMenu()? };
         let mut signals = Vec::<T>::new();
-        add_all(hmenu, &mut signals, value.items)?;
+        let mut styled_labels = Vec::new();
+        let mut bitmaps = Vec::new();
+        let layout = add_all(hmenu, &mut signals, &mut styled_labels, &mut bitmaps, value.items)?;
         Ok(Self {
             hmenu,
-            signals_map: Box::new(signals),
+            owned: true,
+            layout: RefCell::new(layout),
+            _styled_labels: styled_labels,
+            _bitmaps: bitmaps,
+            signals_map: RefCell::new(Box::new(signals)),
         })
     }
 }
 
+/// Handles `WM_MEASUREITEM` for the owner-drawn items of a menu
+pub unsafe fn measure_item(info: &mut MEASUREITEMSTRUCT) {
+    if info.CtlType != ODT_MENU.0 || info.itemData == 0 {
+        return;
+    }
+    let label = &*(info.itemData as *const StyledLabel);
+    let hdc = GetDC(HWND::default());
+    let font = menu_font(label.style);
+    let previous = SelectObject(hdc, font);
+    let mut size = SIZE::default();
+    GetTextExtentPoint32W(hdc, label.text(), &mut size);
+    SelectObject(hdc, previous);
+    DeleteObject(font);
+    ReleaseDC(HWND::default(), hdc);
+    // Leave room for the checkmark on the left and some padding on the right, just like regular items
+    let check_width = GetSystemMetrics(SM_CXMENUCHECK);
+    info.itemWidth = (size.cx + 2 * check_width) as u32;
+    info.itemHeight = size.cy.max(GetSystemMetrics(SM_CYMENU)) as u32;
+}
+
+/// Handles `WM_DRAWITEM` for the owner-drawn items of a menu
+pub unsafe fn draw_item(info: &DRAWITEMSTRUCT) {
+    if info.CtlType != ODT_MENU || info.itemData == 0 {
+        return;
+    }
+    let label = &*(info.itemData as *const StyledLabel);
+    let selected = (info.itemState & ODS_SELECTED).0 != 0;
+    let (background, foreground) = match selected {
+        true => (COLOR_HIGHLIGHT, COLOR_HIGHLIGHTTEXT),
+        false => (COLOR_MENU, COLOR_MENUTEXT)
+    };
+    let mut rect = info.rcItem;
+    FillRect(info.hDC, &rect, GetSysColorBrush(background));
+    let check_width = GetSystemMetrics(SM_CXMENUCHECK);
+    if (info.itemState & ODS_CHECKED).0 != 0 {
+        let mut check = RECT { right: rect.left + check_width, ..rect };
+        DrawFrameControl(info.hDC, &mut check, DFC_MENU, DFCS_MENUCHECK);
+    }
+    let color = match (label.style, selected) {
+        (ItemStyle::Destructive, false) => COLORREF(DESTRUCTIVE_COLOR),
+        _ => COLORREF(GetSysColor(foreground))
+    };
+    SetTextColor(info.hDC, color);
+    SetBkMode(info.hDC, TRANSPARENT);
+    let font = menu_font(label.style);
+    let previous = SelectObject(info.hDC, font);
+    rect.left += check_width;
+    let mut text = label.text().to_vec();
+    DrawTextW(info.hDC, &mut text, &mut rect, DT_SINGLELINE | DT_VCENTER | DT_LEFT);
+    SelectObject(info.hDC, previous);
+    DeleteObject(font);
+}
+
+// A red that stays readable on both the light and the dark menu background (0x00BBGGRR)
+const DESTRUCTIVE_COLOR: u32 = 0x001C2BC4;
+
+/// Creates the font that is used for regular menu items, in bold if the item is highlighted
+unsafe fn menu_font(style: ItemStyle) -> HFONT {
+    let mut metrics = NONCLIENTMETRICSW {
+        cbSize: size_of::<NONCLIENTMETRICSW>() as u32,
+        ..Default::default()
+    };
+    SystemParametersInfoW(SPI_GETNONCLIENTMETRICS, metrics.cbSize, Some(&mut metrics as *mut _ as _), SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0))
+        .unwrap_or_else(|err| log::debug!("Failed to query the menu font: {err}"));
+    if style == ItemStyle::Highlighted {
+        metrics.lfMenuFont.lfWeight = FW_BOLD.0 as i32;
+    }
+    CreateFontIndirectW(&metrics.lfMenuFont)
+}
+
+impl StyledLabel {
+    /// The text without the trailing null
+    fn text(&self) -> &[u16] {
+        self.text.strip_suffix(&[0]).unwrap_or(&self.text)
+    }
+}
+
 trait SignalMap {
     fn map(&self, id: u16) -> Option<&dyn Any>;
 }
 
 impl<T: 'static> SignalMap for Vec<T> {
+    fn map(&self, id: u16) -> Option<&dyn Any> {
+        let index = id.checked_sub(1)?;
+        self
+            .get(index as usize)
+            .map(|r| r as _)
+    }
+}
+
+impl<T: 'static> SignalMap for HashMap<u16, T> {
     fn map(&self, id: u16) -> Option<&dyn Any> {
         self
-            .get(id as usize)
+            .get(&id)
             .map(|r| r as _)
     }
+}
+
+/// Command ids start at 1, as `TrackPopupMenu` returns 0 when no item was selected
+fn command_id(signal_index: usize) -> TrayResult<u16> {
+    signal_index
+        .checked_add(1)
+        .and_then(|id| u16::try_from(id).ok())
+        .ok_or_else(|| TrayError::custom(format!("Menus are limited to {} buttons", u16::MAX)))
+}
+
+impl ItemSource {
+    fn new(kind: ItemKind, label: String) -> Self {
+        Self {
+            kind,
+            label,
+            checked: false,
+            style: ItemStyle::Normal,
+            state_images: StateImages::default(),
+        }
+    }
 }
\ No newline at end of file
@@ -1,117 +1,228 @@
 mod menu;
 mod tray;
 mod icon;
+mod toast;
+mod flyout;
+#[cfg(any(feature = "remote", feature = "single-instance"))]
+mod pipe;
+#[cfg(feature = "remote")]
+mod remote;
+#[cfg(feature = "tray-notify")]
+mod promotion;
+#[cfg(any(feature = "tray-notify", feature = "taskbar-progress"))]
+mod com;
+#[cfg(feature = "taskbar-progress")]
+mod taskbar;
+#[cfg(feature = "autostart")]
+pub mod autostart;
+#[cfg(feature = "single-instance")]
+pub mod instance;
 
 use std::any::Any;
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
 use std::iter::once;
 use std::marker::PhantomData;
-use std::rc::Rc;
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::rc::{Rc, Weak};
+use std::sync::atomic::{AtomicIsize, AtomicU32, AtomicUsize, Ordering};
 use std::sync::Once;
+use std::time::Duration;
 use once_cell::sync::Lazy;
 use windows::core::{PCWSTR, w};
-use windows::Win32::Foundation::{HINSTANCE, HWND, LPARAM, LRESULT, WPARAM};
+use windows::Win32::Foundation::{HANDLE, HINSTANCE, HWND, LPARAM, LRESULT, RECT, WPARAM};
+use windows::Win32::System::Power::{HPOWERNOTIFY, RegisterSuspendResumeNotification, UnregisterSuspendResumeNotification};
+use windows::Win32::System::RemoteDesktop::ProcessIdToSessionId;
 use windows::Win32::System::SystemServices::IMAGE_DOS_HEADER;
-use windows::Win32::UI::Shell::{DefSubclassProc, SetWindowSubclass};
-use windows::Win32::UI::WindowsAndMessaging::{CreateWindowExW, DefWindowProcW, DestroyWindow, HICON, HMENU, HWND_MESSAGE, RegisterClassW, RegisterWindowMessageW, WINDOW_EX_STYLE, WINDOW_STYLE, WM_COMMAND, WM_DESTROY, WM_LBUTTONDBLCLK, WM_LBUTTONUP, WM_RBUTTONUP, WNDCLASSW};
+use windows::Win32::System::Threading::GetCurrentProcessId;
+use windows::Win32::UI::Controls::{DRAWITEMSTRUCT, MEASUREITEMSTRUCT};
+use windows::Win32::UI::Shell::{NIN_BALLOONTIMEOUT, NIN_BALLOONUSERCLICK, NIN_KEYSELECT, NIN_POPUPCLOSE, NIN_POPUPOPEN, NOTIFYICON_VERSION_4, QUNS_ACCEPTS_NOTIFICATIONS, QUNS_APP, QUNS_BUSY, QUNS_NOT_PRESENT, QUNS_PRESENTATION_MODE, QUNS_QUIET_TIME, QUNS_RUNNING_D3D_FULL_SCREEN, SHQueryUserNotificationState};
+use windows::Win32::UI::WindowsAndMessaging::{CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW, GetMessageW, KillTimer, MSG, PostMessageW, TranslateMessage, WM_CONTEXTMENU, WM_NULL, HICON, HMENU, DEVICE_NOTIFY_WINDOW_HANDLE, PBT_APMRESUMEAUTOMATIC, RegisterClassW, RegisterWindowMessageW, SetTimer, WINDOW_EX_STYLE, WINDOW_STYLE, WM_DRAWITEM, WM_ENDSESSION, WM_LBUTTONDBLCLK, WM_MEASUREITEM, WM_LBUTTONUP, WM_POWERBROADCAST, WM_QUERYENDSESSION, WM_RBUTTONUP, WM_TIMER, WNDCLASSW};
 use crate::platform::windows::menu::NativeMenu;
-use crate::{ClickType, ensure, Icon, Menu, TrayEvent, TrayIconBuilder};
+use crate::platform::windows::toast::ActiveToasts;
+use crate::{Backend, ClickPolicy, ClickType, ensure, Icon, IconRect, Menu, MenuItemId, MenuPlacement, MenuTrigger, Notification, NotificationEvent, TooltipProvider, TrayEvent, TrayIconBuilder, WndProcHook};
 use crate::error::{ErrorSource, TrayError, TrayResult};
 use crate::platform::windows::tray::{DataAction, TrayIconData};
 use crate::utils::OptionCellExt;
+use crate::control_flow::ExitSignal;
+use crate::log;
 
 pub use icon::NativeIcon;
+#[cfg(feature = "remote")]
+pub use remote::RemoteTransport;
 
 //TODO Better error handling for the set_* functions
-//TODO Replace Cell to avoid potential overrides
 
-const TRAY_SUBCLASS_ID: usize = 6001;
 const WM_USER_TRAY_ICON: u32 = 6002;
+const WM_USER_TOAST_EVENT: u32 = 6003;
+const WM_USER_WAKE: u32 = 6004;
+const INTERVAL_TIMER_BASE_ID: usize = 7000;
+
+pub const BACKEND: Backend = Backend::WindowsShell;
+
+pub type IntervalCallback = Box<dyn FnMut() + 'static>;
+
+pub type Acknowledgement = std::future::Ready<()>;
 
 pub struct NativeTrayIcon<T> {
-    hwnd: HWND,
+    window: Rc<MessageWindow>,
     tray_id: u32,
     shared: Rc<SharedTrayData>,
+    app_id: Option<String>,
     _signal_type: PhantomData<T>
 }
 
+/// A hidden window that receives the messages of all tray icons on the current thread
+///
+/// The individual tray icons are distinguished by the id of their notify icon.
+struct MessageWindow {
+    hwnd: HWND,
+    power_notify: Option<HPOWERNOTIFY>,
+    // Whether SessionEnding was already emitted for WM_QUERYENDSESSION
+    session_ending: Cell<bool>,
+    trays: RefCell<HashMap<u32, Rc<TrayLoopData>>>,
+    intervals: RefCell<HashMap<usize, Rc<RefCell<IntervalCallback>>>>
+}
+
+thread_local! {
+    static MESSAGE_WINDOW: RefCell<Weak<MessageWindow>> = RefCell::new(Weak::new());
+}
+
 struct TrayLoopData {
+    tray_id: u32,
     shared: Rc<SharedTrayData>,
-    callback: Box<dyn FnMut(TrayEvent<&dyn Any>) + 'static>
+    tooltip_provider: Option<TooltipProvider>,
+    click_policy: ClickPolicy,
+    menu_trigger: MenuTrigger,
+    menu_placement: MenuPlacement,
+    wndproc_hook: Option<WndProcHook>,
+    mouse_context: Cell<bool>,
+    // Events are queued before they are dispatched, so that a callback that (indirectly) causes new
+    // messages to arrive, e.g. by opening a menu, doesn't re-enter itself
+    enqueue: Box<dyn Fn(TrayEvent<&dyn Any>) + 'static>,
+    dispatch: Box<dyn Fn() + 'static>
+}
+
+impl TrayLoopData {
+    fn emit(&self, event: TrayEvent<&dyn Any>) {
+        (self.enqueue)(event);
+        (self.dispatch)();
+    }
+
+    /// Handles a click or keyboard activation of the icon
+    fn activate(&self, hwnd: HWND, click: ClickType, keyboard: bool) {
+        self.emit(TrayEvent::Tray { click, activation_token: None });
+        if !self.menu_trigger.matches(click) {
+            return;
+        }
+        if let Some(menu) = self.shared.menu() {
+            // Keyboard users expect the menu at the focused icon rather than wherever the cursor happens to be
+            let shown = match keyboard {
+                true => tray::icon_rect(hwnd, self.tray_id).and_then(|rect| menu.show_on_rect(hwnd, rect, self.menu_placement)),
+                false => menu.show_on_cursor(hwnd, self.menu_placement)
+            };
+            match shown {
+                Ok(Some(id)) => match menu.map(id) {
+                    None => log::debug!("Unknown menu item id: {id}"),
+                    Some(signal) => (self.enqueue)(TrayEvent::Menu { signal: &*signal, activation_token: None })
+                },
+                Ok(None) => {},
+                Err(err) => log::native_error!("Failed to show menu: {err}")
+            }
+            (self.dispatch)();
+        }
+    }
 }
 
 struct SharedTrayData {
-    menu: Cell<Option<NativeMenu>>,
+    menu: RefCell<Option<Rc<NativeMenu>>>,
     tooltip: Cell<Option<String>>,
-    icon: Cell<Option<NativeIcon>>
+    icon: Cell<Option<NativeIcon>>,
+    notification_icon: Cell<Option<NativeIcon>>,
+    toasts: ActiveToasts,
+    // Hovering shows the application's flyout instead of the standard tooltip
+    flyout: bool,
+    timers: RefCell<Vec<usize>>,
+    // Callbacks of wakers, which share the id space and storage of the interval timers
+    wakeups: RefCell<Vec<usize>>
 }
 
 impl<T: Clone + 'static> NativeTrayIcon<T> {
 
-    pub fn new<F>(builder: TrayIconBuilder<T>, mut callback: F) -> TrayResult<Self>
+    pub fn new<F>(builder: TrayIconBuilder<T>, callback: F) -> TrayResult<Self>
         where F: FnMut(TrayEvent<T>) + Send + 'static
     {
+        ensure_interactive_session()?;
+
+        if let Some(app_id) = &builder.app_id {
+            let display_name = builder.title.as_deref().unwrap_or(app_id);
+            toast::register_app_id(app_id, display_name)
+                .unwrap_or_else(|err| log::native_error!("Failed to register the app id: {err}"));
+        }
+
         let tray_id = GLOBAL_TRAY_COUNTER.fetch_add(1, Ordering::Relaxed);
 
-        let hwnd = unsafe {
-            CreateWindowExW(
-                WINDOW_EX_STYLE::default(),
-                get_class_name(),
-                PCWSTR::null(),
-                WINDOW_STYLE::default(),
-                0, 0,
-                0, 0,
-                HWND_MESSAGE,
-                HMENU::default(),
-                get_instance_handle(),
-                None
-            )
-        };
-        ensure!(hwnd != HWND::default(), TrayError::custom("Invalid HWND"));
-        log::trace!("Created new message window (tray id: {tray_id})");
+        let window = MessageWindow::get_or_create()?;
+        let hwnd = window.hwnd;
 
         let shared = Rc::new(SharedTrayData {
-            menu: Cell::new(builder
+            menu: RefCell::new(builder
                 .menu
                 .map(NativeMenu::try_from)
-                .transpose()?),
+                .transpose()?
+                .map(Rc::new)),
             tooltip: Cell::new(builder.tooltip),
             icon: Cell::new(builder.icon.map(NativeIcon::from)),
+            notification_icon: Cell::new(None),
+            toasts: ActiveToasts::default(),
+            flyout: builder.hover_flyout,
+            timers: RefCell::new(Vec::new()),
+            wakeups: RefCell::new(Vec::new()),
         });
 
-        TrayIconData::from(&shared)
-            .with_message(WM_USER_TRAY_ICON)
-            .apply(hwnd, tray_id, DataAction::Add)?;
-
+        add_tray_icon(hwnd, tray_id, &shared)?;
 
+        let queue = Rc::new(RefCell::new(VecDeque::new()));
+        let callback = RefCell::new(callback);
         let data = TrayLoopData {
+            tray_id,
             shared: shared.clone(),
-            callback: Box::new(move |event: TrayEvent<&dyn Any> | {
-                let event = match event {
-                    TrayEvent::Menu(signal) => TrayEvent::Menu(signal
+            tooltip_provider: builder.tooltip_provider,
+            click_policy: builder.click_policy,
+            menu_trigger: builder.menu_trigger,
+            menu_placement: builder.menu_placement,
+            wndproc_hook: builder.wndproc_hook,
+            mouse_context: Cell::new(false),
+            enqueue: Box::new({
+                let queue = queue.clone();
+                move |event: TrayEvent<&dyn Any> | {
+                    let event = event.map(|signal| signal
                         .downcast_ref::<T>()
                         .expect("Signal has the wrong type")
-                        .clone()),
-                    TrayEvent::Tray(click) => TrayEvent::Tray(click)
+                        .clone());
+                    queue.borrow_mut().push_back(event);
+                }
+            }),
+            dispatch: Box::new(move || {
+                // A callback that is already running picks up the new events once it returns
+                let Ok(mut callback) = callback.try_borrow_mut() else {
+                    return;
                 };
-                callback(event);
+                loop {
+                    let Some(event) = queue.borrow_mut().pop_front() else {
+                        break;
+                    };
+                    callback(event);
+                }
             }),
         };
 
-        unsafe {
-            SetWindowSubclass(
-                hwnd,
-                Some(tray_subclass_proc),
-                TRAY_SUBCLASS_ID,
-                Box::into_raw(Box::new(data)) as _)
-                .ok()?;
-        }
+        window.trays.borrow_mut().insert(tray_id, Rc::new(data));
 
         Ok(NativeTrayIcon {
-            hwnd,
+            window,
             tray_id,
             shared,
+            app_id: builder.app_id,
             _signal_type: PhantomData::default(),
         })
 
@@ -125,93 +236,499 @@ impl<T> NativeTrayIcon<T> {
             .with_tooltip(tooltip
                 .as_ref()
                 .map(|s| s.as_str())
-                .unwrap_or(""))
-            .apply(self.hwnd, self.tray_id, DataAction::Modify)
+                .unwrap_or(""), !self.shared.flyout)
+            .apply(self.window.hwnd, self.tray_id, DataAction::Modify)
             .unwrap();
         self.shared.tooltip.set(tooltip)
     }
 
+    pub fn set_title(&self, _title: Option<String>) {
+
+    }
+
     pub fn set_icon(&self, icon: Option<Icon>) {
         TrayIconData::default()
             .with_icon(icon.as_ref().map(|i| i.0.handle()).unwrap_or(HICON::default()))
-            .apply(self.hwnd, self.tray_id, DataAction::Modify)
+            .apply(self.window.hwnd, self.tray_id, DataAction::Modify)
             .unwrap();
         self.shared.icon.set(icon.map(|i| i.0))
     }
 
+    /// There is no icon theme, so this always shows the fallback
+    pub fn set_themed_icon(&self, _name: String, fallback: Option<Icon>) {
+        self.set_icon(fallback)
+    }
+
+    pub fn show_notification(&self, notification: Notification) {
+        if notification.respect_dnd && self.is_do_not_disturb().unwrap_or(false) {
+            log::debug!("Dropping notification as do not disturb is active");
+            return;
+        }
+        // Balloons don't support buttons, but toasts can only be shown under a registered app id
+        let result = match (notification.actions.is_empty(), self.app_id.as_deref()) {
+            (false, Some(app_id)) => self.shared.toasts
+                .show(self.window.hwnd, WM_USER_TOAST_EVENT, self.tray_id, app_id, &notification),
+            (empty, _) => {
+                if !empty {
+                    log::warn!("Notification actions require an app id, showing the notification without them");
+                }
+                TrayIconData::default()
+                    .with_notification(&notification)
+                    .apply(self.window.hwnd, self.tray_id, DataAction::Modify)
+            }
+        };
+        result.unwrap_or_else(|err| log::native_error!("Failed to show notification: {err}"));
+        // Keep the balloon icon alive while the notification might still be visible
+        self.shared.notification_icon.set(notification.icon.map(|i| i.0));
+    }
+
+    pub fn is_do_not_disturb(&self) -> TrayResult<bool> {
+        let state = unsafe { SHQueryUserNotificationState()? };
+        log::trace!("Notification state: {}", state.0);
+        match state {
+            // A locked or inactive session is no choice of the user, so it doesn't count as do not disturb
+            QUNS_ACCEPTS_NOTIFICATIONS | QUNS_APP | QUNS_NOT_PRESENT => Ok(false),
+            QUNS_BUSY | QUNS_RUNNING_D3D_FULL_SCREEN | QUNS_PRESENTATION_MODE | QUNS_QUIET_TIME => Ok(true),
+            _ => Err(TrayError::from(ErrorSource::NotSupported))
+        }
+    }
+
+    /// Updates are applied synchronously, so they are always acknowledged
+    pub fn acknowledge(&self) -> Acknowledgement {
+        std::future::ready(())
+    }
+
+    pub fn id(&self) -> String {
+        format!("{:x}:{}", self.window.hwnd.0, self.tray_id)
+    }
+
+    pub fn run(&self, exit: &ExitSignal) {
+        let mut msg = MSG::default();
+        while !exit.is_requested() {
+            match unsafe { GetMessageW(&mut msg, HWND::default(), 0, 0) }.0 {
+                0 => break,
+                -1 => {
+                    log::native_error!("Failed to get message: {}", windows::core::Error::from_win32());
+                    break;
+                }
+                _ => unsafe {
+                    TranslateMessage(&msg);
+                    DispatchMessageW(&msg);
+                }
+            }
+        }
+    }
+
+    pub fn set_interval(&self, interval: Duration, callback: IntervalCallback) {
+        let timer_id = INTERVAL_TIMER_BASE_ID + GLOBAL_TIMER_COUNTER.fetch_add(1, Ordering::Relaxed);
+        self.window.intervals.borrow_mut().insert(timer_id, Rc::new(RefCell::new(callback)));
+        self.shared.timers.borrow_mut().push(timer_id);
+        let millis = u32::try_from(interval.as_millis()).unwrap_or(u32::MAX);
+        if unsafe { SetTimer(self.window.hwnd, timer_id, millis, None) } == 0 {
+            log::native_error!("Failed to create interval timer: {}", windows::core::Error::from_win32());
+        }
+    }
+
+    /// Calls `callback` on the thread of the tray icon after the returned waker was woken
+    #[cfg(feature = "remote")]
+    pub fn set_wakeup(&self, callback: IntervalCallback) -> TrayWaker {
+        let id = INTERVAL_TIMER_BASE_ID + GLOBAL_TIMER_COUNTER.fetch_add(1, Ordering::Relaxed);
+        self.window.intervals.borrow_mut().insert(id, Rc::new(RefCell::new(callback)));
+        self.shared.wakeups.borrow_mut().push(id);
+        TrayWaker { hwnd: self.window.hwnd, id }
+    }
+
+    pub fn is_in_overflow(&self) -> TrayResult<bool> {
+        tray::is_in_overflow(self.window.hwnd, self.tray_id)
+    }
+
+    pub fn icon_rect(&self) -> TrayResult<IconRect> {
+        tray::icon_rect(self.window.hwnd, self.tray_id).map(IconRect::from)
+    }
+
+    pub fn show_flyout(&self, hwnd: isize) -> TrayResult<()> {
+        let anchor = tray::icon_rect(self.window.hwnd, self.tray_id)?;
+        let placement = self.window.trays
+            .borrow()
+            .get(&self.tray_id)
+            .map(|tray| tray.menu_placement)
+            .unwrap_or_default();
+        flyout::show_flyout(HWND(hwnd), anchor, placement)
+    }
+
+    pub fn hide_flyout(&self, hwnd: isize) -> TrayResult<()> {
+        flyout::hide_flyout(HWND(hwnd))
+    }
+
+    #[cfg(feature = "tray-notify")]
+    pub fn request_promotion(&self) -> TrayResult<()> {
+        promotion::request_promotion(self.window.hwnd, self.tray_id)
+    }
+
+    #[cfg(feature = "taskbar-progress")]
+    pub fn set_taskbar_progress(&self, hwnd: isize, progress: crate::TaskbarProgress) -> TrayResult<()> {
+        taskbar::set_progress(HWND(hwnd), progress)
+    }
+
+    pub fn set_visible(&self, id: &MenuItemId, visible: bool) {
+        if let Some(menu) = self.shared.menu() {
+            menu.set_visible(id, visible)
+                .unwrap_or_else(|err| log::native_error!("Failed to change the visibility of menu item {id:?}: {err}"));
+        }
+    }
+
+    pub fn native_id(&self, id: &MenuItemId) -> Option<u32> {
+        let menu = self.shared.menu()?;
+        menu.command_id(id)
+            .unwrap_or_else(|err| {
+                log::native_error!("Failed to get the command id of menu item {id:?}: {err}");
+                None
+            })
+            .map(u32::from)
+    }
+
+    pub fn move_item(&self, id: &MenuItemId, new_index: usize) {
+        if let Some(menu) = self.shared.menu() {
+            menu.move_item(id, new_index)
+                .unwrap_or_else(|err| log::native_error!("Failed to move menu item {id:?}: {err}"));
+        }
+    }
+
 }
 
 impl<T: 'static> NativeTrayIcon<T> {
     pub fn set_menu(&self, menu: Option<Menu<T>>) {
-        let menu = menu
-            .map(|m| NativeMenu::try_from(m).unwrap());
-        self.shared.menu.set(menu);
+        let menu = match (self.shared.menu(), menu) {
+            // Updating the existing menu keeps submenus that are currently open
+            (Some(current), Some(menu)) if current.accepts(&menu) => {
+                current.update(menu)
+                    .unwrap_or_else(|err| log::native_error!("Failed to update menu: {err}"));
+                return;
+            }
+            (_, menu) => menu
+        };
+        match menu.map(NativeMenu::try_from).transpose() {
+            // A menu that is currently shown stays alive until it is closed
+            Ok(menu) => *self.shared.menu.borrow_mut() = menu.map(Rc::new),
+            Err(err) => log::native_error!("Failed to create menu: {err}")
+        }
+    }
+
+    pub fn set_native_menu(&self, hmenu: isize, signals: HashMap<u16, T>) {
+        *self.shared.menu.borrow_mut() = Some(Rc::new(NativeMenu::from_native(HMENU(hmenu), signals)));
     }
 }
 
 impl<T> Drop for NativeTrayIcon<T> {
     fn drop(&mut self) {
-        log::trace!("Destroying message window (tray id: {})", self.tray_id);
+        log::trace!("Removing tray icon (tray id: {})", self.tray_id);
 
         TrayIconData::default()
-            .apply(self.hwnd, self.tray_id, DataAction::Remove)
-            .unwrap_or_else(|err| log::warn!("Failed to remove tray icon: {err}"));
+            .apply(self.window.hwnd, self.tray_id, DataAction::Remove)
+            .unwrap_or_else(|err| log::native_error!("Failed to remove tray icon: {err}"));
 
-        unsafe {
-            DestroyWindow(self.hwnd)
-                .unwrap_or_else(|err| log::warn!("Failed to destroy message window: {err}"));
-        };
+        for timer_id in self.shared.timers.take() {
+            unsafe {
+                KillTimer(self.window.hwnd, timer_id)
+                    .unwrap_or_else(|err| log::native_error!("Failed to stop interval timer: {err}"));
+            }
+            self.window.intervals.borrow_mut().remove(&timer_id);
+        }
+        for id in self.shared.wakeups.take() {
+            self.window.intervals.borrow_mut().remove(&id);
+        }
+        self.window.trays.borrow_mut().remove(&self.tray_id);
     }
 }
 
+impl MessageWindow {
+
+    fn get_or_create() -> TrayResult<Rc<Self>> {
+        MESSAGE_WINDOW.with(|window| {
+            if let Some(window) = window.borrow().upgrade() {
+                return Ok(window);
+            }
+            let new = Rc::new(Self::new()?);
+            *window.borrow_mut() = Rc::downgrade(&new);
+            Ok(new)
+        })
+    }
 
+    fn get() -> Option<Rc<Self>> {
+        MESSAGE_WINDOW.with(|window| window.borrow().upgrade())
+    }
 
+    fn new() -> TrayResult<Self> {
+        let hwnd = unsafe {
+            CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                get_class_name(),
+                PCWSTR::null(),
+                WINDOW_STYLE::default(),
+                0, 0,
+                0, 0,
+                // A hidden top-level window instead of a message-only window, as the latter doesn't receive
+                // broadcasts like WM_ENDSESSION or TaskbarCreated
+                HWND::default(),
+                HMENU::default(),
+                get_instance_handle(),
+                None
+            )
+        };
+        ensure!(hwnd != HWND::default(), TrayError::custom("Invalid HWND"));
+        log::trace!("Created new hidden window");
+        LOOP_WINDOW.store(hwnd.0, Ordering::Release);
 
+        // Resume notifications are only broadcast to windows that explicitly request them
+        let power_notify = unsafe { RegisterSuspendResumeNotification(HANDLE(hwnd.0), DEVICE_NOTIFY_WINDOW_HANDLE) }
+            .map_err(|err| log::native_error!("Failed to register for resume notifications: {err}"))
+            .ok();
 
-unsafe extern "system" fn tray_subclass_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM, _id: usize, subclass_input_ptr: usize) -> LRESULT {
-    let subclass_input_ptr = subclass_input_ptr as *mut TrayLoopData;
-    let subclass_input = &mut *subclass_input_ptr;
+        Ok(Self {
+            hwnd,
+            power_notify,
+            session_ending: Cell::new(false),
+            trays: RefCell::new(HashMap::new()),
+            intervals: RefCell::new(HashMap::new()),
+        })
+    }
+
+    fn tray(&self, tray_id: u32) -> Option<Rc<TrayLoopData>> {
+        let tray = self.trays.borrow().get(&tray_id).cloned();
+        if tray.is_none() {
+            log::debug!("Unknown tray id: {tray_id}");
+        }
+        tray
+    }
+
+    /// Copies the trays so that callbacks are free to add or remove tray icons
+    fn all_trays(&self) -> Vec<Rc<TrayLoopData>> {
+        self.trays.borrow().values().cloned().collect()
+    }
+
+    /// Passes the message to the hooks of all tray icons. The first result overrides the default handling.
+    fn run_hooks(&self, msg: u32, wparam: WPARAM, lparam: LPARAM) -> Option<LRESULT> {
+        self.all_trays()
+            .iter()
+            .filter_map(|tray| tray.wndproc_hook.as_ref())
+            .fold(None, |result, hook| result.or(hook.call(msg, wparam.0, lparam.0)))
+            .map(LRESULT)
+    }
+
+    /// Runs the callback of an interval timer or waker
+    fn run_interval(&self, id: usize) {
+        let interval = self
+            .intervals
+            .borrow()
+            .get(&id)
+            .cloned();
+        // A modal loop started from inside the callback can deliver the same timer again
+        match interval.as_ref().map(|i| i.try_borrow_mut()) {
+            Some(Ok(mut callback)) => callback(),
+            Some(Err(_)) => log::debug!("Skipping re-entrant interval callback"),
+            // Wakes can still be queued after the tray icon was dropped
+            None => log::debug!("Unknown interval id: {id}")
+        }
+    }
+
+}
+
+impl Drop for MessageWindow {
+    fn drop(&mut self) {
+        log::trace!("Destroying hidden window");
+        // Another thread might have created its own window in the meantime
+        let _ = LOOP_WINDOW.compare_exchange(self.hwnd.0, 0, Ordering::AcqRel, Ordering::Acquire);
+        unsafe {
+            if let Some(power_notify) = self.power_notify.take() {
+                UnregisterSuspendResumeNotification(power_notify)
+                    .unwrap_or_else(|err| log::native_error!("Failed to unregister resume notifications: {err}"));
+            }
+            DestroyWindow(self.hwnd)
+                .unwrap_or_else(|err| log::native_error!("Failed to destroy hidden window: {err}"));
+        };
+    }
+}
+
+fn handle_message(window: &MessageWindow, hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) {
     match msg {
-        WM_DESTROY => {
-            drop(Box::from_raw(subclass_input_ptr));
-            log::trace!("Dropped message loop data");
-        },
-        _ if msg == *S_U_TASKBAR_RESTART => log::debug!("Taskbar restarted"),
-        WM_USER_TRAY_ICON => if let Some(click) = ClickType::from_lparam(lparam) {
-            (subclass_input.callback)(TrayEvent::Tray(click));
-            if click == ClickType::Right {
-                subclass_input
-                    .shared
-                    .menu
-                    .with(|menu| menu
-                        .show_on_cursor(hwnd)
-                        .unwrap_or_else(|err| log::warn!("Failed to show menu: {err}")));
+        _ if msg == *S_U_TASKBAR_RESTART => {
+            log::debug!("Taskbar restarted");
+            // The new taskbar doesn't know about any of the existing icons
+            for tray in window.all_trays() {
+                match add_tray_icon(hwnd, tray.tray_id, &tray.shared) {
+                    Ok(()) => tray.emit(TrayEvent::HostRestarted),
+                    Err(err) => log::native_error!("Failed to restore tray icon: {err}")
+                }
             }
         }
-        WM_COMMAND => {
-            let id = LOWORD(wparam.0 as _);
-            subclass_input
-                .shared
-                .menu
-                .with(|menu| {
-                    match menu.map(id) {
-                        None => log::debug!("Unknown menu item id: {id}"),
-                        Some(signal) => (subclass_input.callback)(TrayEvent::Menu(signal))
+        WM_POWERBROADCAST if wparam.0 as u32 == PBT_APMRESUMEAUTOMATIC => {
+            log::debug!("System resumed");
+            for tray in window.all_trays() {
+                // The icon frequently disappears during hibernation, so check that it still exists and re-add it if not
+                let exists = TrayIconData::from(&tray.shared)
+                    .apply(hwnd, tray.tray_id, DataAction::Modify)
+                    .is_ok();
+                if !exists {
+                    add_tray_icon(hwnd, tray.tray_id, &tray.shared)
+                        .unwrap_or_else(|err| log::native_error!("Failed to restore tray icon: {err}"));
+                }
+                tray.emit(TrayEvent::Resumed);
+            }
+        }
+        // Emitting on the query gives apps the most time, DefWindowProcW then allows the session to end
+        WM_QUERYENDSESSION => {
+            log::debug!("Session is about to end");
+            window.session_ending.set(true);
+            for tray in window.all_trays() {
+                tray.emit(TrayEvent::SessionEnding);
+            }
+        }
+        WM_ENDSESSION => {
+            // A zero wparam means that the shutdown was cancelled, so the next query is a new shutdown
+            let announced = window.session_ending.replace(false);
+            if wparam.0 != 0 && !announced {
+                log::debug!("Session is ending");
+                for tray in window.all_trays() {
+                    tray.emit(TrayEvent::SessionEnding);
+                }
+            }
+        }
+        // With NOTIFYICON_VERSION_4 the high word of lparam contains the id of the icon
+        WM_USER_TRAY_ICON => if let Some(tray) = window.tray(HIWORD(lparam.0 as u32) as u32) {
+            match LOWORD(lparam.0 as u32) as u32 {
+                NIN_POPUPOPEN => {
+                    if let Some(provider) = &tray.tooltip_provider {
+                        let tooltip = provider.get();
+                        TrayIconData::default()
+                            .with_tooltip(&tooltip, !tray.shared.flyout)
+                            .apply(hwnd, tray.tray_id, DataAction::Modify)
+                            .unwrap_or_else(|err| log::native_error!("Failed to update tooltip: {err}"));
+                        tray.shared.tooltip.set(Some(tooltip));
+                    }
+                    match tray.shared.flyout {
+                        true => match tray::icon_rect(hwnd, tray.tray_id) {
+                            Ok(rect) => tray.emit(TrayEvent::ShowFlyout { anchor: rect.into() }),
+                            Err(err) => log::native_error!("Failed to get the position of the icon: {err}")
+                        },
+                        false => tray.emit(TrayEvent::TooltipShown)
                     }
-                });
+                },
+                NIN_POPUPCLOSE => match tray.shared.flyout {
+                    true => tray.emit(TrayEvent::HideFlyout),
+                    false => tray.emit(TrayEvent::TooltipHidden)
+                },
+                NIN_BALLOONUSERCLICK => tray.emit(TrayEvent::Notification(NotificationEvent::Clicked)),
+                NIN_BALLOONTIMEOUT => tray.emit(TrayEvent::Notification(NotificationEvent::Dismissed)),
+                event => {
+                    // A mouse right click is followed by WM_CONTEXTMENU as well, which must not open the menu a second time.
+                    // It's sent right after WM_RBUTTONUP, so any other message in between means that it isn't coming.
+                    match event {
+                        WM_RBUTTONUP => tray.mouse_context.set(true),
+                        WM_CONTEXTMENU => {}
+                        _ => tray.mouse_context.set(false)
+                    }
+                    let (click, keyboard) = match event {
+                        NIN_KEYSELECT => (Some(ClickType::Left), true),
+                        WM_CONTEXTMENU if !tray.mouse_context.take() => (Some(ClickType::Right), true),
+                        event => (ClickType::from_event(event, tray.click_policy), false)
+                    };
+                    if let Some(click) = click {
+                        tray.activate(hwnd, click, keyboard);
+                    }
+                }
+            }
         }
+        WM_USER_TOAST_EVENT => if let Some(tray) = window.tray(toast::decode_tray_id(wparam)) {
+            if let Some(event) = tray.shared.toasts.handle_event(wparam, lparam) {
+                tray.emit(TrayEvent::Notification(event));
+            }
+        }
+        // Styled menu items are owner-drawn
+        WM_MEASUREITEM => unsafe { menu::measure_item(&mut *(lparam.0 as *mut MEASUREITEMSTRUCT)) },
+        WM_DRAWITEM => unsafe { menu::draw_item(&*(lparam.0 as *const DRAWITEMSTRUCT)) },
+        WM_TIMER if wparam.0 >= INTERVAL_TIMER_BASE_ID => window.run_interval(wparam.0),
+        WM_USER_WAKE => window.run_interval(wparam.0),
         _ => {}
     }
-    DefSubclassProc(hwnd, msg, wparam, lparam)
 }
 
+/// Makes the tray icon run the callback passed to [NativeTrayIcon::set_wakeup]
+#[cfg(feature = "remote")]
+#[derive(Clone)]
+pub struct TrayWaker {
+    hwnd: HWND,
+    id: usize
+}
+
+#[cfg(feature = "remote")]
+impl TrayWaker {
+    pub fn wake(&self) {
+        unsafe {
+            PostMessageW(self.hwnd, WM_USER_WAKE, WPARAM(self.id), LPARAM(0))
+                .unwrap_or_else(|err| log::native_error!("Failed to wake the tray icon: {err}"));
+        }
+    }
+}
+
+/// Makes a pending `GetMessageW` in [NativeTrayIcon::run] return so that the exit signal gets checked
+///
+/// This is usually called from a different thread, so the message has to go to the hidden window
+/// instead of the queue of the calling thread.
+pub fn wake_event_loop() {
+    let hwnd = HWND(LOOP_WINDOW.load(Ordering::Acquire));
+    if hwnd == HWND::default() {
+        log::debug!("No event loop to wake");
+        return;
+    }
+    unsafe {
+        PostMessageW(hwnd, WM_NULL, WPARAM(0), LPARAM(0))
+            .unwrap_or_else(|err| log::native_error!("Failed to wake event loop: {err}"));
+    }
+}
+
+impl SharedTrayData {
+    fn menu(&self) -> Option<Rc<NativeMenu>> {
+        self.menu.borrow().clone()
+    }
+}
+
+/// The id of the Remote Desktop Services session the process runs in. Services run in session 0.
+fn session_id() -> TrayResult<u32> {
+    let mut session_id = 0;
+    unsafe { ProcessIdToSessionId(GetCurrentProcessId(), &mut session_id)? };
+    Ok(session_id)
+}
+
+/// Services run in session 0, which has no desktop where the tray icon could show up
+fn ensure_interactive_session() -> TrayResult<()> {
+    ensure!(session_id()? != 0, TrayError::from(ErrorSource::NoInteractiveSession));
+    Ok(())
+}
+
+fn add_tray_icon(hwnd: HWND, tray_id: u32, shared: &Rc<SharedTrayData>) -> TrayResult<()> {
+    TrayIconData::from(shared)
+        .with_message(WM_USER_TRAY_ICON)
+        .apply(hwnd, tray_id, DataAction::Add)?;
+    TrayIconData::default()
+        .with_version(NOTIFYICON_VERSION_4)
+        .apply(hwnd, tray_id, DataAction::SetVersion)?;
+    Ok(())
+}
 
 #[allow(non_snake_case)]
 pub fn LOWORD(dword: u32) -> u16 {
     (dword & 0xFFFF) as u16
 }
 
+#[allow(non_snake_case)]
+pub fn HIWORD(dword: u32) -> u16 {
+    (dword >> 16) as u16
+}
+
 static GLOBAL_TRAY_COUNTER: AtomicU32 = AtomicU32::new(1);
+static GLOBAL_TIMER_COUNTER: AtomicUsize = AtomicUsize::new(0);
+// The hidden window of the thread that runs the event loop, for waking it up from other threads
+static LOOP_WINDOW: AtomicIsize = AtomicIsize::new(0);
 
 static S_U_TASKBAR_RESTART: Lazy<u32> = Lazy::new(|| unsafe { RegisterWindowMessageW(w!("TaskbarCreated")) });
 
@@ -225,6 +742,13 @@ fn get_class_name() -> PCWSTR {
         let hinstance = get_instance_handle();
 
         unsafe extern "system" fn tray_icon_window_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+            // The window isn't registered yet while it is being created
+            if let Some(window) = MessageWindow::get().filter(|window| window.hwnd == hwnd) {
+                handle_message(&window, hwnd, msg, wparam, lparam);
+                if let Some(result) = window.run_hooks(msg, wparam, lparam) {
+                    return result;
+                }
+            }
             DefWindowProcW(hwnd, msg, wparam, lparam)
         }
 
@@ -274,16 +798,38 @@ impl From<PlatformError> for ErrorSource {
 }
 
 impl ClickType {
-    fn from_lparam(lparam: LPARAM) -> Option<Self> {
-        match lparam.0 as u32 {
+    fn from_event(event: u32, policy: ClickPolicy) -> Option<Self> {
+        match event {
             WM_LBUTTONUP => Some(Self::Left),
             WM_RBUTTONUP => Some(Self::Right),
-            WM_LBUTTONDBLCLK => Some(Self::Double),
+            // The second press of a double click is still followed by a WM_LBUTTONUP
+            WM_LBUTTONDBLCLK if policy == ClickPolicy::DetectDoubleClicks => Some(Self::Double),
             _ => None
         }
     }
 }
 
+impl From<RECT> for IconRect {
+    fn from(rect: RECT) -> Self {
+        Self {
+            x: rect.left,
+            y: rect.top,
+            width: (rect.right - rect.left).max(0) as u32,
+            height: (rect.bottom - rect.top).max(0) as u32,
+        }
+    }
+}
+
+impl MenuTrigger {
+    fn matches(self, click: ClickType) -> bool {
+        match self {
+            MenuTrigger::RightClick => click == ClickType::Right,
+            MenuTrigger::LeftClick => click == ClickType::Left,
+            MenuTrigger::AnyClick => matches!(click, ClickType::Left | ClickType::Right)
+        }
+    }
+}
+
 impl<T: AsRef<SharedTrayData>> From<T> for TrayIconData {
     fn from(value: T) -> Self {
         let shared = value.as_ref();
@@ -292,7 +838,7 @@ impl<T: AsRef<SharedTrayData>> From<T> for TrayIconData {
             let t = data
                 .take()
                 .unwrap()
-                .with_tooltip(tooltip);
+                .with_tooltip(tooltip, !shared.flyout);
             data = Some(t);
         });
         shared.icon.with(|icon| {
@@ -0,0 +1,110 @@
+use std::fs::File;
+use std::mem::size_of;
+use std::os::windows::io::{AsRawHandle, FromRawHandle};
+use windows::core::{PCWSTR, PWSTR};
+use windows::Win32::Foundation::{CloseHandle, ERROR_PIPE_CONNECTED, FALSE, HANDLE, HLOCAL, LocalFree};
+use windows::Win32::Security::{GetTokenInformation, PSECURITY_DESCRIPTOR, SECURITY_ATTRIBUTES, TOKEN_QUERY, TOKEN_USER, TokenUser};
+use windows::Win32::Security::Authorization::{ConvertSidToStringSidW, ConvertStringSecurityDescriptorToSecurityDescriptorW, SDDL_REVISION_1};
+use windows::Win32::Storage::FileSystem::{FILE_FLAG_FIRST_PIPE_INSTANCE, FILE_FLAGS_AND_ATTRIBUTES};
+use windows::Win32::System::Pipes::{ConnectNamedPipe, CreateNamedPipeW, DisconnectNamedPipe, PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE, PIPE_WAIT};
+use windows::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+use crate::ensure;
+use crate::error::{TrayError, TrayResult};
+use crate::platform::windows::encode_wide;
+use crate::log;
+
+const BUFFER_SIZE: u32 = 64 * 1024;
+
+/// A security descriptor that was allocated by the system, which is freed when dropped
+struct SecurityDescriptor(PSECURITY_DESCRIPTOR);
+
+impl SecurityDescriptor {
+    /// Only grants access to the current user and the system, the default would also let other users read the pipe
+    fn current_user() -> TrayResult<Self> {
+        let sddl = encode_wide(&format!("D:P(A;;GA;;;{})(A;;GA;;;SY)", current_user_sid()?));
+        let mut descriptor = PSECURITY_DESCRIPTOR::default();
+        unsafe { ConvertStringSecurityDescriptorToSecurityDescriptorW(PCWSTR(sddl.as_ptr()), SDDL_REVISION_1, &mut descriptor, None)? };
+        Ok(Self(descriptor))
+    }
+}
+
+impl Drop for SecurityDescriptor {
+    fn drop(&mut self) {
+        unsafe {
+            LocalFree(HLOCAL(self.0.0 as _))
+                .map(|_| ())
+                .unwrap_or_else(|err| log::native_error!("Failed to free security descriptor: {err}"));
+        }
+    }
+}
+
+/// The SID of the user running this process in its string form
+fn current_user_sid() -> TrayResult<String> {
+    unsafe {
+        let mut token = HANDLE::default();
+        OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token)?;
+        let mut size = 0;
+        // The first call only reports the required size
+        let _ = GetTokenInformation(token, TokenUser, None, 0, &mut size);
+        // Aligned for the pointers in TOKEN_USER
+        let mut buffer = vec![0u64; (size as usize).div_ceil(size_of::<u64>())];
+        let result = GetTokenInformation(token, TokenUser, Some(buffer.as_mut_ptr().cast()), size, &mut size);
+        CloseHandle(token)
+            .unwrap_or_else(|err| log::native_error!("Failed to close process token: {err}"));
+        result?;
+        let user = &*buffer.as_ptr().cast::<TOKEN_USER>();
+        let mut sid = PWSTR::null();
+        ConvertSidToStringSidW(user.User.Sid, &mut sid)?;
+        let string = sid.to_string();
+        LocalFree(HLOCAL(sid.0 as _))
+            .map(|_| ())
+            .unwrap_or_else(|err| log::native_error!("Failed to free SID string: {err}"));
+        string.map_err(|err| TrayError::custom(format!("Invalid SID: {err}")))
+    }
+}
+
+/// Fails if another process already created a pipe with this name, as it could otherwise impersonate the application
+pub fn create_pipe(name: &str, access: FILE_FLAGS_AND_ATTRIBUTES) -> TrayResult<File> {
+    let name = encode_wide(name);
+    let descriptor = SecurityDescriptor::current_user()?;
+    let attributes = SECURITY_ATTRIBUTES {
+        nLength: size_of::<SECURITY_ATTRIBUTES>() as u32,
+        lpSecurityDescriptor: descriptor.0.0,
+        bInheritHandle: FALSE
+    };
+    let handle = unsafe {
+        CreateNamedPipeW(
+            PCWSTR(name.as_ptr()),
+            access | FILE_FLAG_FIRST_PIPE_INSTANCE,
+            PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+            1,
+            BUFFER_SIZE,
+            BUFFER_SIZE,
+            0,
+            Some(&attributes as *const _)
+        )
+    };
+    ensure!(!handle.is_invalid(), TrayError::from(windows::core::Error::from_win32()));
+    Ok(unsafe { File::from_raw_handle(handle.0 as _) })
+}
+
+fn handle(pipe: &File) -> HANDLE {
+    HANDLE(pipe.as_raw_handle() as _)
+}
+
+/// Blocks until a client connects to the pipe
+pub fn accept(pipe: &File) -> TrayResult<()> {
+    match unsafe { ConnectNamedPipe(handle(pipe), None) } {
+        Ok(()) => Ok(()),
+        // The client connected between creating the pipe and waiting for it
+        Err(err) if err.code() == ERROR_PIPE_CONNECTED.to_hresult() => Ok(()),
+        Err(err) => Err(err.into())
+    }
+}
+
+/// Drops the current client, keeping the pipe so that no other process can take over the name in the meantime
+pub fn disconnect(pipe: &File) -> bool {
+    unsafe { DisconnectNamedPipe(handle(pipe)) }
+        .map_err(|err| log::native_error!("Failed to disconnect pipe client: {err}"))
+        .is_ok()
+}
@@ -0,0 +1,134 @@
+#![allow(non_snake_case)]
+
+use std::ffi::c_void;
+use std::mem::size_of_val;
+use windows::core::{GUID, HRESULT, interface, IUnknown, IUnknown_Vtbl, PCWSTR, PWSTR, w};
+use windows::Win32::Foundation::{BOOL, ERROR_NO_MORE_ITEMS, HWND};
+use windows::Win32::System::Com::{CLSCTX_LOCAL_SERVER, CoCreateInstance};
+use windows::Win32::System::Registry::{HKEY, HKEY_CURRENT_USER, KEY_READ, KEY_SET_VALUE, REG_DWORD, RegCloseKey, RegEnumKeyExW, RegGetValueW, RegOpenKeyExW, RegSetKeyValueW, RRF_RT_REG_SZ};
+use windows::Win32::UI::WindowsAndMessaging::HICON;
+use crate::ensure;
+use crate::error::{TrayError, TrayResult};
+use crate::platform::windows::com::ComGuard;
+use crate::platform::windows::encode_wide;
+use crate::log;
+
+// The tray notification interfaces are undocumented, but have been stable since Windows 7
+const CLSID_TRAY_NOTIFY: GUID = GUID::from_u128(0x25DEAD04_1EAC_4911_9E3A_AD0A4AB560FD);
+
+const PREFERENCE_SHOW_ALWAYS: u32 = 2;
+
+// Windows 11 keeps the promotion state of every icon in a subkey of this key
+const NOTIFY_ICON_SETTINGS: PCWSTR = w!(r"Control Panel\NotifyIconSettings");
+
+#[repr(C)]
+struct NotifyItem {
+    exe_name: PWSTR,
+    tip: PWSTR,
+    icon: HICON,
+    hwnd: HWND,
+    preference: u32,
+    id: u32,
+    guid: GUID
+}
+
+/// Windows 8 and later
+#[interface("D133CE13-3537-48BA-93A7-AFCD5D2053B4")]
+unsafe trait ITrayNotify: IUnknown {
+    fn RegisterCallback(&self, callback: *mut c_void, handle: *mut u32) -> HRESULT;
+    fn UnregisterCallback(&self, handle: u32) -> HRESULT;
+    fn SetPreference(&self, item: *const NotifyItem) -> HRESULT;
+    fn EnableAutoTray(&self, enabled: BOOL) -> HRESULT;
+    fn DoAction(&self, action: BOOL) -> HRESULT;
+}
+
+/// Windows 7
+#[interface("FB852B2C-6BAD-4605-9551-F15F87830935")]
+unsafe trait ITrayNotifyWin7: IUnknown {
+    fn RegisterCallback(&self, callback: *mut c_void) -> HRESULT;
+    fn SetPreference(&self, item: *const NotifyItem) -> HRESULT;
+    fn EnableAutoTray(&self, enabled: BOOL) -> HRESULT;
+}
+
+/// Asks the shell to always show the icon in the taskbar instead of the overflow area
+///
+/// Newer versions of Windows may silently ignore this, so check [is_in_overflow](super::tray::is_in_overflow) afterward.
+/// Without the `ITrayNotify` interface, the promotion setting of Windows 11 is changed instead.
+pub fn request_promotion(hwnd: HWND, tray_id: u32) -> TrayResult<()> {
+    let exe = std::env::current_exe()
+        .map_err(|err| TrayError::custom(format!("Failed to get the path of the executable: {err}")))?
+        .to_string_lossy()
+        .into_owned();
+    let mut wide_exe = encode_wide(&exe);
+    let item = NotifyItem {
+        exe_name: PWSTR(wide_exe.as_mut_ptr()),
+        tip: PWSTR::null(),
+        icon: HICON::default(),
+        hwnd,
+        preference: PREFERENCE_SHOW_ALWAYS,
+        id: tray_id,
+        guid: GUID::zeroed(),
+    };
+    let _com = ComGuard::new();
+    unsafe {
+        if let Ok(notify) = CoCreateInstance::<_, ITrayNotify>(&CLSID_TRAY_NOTIFY, None, CLSCTX_LOCAL_SERVER) {
+            return Ok(notify.SetPreference(&item).ok()?);
+        }
+        if let Ok(notify) = CoCreateInstance::<_, ITrayNotifyWin7>(&CLSID_TRAY_NOTIFY, None, CLSCTX_LOCAL_SERVER) {
+            return Ok(notify.SetPreference(&item).ok()?);
+        }
+    }
+    log::debug!("ITrayNotify is not available, falling back to the notify icon settings");
+    promote_in_settings(&exe)
+}
+
+/// An open registry key, which is closed when dropped
+struct RegistryKey(HKEY);
+
+impl Drop for RegistryKey {
+    fn drop(&mut self) {
+        unsafe {
+            RegCloseKey(self.0)
+                .ok()
+                .unwrap_or_else(|err| log::native_error!("Failed to close registry key: {err}"));
+        }
+    }
+}
+
+/// Sets `IsPromoted` on the entries of the executable in the notify icon settings of Windows 11
+///
+/// The shell creates the entries once an icon has been shown. Executables in well-known folders may be stored
+/// with the id of the folder instead of its path, which this doesn't resolve.
+fn promote_in_settings(exe: &str) -> TrayResult<()> {
+    let mut key = HKEY::default();
+    unsafe { RegOpenKeyExW(HKEY_CURRENT_USER, NOTIFY_ICON_SETTINGS, 0, KEY_READ | KEY_SET_VALUE, &mut key).ok()? };
+    let key = RegistryKey(key);
+    let mut promoted = false;
+    for index in 0.. {
+        let mut name = [0u16; 256];
+        let mut name_len = name.len() as u32;
+        match unsafe { RegEnumKeyExW(key.0, index, PWSTR(name.as_mut_ptr()), &mut name_len, None, PWSTR::null(), None, None) } {
+            ERROR_NO_MORE_ITEMS => break,
+            result => result.ok()?
+        }
+        let subkey = PCWSTR(name.as_ptr());
+        let mut path = [0u16; 1024];
+        let mut path_size = size_of_val(&path) as u32;
+        let result = unsafe {
+            RegGetValueW(key.0, subkey, w!("ExecutablePath"), RRF_RT_REG_SZ, None, Some(path.as_mut_ptr().cast()), Some(&mut path_size))
+        };
+        if result.is_err() {
+            continue;
+        }
+        let path = String::from_utf16_lossy(&path[..(path_size as usize / 2).saturating_sub(1)]);
+        if path.eq_ignore_ascii_case(exe) {
+            let value = 1u32;
+            unsafe {
+                RegSetKeyValueW(key.0, subkey, w!("IsPromoted"), REG_DWORD.0, Some((&value as *const u32).cast()), size_of_val(&value) as u32).ok()?
+            };
+            promoted = true;
+        }
+    }
+    ensure!(promoted, TrayError::custom("The shell has no notify icon settings for this executable"));
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+use windows::Win32::Storage::FileSystem::{PIPE_ACCESS_INBOUND, PIPE_ACCESS_OUTBOUND};
+use crate::error::{TrayError, TrayResult};
+use crate::platform::windows::pipe::{accept, create_pipe, disconnect};
+use crate::remote::{read_request, Update, UpdateSender};
+use crate::log;
+
+/// How often the event thread checks whether it should stop while no events arrive
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+pub type EventSender = Sender<String>;
+
+/// Serves one pipe for requests and one for events, as a blocking pipe can't be read and written at the same time
+pub struct RemoteTransport {
+    stop: Arc<AtomicBool>,
+    requests: String,
+    events: String
+}
+
+impl RemoteTransport {
+    pub fn start(name: &str, updates: UpdateSender) -> TrayResult<(Self, EventSender)> {
+        let requests = format!(r"\\.\pipe\{name}.requests");
+        let events = format!(r"\\.\pipe\{name}.events");
+        // The pipes are created here, so that a name that is already taken is reported to the caller
+        let request_pipe = create_pipe(&requests, PIPE_ACCESS_INBOUND)?;
+        let event_pipe = create_pipe(&events, PIPE_ACCESS_OUTBOUND)?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::channel();
+        std::thread::Builder::new()
+            .name(String::from("remote requests"))
+            .spawn({
+                let stop = stop.clone();
+                move || serve_requests(request_pipe, &updates, &stop)
+            })
+            .map_err(|err| TrayError::custom(format!("Failed to spawn thread: {err}")))?;
+        std::thread::Builder::new()
+            .name(String::from("remote events"))
+            .spawn({
+                let stop = stop.clone();
+                move || serve_events(event_pipe, &receiver, &stop)
+            })
+            .map_err(|err| TrayError::custom(format!("Failed to spawn thread: {err}")))?;
+        Ok((Self { stop, requests, events }, sender))
+    }
+}
+
+impl Drop for RemoteTransport {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        // Threads that wait for a client are woken up by connecting to them. This fails for pipes that already have
+        // a client, but those threads notice once the client disconnects.
+        let _ = OpenOptions::new().write(true).open(&self.requests);
+        let _ = OpenOptions::new().read(true).open(&self.events);
+    }
+}
+
+fn serve_requests(pipe: File, updates: &UpdateSender, stop: &AtomicBool) {
+    loop {
+        if let Err(err) = accept(&pipe) {
+            log::native_error!("Failed to wait for a remote client: {err}");
+            return;
+        }
+        if stop.load(Ordering::Acquire) {
+            return;
+        }
+        // Reading fails once the client disconnects
+        let mut reader = BufReader::new(&pipe);
+        let mut line = Vec::new();
+        while let Some(request) = read_request(&mut reader, &mut line) {
+            match request.and_then(Update::try_from) {
+                Ok(update) => if updates.send(update).is_err() {
+                    return;
+                },
+                Err(err) => log::warn!("Ignoring remote request: {err}")
+            }
+        }
+        if stop.load(Ordering::Acquire) || !disconnect(&pipe) {
+            return;
+        }
+    }
+}
+
+fn serve_events(pipe: File, events: &Receiver<String>, stop: &AtomicBool) {
+    loop {
+        if let Err(err) = accept(&pipe) {
+            log::native_error!("Failed to wait for a remote client: {err}");
+            return;
+        }
+        // Events from before the client connected are outdated
+        while events.try_recv().is_ok() {}
+        loop {
+            if stop.load(Ordering::Acquire) {
+                return;
+            }
+            match events.recv_timeout(STOP_POLL_INTERVAL) {
+                // Writing fails once the client disconnects
+                Ok(event) => if writeln!(&pipe, "{event}").is_err() {
+                    break;
+                },
+                Err(RecvTimeoutError::Timeout) => {},
+                Err(RecvTimeoutError::Disconnected) => return
+            }
+        }
+        if !disconnect(&pipe) {
+            return;
+        }
+    }
+}
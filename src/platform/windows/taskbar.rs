@@ -0,0 +1,32 @@
+use windows::Win32::Foundation::HWND;
+use windows::Win32::System::Com::{CLSCTX_INPROC_SERVER, CoCreateInstance};
+use windows::Win32::UI::Shell::{ITaskbarList3, TaskbarList, TBPF_ERROR, TBPF_INDETERMINATE, TBPF_NOPROGRESS, TBPF_NORMAL, TBPF_PAUSED};
+use crate::error::TrayResult;
+use crate::platform::windows::com::ComGuard;
+use crate::TaskbarProgress;
+
+/// The resolution of the progress bar
+const PROGRESS_STEPS: u64 = 10000;
+
+/// Shows `progress` on the taskbar button of `hwnd`
+pub fn set_progress(hwnd: HWND, progress: TaskbarProgress) -> TrayResult<()> {
+    let (state, value) = match progress {
+        TaskbarProgress::None => (TBPF_NOPROGRESS, None),
+        TaskbarProgress::Indeterminate => (TBPF_INDETERMINATE, None),
+        TaskbarProgress::Normal(value) => (TBPF_NORMAL, Some(value)),
+        TaskbarProgress::Paused(value) => (TBPF_PAUSED, Some(value)),
+        TaskbarProgress::Error(value) => (TBPF_ERROR, Some(value))
+    };
+    let _com = ComGuard::new();
+    unsafe {
+        let taskbar = CoCreateInstance::<_, ITaskbarList3>(&TaskbarList, None, CLSCTX_INPROC_SERVER)?;
+        taskbar.HrInit()?;
+        // Setting the value switches the bar to the normal state, so it has to come first
+        if let Some(value) = value {
+            let completed = (value.clamp(0.0, 1.0) * PROGRESS_STEPS as f32).round() as u64;
+            taskbar.SetProgressValue(hwnd, completed, PROGRESS_STEPS)?;
+        }
+        taskbar.SetProgressState(hwnd, state)?;
+    }
+    Ok(())
+}
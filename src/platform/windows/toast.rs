@@ -0,0 +1,135 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use windows::core::{ComInterface, HSTRING, IInspectable, PCWSTR, w};
+use windows::Data::Xml::Dom::XmlDocument;
+use windows::Foundation::TypedEventHandler;
+use windows::UI::Notifications::{ToastActivatedEventArgs, ToastDismissedEventArgs, ToastFailedEventArgs, ToastNotification, ToastNotificationManager};
+use windows::Win32::Foundation::{HWND, LPARAM, WPARAM};
+use windows::Win32::System::Registry::{HKEY_CURRENT_USER, REG_SZ, RegSetKeyValueW};
+use windows::Win32::UI::Shell::SetCurrentProcessExplicitAppUserModelID;
+use windows::Win32::UI::WindowsAndMessaging::PostMessageW;
+use crate::{Notification, NotificationEvent};
+use crate::error::TrayResult;
+use crate::platform::windows::encode_wide;
+
+const TOAST_CLICKED: usize = 0;
+const TOAST_ACTION: usize = 1;
+const TOAST_DISMISSED: usize = 2;
+const TOAST_FAILED: usize = 3;
+
+/// Registers `app_id` for the current user and assigns it to the process, so toasts show up without a start menu shortcut
+///
+/// `display_name` is shown as the sender of the toasts.
+pub fn register_app_id(app_id: &str, display_name: &str) -> TrayResult<()> {
+    let key = encode_wide(&format!(r"Software\Classes\AppUserModelId\{app_id}"));
+    let data: Vec<u8> = encode_wide(display_name)
+        .into_iter()
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    unsafe {
+        RegSetKeyValueW(HKEY_CURRENT_USER, PCWSTR(key.as_ptr()), w!("DisplayName"), REG_SZ.0, Some(data.as_ptr().cast()), data.len() as u32).ok()?;
+        SetCurrentProcessExplicitAppUserModelID(&HSTRING::from(app_id))?;
+    }
+    Ok(())
+}
+
+/// The toasts of a tray icon that are still shown
+///
+/// The event handlers are only invoked as long as the [ToastNotification] is alive.
+#[derive(Default)]
+pub struct ActiveToasts {
+    next_id: Cell<u16>,
+    toasts: RefCell<HashMap<u16, ToastNotification>>
+}
+
+impl ActiveToasts {
+
+    /// Shows the notification as a toast under `app_id`, which must be registered by [register_app_id].
+    /// The events are posted to `hwnd` as `message` as the handlers run on a thread pool.
+    pub fn show(&self, hwnd: HWND, message: u32, tray_id: u32, app_id: &str, notification: &Notification) -> TrayResult<()> {
+        let toast_id = self.next_id.get();
+        self.next_id.set(toast_id.wrapping_add(1));
+        let toast = create_toast(hwnd, message, tray_id, toast_id, notification)?;
+        ToastNotificationManager::CreateToastNotifierWithId(&HSTRING::from(app_id))?
+            .Show(&toast)?;
+        self.toasts.borrow_mut().insert(toast_id, toast);
+        Ok(())
+    }
+
+    /// Forgets the toast once it is gone and returns the event that should be emitted, if any
+    pub fn handle_event(&self, wparam: WPARAM, lparam: LPARAM) -> Option<NotificationEvent> {
+        let toast_id = (lparam.0 >> 16) as u16;
+        let index = lparam.0 as usize & 0xFFFF;
+        // Every event ends the toast, activating it removes it from the screen as well
+        self.toasts.borrow_mut().remove(&toast_id);
+        match wparam.0 & 0xFFFF {
+            TOAST_CLICKED => Some(NotificationEvent::Clicked),
+            TOAST_ACTION => Some(NotificationEvent::Action(index)),
+            TOAST_DISMISSED => Some(NotificationEvent::Dismissed),
+            _ => None
+        }
+    }
+
+}
+
+fn create_toast(hwnd: HWND, message: u32, tray_id: u32, toast_id: u16, notification: &Notification) -> TrayResult<ToastNotification> {
+    let xml = XmlDocument::new()?;
+    xml.LoadXml(&HSTRING::from(toast_xml(notification)))?;
+    let toast = ToastNotification::CreateToastNotification(&xml)?;
+    let post = move |kind: usize, index: usize| unsafe {
+        PostMessageW(hwnd, message, encode_wparam(tray_id, kind), LPARAM(((toast_id as usize) << 16 | index) as isize))
+    };
+    toast.Activated(&TypedEventHandler::new(move |_, args: &Option<IInspectable>| {
+        let action = args
+            .as_ref()
+            .and_then(|args| args.cast::<ToastActivatedEventArgs>().ok())
+            .and_then(|args| args.Arguments().ok())
+            .and_then(|arguments| arguments.to_string().parse::<usize>().ok());
+        match action {
+            Some(index) => post(TOAST_ACTION, index),
+            None => post(TOAST_CLICKED, 0)
+        }
+    }))?;
+    toast.Dismissed(&TypedEventHandler::new(move |_, _: &Option<ToastDismissedEventArgs>| {
+        post(TOAST_DISMISSED, 0)
+    }))?;
+    toast.Failed(&TypedEventHandler::new(move |_, _: &Option<ToastFailedEventArgs>| {
+        post(TOAST_FAILED, 0)
+    }))?;
+    Ok(toast)
+}
+
+fn encode_wparam(tray_id: u32, kind: usize) -> WPARAM {
+    WPARAM((tray_id as usize) << 16 | kind)
+}
+
+/// The id of the tray icon that showed the toast
+pub fn decode_tray_id(wparam: WPARAM) -> u32 {
+    (wparam.0 >> 16) as u32
+}
+
+fn toast_xml(notification: &Notification) -> String {
+    let actions: String = notification
+        .actions
+        .iter()
+        .enumerate()
+        .map(|(i, label)| format!(r#"<action content="{}" arguments="{i}"/>"#, escape_xml(label)))
+        .collect();
+    let audio = match notification.sound {
+        true => "",
+        false => r#"<audio silent="true"/>"#
+    };
+    format!(
+        r#"<toast><visual><binding template="ToastGeneric"><text>{}</text><text>{}</text></binding></visual><actions>{actions}</actions>{audio}</toast>"#,
+        escape_xml(&notification.title),
+        escape_xml(&notification.body))
+}
+
+fn escape_xml(string: &str) -> String {
+    string
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
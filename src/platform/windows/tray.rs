@@ -1,13 +1,18 @@
 use std::mem::size_of;
-use windows::Win32::Foundation::HWND;
-use windows::Win32::UI::Shell::{NIF_ICON, NIF_MESSAGE, NIF_TIP, NIM_ADD, NIM_DELETE, NIM_MODIFY, NOTIFY_ICON_MESSAGE, NOTIFYICONDATAW, Shell_NotifyIconW};
-use windows::Win32::UI::WindowsAndMessaging::HICON;
-use crate::error::TrayResult;
+use windows::core::{PCWSTR, w};
+use windows::Win32::Foundation::{HWND, RECT};
+use windows::Win32::UI::Shell::{NIF_ICON, NIF_INFO, NIF_MESSAGE, NIF_SHOWTIP, NIIF_INFO, NIIF_LARGE_ICON, NIIF_NOSOUND, NIIF_USER, NIF_TIP, NIM_ADD, NIM_DELETE, NIM_MODIFY, NIM_SETVERSION, NOTIFY_ICON_MESSAGE, NOTIFYICONDATAW, NOTIFYICONDATAW_0, NOTIFYICONIDENTIFIER, Shell_NotifyIconGetRect, Shell_NotifyIconW};
+use windows::Win32::UI::WindowsAndMessaging::{FindWindowW, GetWindowRect, HICON};
+use crate::ensure;
+use crate::label::truncate_graphemes;
+use crate::error::{TrayError, TrayResult};
+use crate::Notification;
 
 pub enum DataAction {
     Add,
     Modify,
-    Remove
+    Remove,
+    SetVersion
 }
 
 impl From<DataAction> for NOTIFY_ICON_MESSAGE {
@@ -15,7 +20,8 @@ impl From<DataAction> for NOTIFY_ICON_MESSAGE {
         match value {
             DataAction::Add => NIM_ADD,
             DataAction::Modify => NIM_MODIFY,
-            DataAction::Remove => NIM_DELETE
+            DataAction::Remove => NIM_DELETE,
+            DataAction::SetVersion => NIM_SETVERSION
         }
     }
 }
@@ -39,20 +45,46 @@ impl TrayIconData {
         self
     }
 
+    pub fn with_version(mut self, version: u32) -> Self {
+        self.0.Anonymous = NOTIFYICONDATAW_0 {
+            uVersion: version,
+        };
+        self
+    }
+
     pub fn with_icon(mut self, icon: HICON) -> Self {
         self.0.uFlags |= NIF_ICON;
         self.0.hIcon = icon;
         self
     }
 
-    pub fn with_tooltip<S: AsRef<str>>(mut self, tooltip: S) -> Self {
+    pub fn with_tooltip<S: AsRef<str>>(mut self, tooltip: S, standard: bool) -> Self {
+        // Version 4 icons only show the standard tooltip when explicitly requested
         self.0.uFlags |= NIF_TIP;
-        tooltip
-            .as_ref()
-            .encode_utf16()
-            .take(self.0.szTip.len() - 1)
-            .enumerate()
-            .for_each(|(i, c)| self.0.szTip[i] = c);
+        if standard {
+            self.0.uFlags |= NIF_SHOWTIP;
+        }
+        copy_wide(&mut self.0.szTip, tooltip.as_ref());
+        self
+    }
+
+    pub fn with_notification(mut self, notification: &Notification) -> Self {
+        self.0.uFlags |= NIF_INFO;
+        self.0.dwInfoFlags = match &notification.icon {
+            Some(icon) => {
+                self.0.hBalloonIcon = icon.0.handle();
+                match notification.large_icon {
+                    true => NIIF_USER | NIIF_LARGE_ICON,
+                    false => NIIF_USER
+                }
+            }
+            None => NIIF_INFO
+        };
+        if !notification.sound {
+            self.0.dwInfoFlags |= NIIF_NOSOUND;
+        }
+        copy_wide(&mut self.0.szInfoTitle, &notification.title);
+        copy_wide(&mut self.0.szInfo, &notification.body);
         self
     }
 
@@ -63,4 +95,37 @@ impl TrayIconData {
         Ok(())
     }
 
-}
\ No newline at end of file
+}
+
+/// Returns the screen coordinates of the icon
+pub fn icon_rect(hwnd: HWND, id: u32) -> TrayResult<RECT> {
+    let identifier = NOTIFYICONIDENTIFIER {
+        cbSize: size_of::<NOTIFYICONIDENTIFIER>() as u32,
+        hWnd: hwnd,
+        uID: id,
+        ..Default::default()
+    };
+    Ok(unsafe { Shell_NotifyIconGetRect(&identifier)? })
+}
+
+/// Checks whether the icon is hidden in the overflow flyout instead of being shown on the taskbar
+pub fn is_in_overflow(hwnd: HWND, id: u32) -> TrayResult<bool> {
+    let icon = icon_rect(hwnd, id)?;
+    unsafe {
+        let taskbar = FindWindowW(w!("Shell_TrayWnd"), PCWSTR::null());
+        ensure!(taskbar.0 != 0, TrayError::custom("Failed to find the taskbar"));
+        let mut bounds = RECT::default();
+        GetWindowRect(taskbar, &mut bounds)?;
+        // Icons in the overflow area report the position inside the flyout, even while it is closed
+        let on_taskbar = icon.left < bounds.right && icon.right > bounds.left && icon.top < bounds.bottom && icon.bottom > bounds.top;
+        Ok(!on_taskbar)
+    }
+}
+
+/// Copies the string into the fixed size buffer, truncating it on a grapheme boundary if it doesn't fit
+fn copy_wide(target: &mut [u16], string: &str) {
+    let string = truncate_graphemes(string, target.len() - 1, |g| g.encode_utf16().count());
+    let wide: Vec<u16> = string.encode_utf16().collect();
+    target[..wide.len()].copy_from_slice(&wide);
+    target[wide.len()] = 0;
+}
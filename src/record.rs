@@ -0,0 +1,150 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, LineWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use crate::error::{TrayError, TrayResult};
+use crate::TrayEvent;
+use crate::log;
+
+/// A single line of a recording
+#[derive(Serialize, Deserialize)]
+struct Record<E> {
+    /// Milliseconds since the start of the recording
+    time: u64,
+    event: E
+}
+
+/// Wraps an event callback so that every event is also appended to the file at `path`
+///
+/// The file contains one JSON object per line with the event and the time it arrived at.
+/// It's written line by line, so it stays usable even if the application crashes.
+/// Pass the result to [TrayIconBuilder::build](crate::TrayIconBuilder::build) and use [replay_events] to feed
+/// the events back into the callback later.
+pub fn record_events<T, F, R, P>(path: P, mut callback: F) -> TrayResult<impl FnMut(TrayEvent<T>) -> R + Send + 'static>
+    where T: Serialize, F: FnMut(TrayEvent<T>) -> R + Send + 'static, P: AsRef<Path>
+{
+    let path = path.as_ref();
+    let mut writer = File::create(path)
+        .map(LineWriter::new)
+        .map_err(|err| TrayError::custom(format!("Failed to create {path:?}: {err}")))?;
+    let start = Instant::now();
+    Ok(move |event: TrayEvent<T>| {
+        let record = Record {
+            time: start.elapsed().as_millis() as u64,
+            event: &event
+        };
+        serde_json::to_writer(&mut writer, &record)
+            .map_err(std::io::Error::from)
+            .and_then(|_| writer.write_all(b"\n"))
+            .unwrap_or_else(|err| log::warn!("Failed to record event: {err}"));
+        callback(event)
+    })
+}
+
+/// Reads the events recorded by [record_events] together with the time they arrived at, relative to the start of the recording
+pub fn read_events<T, P>(path: P) -> TrayResult<impl Iterator<Item=TrayResult<(Duration, TrayEvent<T>)>>>
+    where T: DeserializeOwned, P: AsRef<Path>
+{
+    let path = path.as_ref();
+    let reader = File::open(path)
+        .map(BufReader::new)
+        .map_err(|err| TrayError::custom(format!("Failed to open {path:?}: {err}")))?;
+    Ok(reader
+        .lines()
+        .filter(|line| !line.as_ref().is_ok_and(|line| line.trim().is_empty()))
+        .map(|line| {
+            let line = line
+                .map_err(|err| TrayError::custom(format!("Failed to read recording: {err}")))?;
+            let record: Record<TrayEvent<T>> = serde_json::from_str(&line)
+                .map_err(|err| TrayError::custom(format!("Invalid recorded event: {err}")))?;
+            Ok((Duration::from_millis(record.time), record.event))
+        }))
+}
+
+/// Feeds the events recorded by [record_events] into `callback`, keeping the original time between them
+///
+/// Stops at the first line that can't be read.
+pub fn replay_events<T, F, R, P>(path: P, mut callback: F) -> TrayResult<()>
+    where T: DeserializeOwned, F: FnMut(TrayEvent<T>) -> R, P: AsRef<Path>
+{
+    let start = Instant::now();
+    for record in read_events(path)? {
+        let (time, event) = record?;
+        if let Some(delay) = time.checked_sub(start.elapsed()) {
+            std::thread::sleep(delay);
+        }
+        callback(event);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use crate::ClickType;
+    use super::*;
+
+    /// A file in the temporary directory that is removed again when dropped
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            Self(std::env::temp_dir().join(format!("betrayer-{}-{name}.jsonl", std::process::id())))
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn events() -> Vec<TrayEvent<String>> {
+        vec![
+            TrayEvent::Tray { click: ClickType::Left, activation_token: None },
+            TrayEvent::Menu { signal: String::from("quit"), activation_token: Some(String::from("token")) },
+            TrayEvent::SessionEnding
+        ]
+    }
+
+    fn record(file: &TempFile) -> Vec<TrayEvent<String>> {
+        let (sender, forwarded) = std::sync::mpsc::channel();
+        let mut callback = record_events(&file.0, move |event| sender.send(event).unwrap()).unwrap();
+        for event in events() {
+            callback(event);
+        }
+        drop(callback);
+        forwarded.try_iter().collect()
+    }
+
+    #[test]
+    fn recorded_events_are_forwarded() {
+        let file = TempFile::new("forwarded");
+        assert_eq!(record(&file), events());
+    }
+
+    #[test]
+    fn recorded_events_can_be_read() {
+        let file = TempFile::new("read");
+        record(&file);
+        let recorded: Vec<(Duration, TrayEvent<String>)> = read_events(&file.0)
+            .unwrap()
+            .collect::<TrayResult<_>>()
+            .unwrap();
+        assert_eq!(recorded.iter().map(|(_, event)| event.clone()).collect::<Vec<_>>(), events());
+        assert!(recorded.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+    }
+
+    #[test]
+    fn replay_stops_at_invalid_lines() {
+        let file = TempFile::new("replay");
+        std::fs::write(&file.0, "{\"time\":0,\"event\":\"Resumed\"}\n\n{\"time\":1,\"event\":\"Activate\"}\nnot json\n").unwrap();
+        let mut replayed = Vec::new();
+        let result = replay_events(&file.0, |event: TrayEvent<String>| replayed.push(event));
+        assert!(result.is_err());
+        assert_eq!(replayed, [TrayEvent::Resumed, TrayEvent::Activate]);
+    }
+
+}
@@ -0,0 +1,248 @@
+//! Lets another process control a tray icon
+//!
+//! This is meant for applications that are split into a privileged daemon and an unprivileged helper that runs in the
+//! user session and shows the tray icon. The helper creates the tray icon with [serve] and the daemon then changes it
+//! with [RemoteRequest]s and receives its events as JSON serialized [TrayEvent]s.
+//!
+//! On *Linux* the tray icon is exposed on the session bus under the given well-known name at [DBUS_PATH] with the
+//! [DBUS_INTERFACE] interface. It has the methods `SetTooltip(s)`, `SetTitle(s)`, `SetIcon(u width, u height, ay rgba)`
+//! and `SetMenu(s json)` and emits every event with the `Event(s json)` signal. Empty strings remove the tooltip and title,
+//! an empty buffer removes the icon and the menu is a JSON array of [RemoteMenuItem]s or `null` to remove it.
+//! Only callers running as the same user or as root are accepted. Note that the session bus itself usually only accepts
+//! connections from the user it belongs to, so a system daemon has to connect with the uid of that user, e.g. from
+//! a child process that dropped its privileges, and use the `DBUS_SESSION_BUS_ADDRESS` of the session.
+//!
+//! On *Windows* the helper opens two named pipes: the daemon writes JSON serialized [RemoteRequest]s to
+//! `\\.\pipe\<name>.requests` and reads events from `\\.\pipe\<name>.events`, one per line.
+//! Only the current user and `SYSTEM` can open the pipes, so a daemon running as a service can connect while other users can't.
+//! Only one daemon can be connected to each pipe at a time, remote clients are rejected and clients sending requests longer
+//! than [MAX_REQUEST_LENGTH] are disconnected.
+
+use std::sync::{mpsc, Arc, OnceLock};
+use serde::{Deserialize, Serialize};
+use crate::{ControlFlow, Icon, Menu, MenuItem, TrayEvent, TrayIcon, TrayIconBuilder};
+use crate::error::TrayResult;
+use crate::platform::{RemoteTransport, TrayWaker};
+use crate::log;
+
+#[cfg(target_os = "linux")]
+pub use crate::platform::{DBUS_INTERFACE, DBUS_PATH};
+
+/// The maximum length of a request in bytes, which leaves enough room for large icons
+pub const MAX_REQUEST_LENGTH: usize = 4 * 1024 * 1024;
+
+/// A change to the tray icon requested by the controlling process
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub enum RemoteRequest {
+    SetTooltip(Option<String>),
+    SetTitle(Option<String>),
+    SetIcon(Option<RemoteIcon>),
+    SetMenu(Option<Vec<RemoteMenuItem>>)
+}
+
+/// An image in the format of [Icon::from_rgba]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct RemoteIcon {
+    pub rgba: Vec<u8>,
+    pub width: u32,
+    pub height: u32
+}
+
+/// A menu entry that can be sent by the controlling process
+///
+/// Selecting a button emits a [TrayEvent::Menu] with its `signal`. In JSON the kind of the entry is stored in the
+/// `type` field, e.g. `{"type": "button", "label": "Quit", "signal": "quit"}`.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RemoteMenuItem {
+    Separator,
+    Button {
+        label: String,
+        signal: String
+    },
+    CheckButton {
+        label: String,
+        signal: String,
+        checked: bool
+    },
+    Menu {
+        label: String,
+        children: Vec<RemoteMenuItem>
+    }
+}
+
+impl From<RemoteMenuItem> for MenuItem<String> {
+    fn from(value: RemoteMenuItem) -> Self {
+        match value {
+            RemoteMenuItem::Separator => MenuItem::separator(),
+            RemoteMenuItem::Button { label, signal } => MenuItem::button(label, signal),
+            RemoteMenuItem::CheckButton { label, signal, checked } => MenuItem::check_button(label, signal, checked),
+            RemoteMenuItem::Menu { label, children } => MenuItem::menu(label, children.into_iter().map(MenuItem::from))
+        }
+    }
+}
+
+/// A validated [RemoteRequest] on its way to the thread of the tray icon
+pub(crate) enum Update {
+    Tooltip(Option<String>),
+    Title(Option<String>),
+    Icon(Option<Icon>),
+    Menu(Option<Menu<String>>)
+}
+
+impl TryFrom<RemoteRequest> for Update {
+    type Error = crate::TrayError;
+
+    fn try_from(value: RemoteRequest) -> TrayResult<Self> {
+        Ok(match value {
+            RemoteRequest::SetTooltip(tooltip) => Update::Tooltip(tooltip),
+            RemoteRequest::SetTitle(title) => Update::Title(title),
+            RemoteRequest::SetIcon(icon) => Update::Icon(icon
+                .map(|icon| Icon::from_rgba(icon.rgba, icon.width, icon.height))
+                .transpose()?),
+            RemoteRequest::SetMenu(menu) => Update::Menu(menu.map(|items| Menu::new(items.into_iter().map(MenuItem::from))))
+        })
+    }
+}
+
+impl Update {
+    fn apply(self, tray: &TrayIcon<String>) {
+        match self {
+            Update::Tooltip(tooltip) => tray.set_tooltip::<String>(tooltip),
+            Update::Title(title) => tray.set_title::<String>(title),
+            Update::Icon(icon) => tray.set_icon(icon),
+            Update::Menu(menu) => tray.set_menu(menu)
+        };
+    }
+}
+
+/// Hands validated requests to the thread of the tray icon and wakes it up to apply them
+#[derive(Clone)]
+pub(crate) struct UpdateSender {
+    updates: mpsc::Sender<Update>,
+    waker: Arc<OnceLock<TrayWaker>>
+}
+
+impl UpdateSender {
+    pub(crate) fn send(&self, update: Update) -> Result<(), mpsc::SendError<Update>> {
+        self.updates.send(update)?;
+        // Requests that arrive before the tray icon exists are applied right after it's created
+        if let Some(waker) = self.waker.get() {
+            waker.wake();
+        }
+        Ok(())
+    }
+}
+
+/// Reads the next request of a stream of newline separated JSON requests, skipping empty lines
+///
+/// Returns `None` once the stream ended or failed, or a request exceeded [MAX_REQUEST_LENGTH],
+/// as the rest of the stream can't be split into requests anymore.
+#[cfg(any(target_os = "windows", test))]
+pub(crate) fn read_request<R: std::io::BufRead>(reader: &mut R, line: &mut Vec<u8>) -> Option<TrayResult<RemoteRequest>> {
+    use std::io::{BufRead, Read};
+    loop {
+        line.clear();
+        match reader.by_ref().take(MAX_REQUEST_LENGTH as u64 + 1).read_until(b'\n', line) {
+            Ok(0) | Err(_) => return None,
+            Ok(length) if length > MAX_REQUEST_LENGTH => {
+                log::warn!("Dropping a remote client after a request longer than {MAX_REQUEST_LENGTH} bytes");
+                return None;
+            }
+            Ok(_) => {}
+        }
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+        return Some(serde_json::from_slice(line)
+            .map_err(|err| crate::TrayError::custom(format!("Invalid request: {err}"))));
+    }
+}
+
+/// Keeps the tray icon reachable by other processes. Dropping it stops accepting requests.
+pub struct RemoteServer {
+    _transport: RemoteTransport
+}
+
+/// Creates the tray icon and lets another process control it under the name `name`
+///
+/// See the [module documentation](self) for the protocol. `name` is a well-known D-Bus name like `dev.example.Tray`
+/// on *Linux* and the base name of the pipes on *Windows*. Every event is sent to the controlling process before it's
+/// passed to `callback`, so the helper can still react to events itself.
+pub fn serve<F, R>(name: &str, builder: TrayIconBuilder<String>, mut callback: F) -> TrayResult<(TrayIcon<String>, RemoteServer)>
+    where F: FnMut(TrayEvent<String>) -> R + Send + 'static, R: Into<ControlFlow>
+{
+    let (updates, receiver) = mpsc::channel();
+    let waker = Arc::new(OnceLock::new());
+    let (transport, events) = RemoteTransport::start(name, UpdateSender { updates, waker: waker.clone() })?;
+    let tray = builder.build(move |event: TrayEvent<String>| {
+        match serde_json::to_string(&event) {
+            Ok(json) => {
+                // Nobody might be listening, which is fine
+                let _ = events.send(json);
+            }
+            Err(err) => log::warn!("Failed to serialize event: {err}")
+        }
+        callback(event)
+    })?;
+    let apply = move |tray: &TrayIcon<String>| {
+        for update in receiver.try_iter() {
+            update.apply(tray);
+        }
+    };
+    // Picks up the requests that arrived before the waker was set
+    waker
+        .get_or_init(|| tray.set_wakeup(apply))
+        .wake();
+    Ok((tray, RemoteServer { _transport: transport }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Cursor};
+    use super::*;
+
+    fn read_all(input: &[u8]) -> Vec<TrayResult<RemoteRequest>> {
+        let mut reader = BufReader::new(Cursor::new(input));
+        let mut line = Vec::new();
+        std::iter::from_fn(|| read_request(&mut reader, &mut line)).collect()
+    }
+
+    #[test]
+    fn requests_are_split_by_lines() {
+        let requests = read_all(b"{\"SetTooltip\":\"Hello\"}\n\n  \r\n{\"SetTitle\":null}");
+        let requests: Vec<RemoteRequest> = requests.into_iter().map(Result::unwrap).collect();
+        assert_eq!(requests, [RemoteRequest::SetTooltip(Some(String::from("Hello"))), RemoteRequest::SetTitle(None)]);
+    }
+
+    #[test]
+    fn invalid_requests_are_skipped() {
+        let requests = read_all(b"{\"Unknown\":1}\n{\"SetTitle\":\"Title\"}\n");
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].is_err());
+        assert_eq!(requests[1].as_ref().unwrap(), &RemoteRequest::SetTitle(Some(String::from("Title"))));
+    }
+
+    #[test]
+    fn overlong_requests_end_the_stream() {
+        let mut input = vec![b' '; MAX_REQUEST_LENGTH + 1];
+        input.extend_from_slice(b"\n{\"SetTitle\":null}\n");
+        assert!(read_all(&input).is_empty());
+    }
+
+    #[test]
+    fn menu_items_are_tagged_by_type() {
+        let json = r#"[{"type": "button", "label": "Quit", "signal": "quit"}, {"type": "separator"},
+            {"type": "menu", "label": "More", "children": [{"type": "check_button", "label": "A", "signal": "a", "checked": true}]}]"#;
+        let items: Vec<RemoteMenuItem> = serde_json::from_str(json).unwrap();
+        assert_eq!(items, [
+            RemoteMenuItem::Button { label: String::from("Quit"), signal: String::from("quit") },
+            RemoteMenuItem::Separator,
+            RemoteMenuItem::Menu {
+                label: String::from("More"),
+                children: vec![RemoteMenuItem::CheckButton { label: String::from("A"), signal: String::from("a"), checked: true }]
+            }
+        ]);
+    }
+
+}
@@ -0,0 +1,57 @@
+use std::ops::{BitOr, BitOrAssign};
+
+/// Modifier keys of a [Shortcut]
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct Modifiers(u8);
+
+impl Modifiers {
+    pub const NONE: Self = Self(0);
+    pub const SHIFT: Self = Self(1 << 0);
+    pub const CONTROL: Self = Self(1 << 1);
+    /// The *Option* key on Mac and the *Alt* key everywhere else
+    pub const OPTION: Self = Self(1 << 2);
+    /// The *Command* key on Mac and the *Super* key everywhere else
+    pub const COMMAND: Self = Self(1 << 3);
+
+    /// Checks if all modifiers in `other` are also set in `self`
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for Modifiers {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Modifiers {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// A keyboard shortcut for a menu item, like `⌘Q` for *Quit*
+///
+/// On *Mac* the shortcut is set as key equivalent and works while the menu is open.
+/// On *Linux* it is only displayed as hint next to the label, provided the host supports it.
+/// Ignored on *Windows*.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Shortcut {
+    pub key: char,
+    pub modifiers: Modifiers
+}
+
+impl Shortcut {
+
+    /// Creates a new shortcut. Letters should be lowercase, use [Modifiers::SHIFT] for uppercase ones.
+    pub fn new(key: char, modifiers: Modifiers) -> Self {
+        Self {
+            key,
+            modifiers,
+        }
+    }
+
+}
@@ -14,4 +14,50 @@ impl<T> OptionCellExt<T> for Cell<Option<T>> {
                 result
             })
     }
-}
\ No newline at end of file
+}
+/// Resizes an RGBA image by averaging the source pixels that each target pixel covers
+///
+/// Menus only stretch images with nearest neighbor or bilinear filtering, which looks jagged or blurry when scaling down.
+pub fn resize_rgba(rgba: &[u8], width: u32, height: u32, new_width: u32, new_height: u32) -> Vec<u8> {
+    if (width, height) == (new_width, new_height) {
+        return rgba.to_vec();
+    }
+    let (width, height) = (width as usize, height as usize);
+    let (new_width, new_height) = (new_width as usize, new_height as usize);
+    let mut resized = Vec::with_capacity(4 * new_width * new_height);
+    for y in 0..new_height {
+        let rows = covered_range(y, height, new_height);
+        for x in 0..new_width {
+            let columns = covered_range(x, width, new_width);
+            // Weighting the colors by their alpha avoids dark fringes around transparent areas
+            let mut sum = [0u64; 4];
+            for row in rows.clone() {
+                for pixel in rgba[4 * (row * width + columns.start)..4 * (row * width + columns.end)].chunks_exact(4) {
+                    let alpha = pixel[3] as u64;
+                    sum[0] += pixel[0] as u64 * alpha;
+                    sum[1] += pixel[1] as u64 * alpha;
+                    sum[2] += pixel[2] as u64 * alpha;
+                    sum[3] += alpha;
+                }
+            }
+            let count = (rows.len() * columns.len()) as u64;
+            match sum[3] {
+                0 => resized.extend_from_slice(&[0; 4]),
+                alpha => resized.extend_from_slice(&[
+                    (sum[0] / alpha) as u8,
+                    (sum[1] / alpha) as u8,
+                    (sum[2] / alpha) as u8,
+                    (alpha / count) as u8
+                ])
+            }
+        }
+    }
+    resized
+}
+
+/// The source pixels that are covered by the target pixel `i`, at least one wide
+fn covered_range(i: usize, size: usize, new_size: usize) -> std::ops::Range<usize> {
+    let start = (i * size / new_size).min(size - 1);
+    let end = ((i + 1) * size).div_ceil(new_size).clamp(start + 1, size);
+    start..end
+}
@@ -0,0 +1,121 @@
+use std::fmt::{Debug, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use png::{ColorType, Transformations};
+use crate::error::{TrayError, TrayResult};
+use crate::Icon;
+use crate::platform::NativeIcon;
+use crate::log;
+
+/// How often tray icons check whether the file of their icon changed
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Counts the changes to a file
+struct FileWatch {
+    path: PathBuf,
+    generation: Arc<AtomicU64>,
+    _watcher: RecommendedWatcher
+}
+
+impl FileWatch {
+    fn new(path: PathBuf) -> TrayResult<Self> {
+        let generation = Arc::new(AtomicU64::new(0));
+        let mut watcher = {
+            let generation = generation.clone();
+            let path = path.clone();
+            notify::recommended_watcher(move |event: notify::Result<Event>| match event {
+                Ok(event) if event.paths.contains(&path) && (event.kind.is_create() || event.kind.is_modify()) => {
+                    generation.fetch_add(1, Ordering::AcqRel);
+                }
+                Ok(_) => {}
+                Err(err) => log::warn!("Failed to watch icon file: {err}")
+            })
+        }.map_err(|err| TrayError::custom(format!("Failed to create file watcher: {err}")))?;
+        // Watching the directory keeps working when the file gets replaced instead of written to
+        let dir = path.parent().unwrap_or(Path::new("/"));
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(|err| TrayError::custom(format!("Failed to watch {dir:?}: {err}")))?;
+        Ok(Self {
+            path,
+            generation,
+            _watcher: watcher,
+        })
+    }
+}
+
+/// The file behind an icon created with [Icon::from_path_watched]
+#[derive(Clone)]
+pub struct WatchedFile {
+    watch: Arc<FileWatch>,
+    generation: u64
+}
+
+impl WatchedFile {
+
+    pub fn open(path: &Path) -> TrayResult<(Icon, Self)> {
+        let path = path
+            .canonicalize()
+            .map_err(|err| TrayError::custom(format!("Failed to resolve {path:?}: {err}")))?;
+        let watch = FileWatch::new(path)?;
+        let generation = watch.generation.load(Ordering::Acquire);
+        let icon = load_png(&watch.path)?;
+        Ok((icon, Self { watch: Arc::new(watch), generation }))
+    }
+
+    /// Loads the icon again if the file changed since it was last loaded
+    pub fn reload(&mut self) -> Option<NativeIcon> {
+        let generation = self.watch.generation.load(Ordering::Acquire);
+        if generation == self.generation {
+            return None;
+        }
+        self.generation = generation;
+        // A partially written file fails to decode, but finishing the write triggers another reload
+        load_png(&self.watch.path)
+            .map(NativeIcon::from)
+            .map_err(|err| log::debug!("Failed to reload icon from {:?}: {err}", self.watch.path))
+            .ok()
+    }
+
+}
+
+impl Debug for WatchedFile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("WatchedFile").field(&self.watch.path).finish()
+    }
+}
+
+impl PartialEq for WatchedFile {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.watch, &other.watch)
+    }
+}
+
+impl Eq for WatchedFile {}
+
+fn load_png(path: &Path) -> TrayResult<Icon> {
+    let file = std::fs::File::open(path)
+        .map_err(|err| TrayError::custom(format!("Failed to open {path:?}: {err}")))?;
+    let mut decoder = png::Decoder::new(std::io::BufReader::new(file));
+    decoder.set_transformations(Transformations::normalize_to_color8() | Transformations::ALPHA);
+    let mut reader = decoder
+        .read_info()
+        .map_err(|err| TrayError::custom(format!("Invalid png image: {err}")))?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut buffer)
+        .map_err(|err| TrayError::custom(format!("Invalid png image: {err}")))?;
+    buffer.truncate(info.buffer_size());
+    let rgba = match info.color_type {
+        ColorType::Rgba => buffer,
+        ColorType::GrayscaleAlpha => buffer
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        other => return Err(TrayError::custom(format!("Unsupported png color type: {other:?}")))
+    };
+    Icon::from_rgba(rgba, info.width, info.height)
+}
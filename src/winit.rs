@@ -1,5 +1,6 @@
 use winit::event_loop::EventLoop;
 use crate::{TrayEvent, TrayIcon, TrayIconBuilder, TrayResult};
+use crate::log;
 
 pub trait WinitTrayIconBuilderExt<T> {
     fn build_event_loop<E, F>(self, event_loop: &EventLoop<E>, filter_map: F) -> TrayResult<TrayIcon<T>>